@@ -1,48 +1,550 @@
 ﻿//! packs stuff into .tar archives, fingerprint.txt embedded so we can find it all again on restore
-use crate::helpers::{Progress, get_fingered};
-use crate::{dlog, elog};
-use std::io::BufWriter;
+use crate::crypt::{self, EncryptWriter};
+#[cfg(unix)]
+use crate::helpers::UnixIds;
+use crate::helpers::{
+    CHECKSUMS_ENTRY, ChecksumExport, JobLog, KONSERVE_CONFIG_ENTRY, ParentLink, Progress,
+    ProgressRead, Retention, UNCHANGED_ENTRY, archive_file_state, checksum_export_path,
+    destination_overlap, entry_human_path, exclude_pattern_matches, format_count, format_duration,
+    format_size, konserve_config_files, nested_selections, note_line, prune_archives,
+    render_checksums, render_manifest, render_unchanged, to_hex,
+};
+use crate::vss::ShadowSet;
+use crate::{dlog, elog, jlog};
+use std::io::{BufWriter, Write};
 use std::{
-    fs::File,
+    collections::HashMap,
+    fs::{self, File},
     io,
     path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use chrono::Local;
+use sha2::{Digest, Sha256};
 use tar::{Builder, Header};
 use uuid::Uuid;
 use walkdir::WalkDir;
 
-/// packs the selected files/folders into a .tar with fingerprint.txt embedded, returns the archive path
+/// backup_gui's error when the user hit cancel, the partial archive is gone by then
+pub const CANCELLED: &str = "cancelled";
+
+/// per-run knobs for backup_gui, everything that isn't the paths themselves
+#[derive(Clone, Default)]
+pub struct BackupOptions {
+    pub verbose: bool,
+    pub skip_locked: bool,
+    pub obfuscate_manifest: bool,
+    /// pack konserve's own config + template under the reserved konserve-config/ folder
+    pub include_konserve_config: bool,
+    /// files/folders under the selected roots that got unchecked in the backup tree
+    pub excluded: Vec<PathBuf>,
+    /// glob patterns for stuff inside the selected folders to leave out
+    pub exclude_patterns: Vec<String>,
+    /// build the archive here and move it into output_dir once it's finished
+    pub scratch_dir: Option<PathBuf>,
+    /// Some = write an encrypted archive with this passphrase
+    pub passphrase: Option<String>,
+    /// incremental: only pack what changed since this archive (same folder as the output)
+    pub parent: Option<PathBuf>,
+    /// parent is a picked baseline, the archive gets marked differential instead of incremental
+    pub differential: bool,
+    /// also write the checksums to <archive>.sha256 in this format
+    pub checksum_export: ChecksumExport,
+    /// also write <archive>.snapshot.json, see snapshot_json
+    pub snapshot_json: bool,
+    /// the destination's rotation, old timestamped archives get deleted once this one is done
+    pub retention: Retention,
+    /// notes the user attached to selected roots, end up in the manifest for the restore tree
+    pub notes: HashMap<PathBuf, String>,
+    /// read the roots from volume shadow copies (windows, needs admin), see vss
+    pub shadow_copy: bool,
+}
+
+impl BackupOptions {
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.excluded.iter().any(|e| path.starts_with(e))
+    }
+
+    /// whether walking the selected folder `root` skips `path`, because it or a folder on the way
+    /// down to it is excluded
+    fn left_out_of(&self, root: &Path, path: &Path) -> bool {
+        if !path.starts_with(root) {
+            return false;
+        }
+        path.ancestors()
+            .take_while(|p| *p != root)
+            .any(|p| self.is_excluded(p) || self.matching_pattern(root, p, p.is_dir()).is_some())
+    }
+
+    /// the first exclude pattern matching a walked entry, `root` being the selected folder
+    fn matching_pattern(&self, root: &Path, path: &Path, is_dir: bool) -> Option<&str> {
+        if self.exclude_patterns.is_empty() {
+            return None;
+        }
+        let rel = path.strip_prefix(root).ok()?;
+        let rel: Vec<_> = rel.iter().map(|c| c.to_string_lossy()).collect();
+        let rel = rel.join("/");
+        self.exclude_patterns
+            .iter()
+            .find(|p| exclude_pattern_matches(p, &rel, is_dir))
+            .map(String::as_str)
+    }
+}
+
+/// fifos, sockets and device nodes. reading one blocks forever or streams a whole disk, so
+/// they never get opened. Some(what it is) for those
+#[cfg(unix)]
+fn special_file_kind(file_type: fs::FileType) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        Some("named pipe")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_char_device() {
+        Some("character device")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_kind(_file_type: fs::FileType) -> Option<&'static str> {
+    None
+}
+
+/// swap/hibernation files, temp folders, recycle bins and half-written konserve files that
+/// nobody wants back. Some(reason) if the entry is one of those. only ever checked for stuff
+/// *inside* a selected folder, picking one of these directly still packs it
+fn auto_exclusion(path: &Path, is_dir: bool, in_progress: &[&Path]) -> Option<&'static str> {
+    if in_progress.contains(&path) {
+        return Some("in-progress archive");
+    }
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if !is_dir {
+        if name.ends_with(".konserve-part") || name.ends_with(".tar.partial") {
+            return Some("Konserve scratch file");
+        }
+        // only at a drive/filesystem root, a file called swapfile anywhere else is someone's data
+        let at_root = path.parent().is_some_and(|p| p.parent().is_none());
+        return match name.as_str() {
+            "pagefile.sys" | "swapfile.sys" if at_root => Some("page file"),
+            "swapfile" | "swap.img" if at_root => Some("swap file"),
+            "hiberfil.sys" if at_root => Some("hibernation file"),
+            _ => None,
+        };
+    }
+
+    let recycle_bin = matches!(
+        name.as_str(),
+        "$recycle.bin" | "recycler" | ".trashes" | ".trash"
+    );
+    if recycle_bin || name.starts_with(".trash-") || path.ends_with(".local/share/Trash") {
+        return Some("recycle bin");
+    }
+    if path == Path::new("/private/var/vm") {
+        return Some("swap files");
+    }
+    let parent = path
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/").to_lowercase())
+        .unwrap_or_default();
+    let temp_dir =
+        name == "temp" && (parent.ends_with("appdata/local") || parent.ends_with("/windows"));
+    let unix_temp = matches!(path.to_str(), Some("/tmp" | "/var/tmp"));
+    if temp_dir || unix_temp || path == std::env::temp_dir() {
+        return Some("temp folder");
+    }
+    None
+}
+
+/// where the tar bytes end up, straight in the file or through the encryptor first
+enum ArchiveSink {
+    Plain(BufWriter<File>),
+    Encrypted(Box<EncryptWriter<BufWriter<File>>>),
+}
+
+impl ArchiveSink {
+    /// flushes everything (and seals the last chunk) so the file is complete
+    fn finish(self) -> io::Result<()> {
+        let buffered = match self {
+            ArchiveSink::Plain(w) => w,
+            ArchiveSink::Encrypted(w) => (*w).finish()?,
+        };
+        buffered.into_inner().map(drop).map_err(|e| e.into_error())
+    }
+}
+
+impl Write for ArchiveSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ArchiveSink::Plain(w) => w.write(buf),
+            ArchiveSink::Encrypted(w) => w.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ArchiveSink::Plain(w) => w.flush(),
+            ArchiveSink::Encrypted(w) => w.flush(),
+        }
+    }
+}
+
+/// what a finished backup run looks like, the archive got written but some roots may not have made it
+pub struct BackupReport {
+    pub archive: PathBuf,
+    /// roots that failed before any of their bytes got written, with the reason. the rest of the
+    /// archive is still fine, a root that breaks halfway through fails the whole backup instead
+    pub failed_roots: Vec<(PathBuf, String)>,
+    /// roots that are in fingerprint.txt but got zero files packed (empty or unreadable)
+    pub empty_roots: Vec<PathBuf>,
+    /// files inside the roots that couldn't be opened (locked by another program, no access), with why.
+    /// everything else in their folders still got packed
+    pub skipped_files: Vec<(PathBuf, String)>,
+    /// drives that were meant to be read from a shadow copy but got read live, with why
+    pub shadow_failed: Vec<(String, String)>,
+    /// selected paths that were inside another selected folder and got packed with it, as
+    /// (path, folder)
+    pub merged_roots: Vec<(PathBuf, PathBuf)>,
+    /// this run's job log, None if it couldn't be created
+    pub log: Option<PathBuf>,
+    /// incremental/differential: what it builds on and how many files were left to it
+    pub incremental: Option<(ParentLink, usize)>,
+    /// a parent was asked for but couldn't be used, so this became a full backup. why
+    pub parent_fallback: Option<String>,
+    /// junk + special files skipped inside the selection without being asked to, with why
+    pub auto_excluded: Vec<(PathBuf, &'static str)>,
+    /// files + folders left out by the exclude patterns
+    pub pattern_excluded: usize,
+    /// size of the finished archive
+    pub bytes: u64,
+    /// old archives the destination's retention rotated out
+    pub pruned: Vec<PathBuf>,
+    /// retention was on but nothing got rotated, why
+    pub prune_error: Option<String>,
+    pub took: Duration,
+}
+
+impl BackupReport {
+    /// status-bar text for the gui
+    pub fn summary(&self) -> String {
+        let stats = format!(
+            "{}, took {}",
+            format_size(self.bytes),
+            format_duration(self.took)
+        );
+        let mut msg = if self.failed_roots.is_empty()
+            && self.empty_roots.is_empty()
+            && self.skipped_files.is_empty()
+            && self.shadow_failed.is_empty()
+            && self.parent_fallback.is_none()
+        {
+            format!("✅ Backup created ({stats}):\n{}", self.archive.display())
+        } else {
+            format!(
+                "⚠ Backup created with warnings ({stats}):\n{}",
+                self.archive.display()
+            )
+        };
+        for (root, err) in &self.failed_roots {
+            msg.push_str(&format!("\n• {}: {err}", root.display()));
+        }
+        for root in &self.empty_roots {
+            msg.push_str(&format!("\n• {}: no files packed", root.display()));
+        }
+        for (volume, why) in &self.shadow_failed {
+            msg.push_str(&format!("\n• No shadow copy of {volume}, read live: {why}"));
+        }
+        if let Some(why) = &self.parent_fallback {
+            msg.push_str(&format!("\n• Full backup instead of incremental, {why}"));
+        }
+        if !self.skipped_files.is_empty() {
+            msg.push_str(&format!(
+                "\nCouldn't read {}, left out:",
+                format_count(self.skipped_files.len(), "file")
+            ));
+            for (path, why) in self.skipped_files.iter().take(5) {
+                msg.push_str(&format!("\n• {} ({why})", path.display()));
+            }
+            if self.skipped_files.len() > 5 {
+                msg.push_str("\n…the job log has the rest");
+            }
+        }
+        if !self.merged_roots.is_empty() {
+            msg.push_str(&format!(
+                "\n{} sat inside other selected folders, packed once with them:",
+                format_count(self.merged_roots.len(), "selected path")
+            ));
+            for (path, folder) in self.merged_roots.iter().take(5) {
+                msg.push_str(&format!("\n• {} (in {})", path.display(), folder.display()));
+            }
+        }
+        if let Some((parent, unchanged)) = &self.incremental {
+            let kind = if parent.differential {
+                "Differential against"
+            } else {
+                "Incremental on top of"
+            };
+            msg.push_str(&format!(
+                "\n{kind} {}, {} left to it.",
+                parent.name,
+                format_count(*unchanged, "unchanged file")
+            ));
+        }
+        if self.pattern_excluded > 0 {
+            msg.push_str(&format!(
+                "\n{} matched exclude patterns.",
+                format_count(self.pattern_excluded, "file/folder")
+            ));
+        }
+        if !self.auto_excluded.is_empty() {
+            msg.push_str(&format!(
+                "\nSkipped automatically ({}):",
+                self.auto_excluded.len()
+            ));
+            for (path, why) in self.auto_excluded.iter().take(5) {
+                msg.push_str(&format!("\n• {} ({why})", path.display()));
+            }
+            if self.auto_excluded.len() > 5 {
+                msg.push_str("\n…the job log has the rest");
+            }
+        }
+        if !self.pruned.is_empty() {
+            let names: Vec<_> = self
+                .pruned
+                .iter()
+                .take(5)
+                .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                .collect();
+            let more = if self.pruned.len() > 5 { ", …" } else { "" };
+            msg.push_str(&format!(
+                "\nRotated out {}: {}{more}",
+                format_count(self.pruned.len(), "old backup"),
+                names.join(", ")
+            ));
+        }
+        if let Some(e) = &self.prune_error {
+            msg.push_str(&format!("\n⚠ Old backups weren't rotated, {e}."));
+        }
+        if (!self.failed_roots.is_empty()
+            || !self.empty_roots.is_empty()
+            || !self.skipped_files.is_empty())
+            && let Some(log) = &self.log
+        {
+            msg.push_str(&format!("\nDetails: {}", log.display()));
+        }
+        msg
+    }
+}
+
+/// the tar being written + progress counters, shared across every root
+struct PackState<'a, W: Write> {
+    tar_builder: Builder<W>,
+    progress: &'a Progress,
+    done: u32,
+    total_files: u32,
+    /// None with an obfuscated manifest, owner names would say whose files these are
+    #[cfg(unix)]
+    ids: Option<UnixIds>,
+    /// original path -> (size, mtime) from the parent chain, empty for a full backup
+    parent_state: HashMap<String, (u64, u64)>,
+    /// uuid -> original path for this archive, to line files up with parent_state
+    path_map: HashMap<String, PathBuf>,
+    /// keys left out because the parent chain already has them
+    unchanged: Vec<String>,
+    /// (key, sha-256 hex) of every packed file, ends up in CHECKSUMS_ENTRY
+    checksums: Vec<(String, String)>,
+    /// key -> (size, mtime) of every file looked at, for the snapshot summary
+    file_info: HashMap<String, (u64, u64)>,
+    /// files in a folder root that couldn't be opened, see BackupReport::skipped_files
+    skipped: Vec<(PathBuf, String)>,
+}
+
+/// hashes whatever goes through it, so packing a file also checksums it without a second read
+struct HashRead<R: io::Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: io::Read> io::Read for HashRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> PackState<'_, W> {
+    /// small generated text file (unchanged list, checksums) at the end of the archive
+    fn append_text(&mut self, name: &str, text: &str) -> io::Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_size(text.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Local::now().timestamp() as u64);
+        header.set_cksum();
+        self.tar_builder
+            .append_data(&mut header, name, text.as_bytes())
+    }
+
+    /// notes down a file that couldn't be opened and moves on, one locked file shouldn't cost the
+    /// rest of its folder
+    fn skip_file(&mut self, path: &Path, e: &io::Error) {
+        let why = unreadable_reason(e);
+        jlog!("[skip]   {} ({why})", path.display());
+        self.skipped.push((path.to_path_buf(), why));
+        self.file_done();
+    }
+
+    fn file_done(&mut self) {
+        self.done += 1;
+        // only a fallback, with sizes known the bar follows the bytes
+        if self.progress.total_bytes() == 0 {
+            self.progress.set(self.done * 100 / self.total_files);
+        }
+    }
+
+    /// incremental: true (and noted down) if the parent chain already has this exact file.
+    /// every file passes through here, so its size + mtime get noted for the snapshot too
+    fn skip_unchanged(&mut self, key: &str, metadata: &fs::Metadata) -> bool {
+        // same seconds the tar header stores
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        self.file_info
+            .insert(key.to_string(), (metadata.len(), mtime));
+        if self.parent_state.is_empty() {
+            return false;
+        }
+        let Some(human) = entry_human_path(key, &self.path_map) else {
+            return false;
+        };
+        if self.parent_state.get(&human) != Some(&(metadata.len(), mtime)) {
+            return false;
+        }
+        jlog!("[DEBUG] Unchanged since parent: {human}");
+        self.unchanged.push(key.to_string());
+        self.progress.add_bytes(metadata.len());
+        self.file_done();
+        true
+    }
+
+    /// stamps owner/group names next to the numeric ids so a restore on another box can map them
+    #[cfg(unix)]
+    fn tag_owner(&self, header: &mut Header) {
+        let Some(ids) = &self.ids else {
+            return;
+        };
+        if let Ok(uid) = header.uid()
+            && let Some(name) = ids.user_name(uid as u32)
+        {
+            let _ = header.set_username(name);
+        }
+        if let Ok(gid) = header.gid()
+            && let Some(name) = ids.group_name(gid as u32)
+        {
+            let _ = header.set_groupname(name);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn tag_owner(&self, _header: &mut Header) {}
+}
+
+/// packs the selected files/folders into a .tar with fingerprint.txt embedded
+/// a root that can't be read gets recorded in the report and skipped. a write into the archive
+/// that fails aborts, the half-written archive gets deleted
 pub fn backup_gui(
     folders: &[PathBuf],
     output_dir: &Path,
     filename: &str,
     progress: &Progress,
-    verbose: bool,
-    skip_locked: bool,
-) -> Result<PathBuf, String> {
+    opts: &BackupOptions,
+) -> Result<BackupReport, String> {
+    let verbose = opts.verbose;
+    let started = Instant::now();
+    let job_log = JobLog::start("backup");
+    jlog!("backup to {}", output_dir.join(filename).display());
+
     if verbose {
         dlog!("[DEBUG] backup_gui: Started");
         dlog!("[DEBUG] Output directory: {}", output_dir.display());
     }
 
+    // a path inside another selected folder would get packed twice under two uuids and counted
+    // twice in the progress, the folder's walk already has it. unless the folder's walk leaves it
+    // out, then it stays a root of its own
+    let merged: Vec<(usize, usize)> = nested_selections(folders)
+        .into_iter()
+        .filter(|&(i, j)| !opts.left_out_of(&folders[j], &folders[i]))
+        .collect();
+    let mut merged_roots = Vec::new();
+    for &(i, j) in &merged {
+        jlog!(
+            "[merge]  {} is inside {}, packed with it",
+            folders[i].display(),
+            folders[j].display()
+        );
+        merged_roots.push((folders[i].clone(), folders[j].clone()));
+    }
+    let kept: Vec<PathBuf> = folders
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !merged.iter().any(|(m, _)| m == i))
+        .map(|(_, f)| f.clone())
+        .collect();
+    let folders = kept.as_slice();
+
+    // the gui already refuses these, this is the last line before packing the archive into itself
+    for dir in std::iter::once(output_dir).chain(opts.scratch_dir.as_deref()) {
+        if let Some(msg) = destination_overlap(folders, dir) {
+            return Err(msg);
+        }
+    }
+
     let zip_path = output_dir.join(filename);
+    // with a scratch dir the destination only ever sees a finished archive
+    let work_path = match &opts.scratch_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(|e| {
+                let msg = format!("ERROR: failed to create scratch dir {}: {e}", dir.display());
+                elog!("{msg}");
+                msg
+            })?;
+            dir.join(format!("{filename}.partial"))
+        }
+        None => zip_path.clone(),
+    };
     if verbose {
-        dlog!("[DEBUG] Creating backup archive: {}", zip_path.display());
+        dlog!("[DEBUG] Creating backup archive: {}", work_path.display());
     }
 
-    let tar_file = File::create(&zip_path).map_err(|e| {
+    let tar_file = File::create(&work_path).map_err(|e| {
         let msg = format!(
             "ERROR: failed to create archive {}: {e}",
-            zip_path.display()
+            work_path.display()
         );
         elog!("{msg}");
         msg
     })?;
-    let mut tar_builder = Builder::new(BufWriter::new(tar_file));
+    let tar_file = BufWriter::new(tar_file);
+    let sink = match &opts.passphrase {
+        Some(passphrase) => {
+            jlog!("archive is encrypted");
+            match EncryptWriter::new(tar_file, passphrase) {
+                Ok(w) => ArchiveSink::Encrypted(Box::new(w)),
+                Err(e) => return Err(discard(&work_path, progress, e.to_string())),
+            }
+        }
+        None => ArchiveSink::Plain(tar_file),
+    };
+    let mut tar_builder = Builder::new(sink);
 
-    let mut fingerprint_content = format!("{}\n[Backup Info]\n", get_fingered());
+    let mut path_lines = String::new();
 
     let folder_uuid: Vec<(Uuid, &PathBuf)> = folders
         .iter()
@@ -55,11 +557,77 @@ pub fn backup_gui(
         })
         .collect();
 
-    let mut done = 0u32;
-
+    let mut note_lines = String::new();
     for (uuid, original_path) in &folder_uuid {
-        fingerprint_content.push_str(&format!("{}: {}\n", uuid, original_path.display()));
+        path_lines.push_str(&format!("{}: {}\n", uuid, original_path.display()));
+        if let Some(note) = opts.notes.get(*original_path)
+            && !note.trim().is_empty()
+        {
+            note_lines.push_str(&note_line(&uuid.to_string(), note));
+        }
+    }
+
+    // incremental/differential: whatever the parent chain already has with the same size + mtime
+    // stays out
+    let mut parent_state = HashMap::new();
+    let mut parent_link = None;
+    let mut parent_fallback = None;
+    if let Some(parent) = &opts.parent
+        && parent.parent() == Some(output_dir)
+        && *parent != zip_path
+    {
+        // a chain normally shares one passphrase
+        if let Some(passphrase) = &opts.passphrase
+            && crypt::is_encrypted(parent)
+            && !crypt::has_passphrase(parent)
+        {
+            crypt::remember_passphrase(parent, passphrase.clone());
+        }
+        match archive_file_state(parent, verbose) {
+            Ok((id, known)) => {
+                jlog!(
+                    "incremental on top of {}, {} files known",
+                    parent.display(),
+                    known.len()
+                );
+                parent_state = known;
+                parent_link = parent.file_name().map(|n| ParentLink {
+                    name: n.to_string_lossy().into_owned(),
+                    id,
+                    differential: opts.differential,
+                });
+            }
+            Err(e) => {
+                jlog!(
+                    "[WARN] can't read parent archive {}, doing a full backup: {e}",
+                    parent.display()
+                );
+                parent_fallback = Some(format!(
+                    "couldn't read {}: {e}",
+                    parent.file_name().unwrap_or_default().to_string_lossy()
+                ));
+            }
+        }
+    } else if let Some(parent) = &opts.parent {
+        // restore looks for the parent next to the child, anywhere else it'd never be found
+        jlog!(
+            "[WARN] parent archive {} isn't in {}, doing a full backup",
+            parent.display(),
+            output_dir.display()
+        );
+        parent_fallback = Some(format!(
+            "{} isn't in the destination folder",
+            parent.display()
+        ));
     }
+    let archive_id = Uuid::new_v4().to_string();
+    let fingerprint_content = render_manifest(
+        &path_lines,
+        &note_lines,
+        &archive_id,
+        parent_link.as_ref(),
+        opts.obfuscate_manifest,
+    );
 
     let mut fingerprint_header = Header::new_gnu();
     fingerprint_header.set_size(fingerprint_content.len() as u64);
@@ -67,208 +635,680 @@ pub fn backup_gui(
     fingerprint_header.set_mtime(Local::now().timestamp() as u64);
     fingerprint_header.set_cksum();
 
-    tar_builder
-        .append_data(
-            &mut fingerprint_header,
-            "fingerprint.txt",
-            fingerprint_content.as_bytes(),
-        )
-        .map_err(|e| e.to_string())?;
+    if let Err(e) = tar_builder.append_data(
+        &mut fingerprint_header,
+        "fingerprint.txt",
+        fingerprint_content.as_bytes(),
+    ) {
+        drop(tar_builder);
+        let why = format!("can't write fingerprint.txt: {e}");
+        return Err(discard(&work_path, progress, why));
+    }
     if verbose {
         dlog!("[DEBUG] fingerprint.txt added to archive");
     }
 
+    if opts.include_konserve_config {
+        for (name, path) in konserve_config_files() {
+            let entry_name = format!("{KONSERVE_CONFIG_ENTRY}/{name}");
+            // settings are a nice-to-have, one that can't be read shouldn't cost the actual
+            // backup. read up front so that's known before anything of it is written
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(e) => {
+                    elog!("ERROR: failed to add {} to archive: {e}", path.display());
+                    continue;
+                }
+            };
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(Local::now().timestamp() as u64);
+            header.set_cksum();
+            if let Err(e) = tar_builder.append_data(&mut header, &entry_name, data.as_slice()) {
+                drop(tar_builder);
+                let why = format!("can't write {entry_name}: {e}");
+                return Err(discard(&work_path, progress, why));
+            }
+            jlog!("[DEBUG] Adding konserve settings: {}", path.display());
+        }
+    }
+
+    let mut failed_roots: Vec<(PathBuf, String)> = Vec::new();
+
+    // one snapshot moment for every root, taken before anything gets walked
+    let mut shadow_failed = Vec::new();
+    let shadows = if opts.shadow_copy {
+        progress.working_on("creating volume shadow copies");
+        let (set, failed) = ShadowSet::create(folders);
+        shadow_failed = failed;
+        set
+    } else {
+        ShadowSet::default()
+    };
+
     // grab everything up front so we only walk the fs once instead of counting then walking again
-    // each element is (uuid, original_path, walk_entries_or_none)
-    let mut all_entries: Vec<(Uuid, &PathBuf, Vec<walkdir::DirEntry>)> = Vec::new();
+    // each element is (uuid, original_path, where it's read from, walk_entries_or_none)
+    let mut all_entries: Vec<(Uuid, &PathBuf, PathBuf, Vec<walkdir::DirEntry>)> = Vec::new();
     let mut total_files: u32 = 0;
+    let mut total_bytes: u64 = 0;
+    let mut auto_excluded: Vec<(PathBuf, &'static str)> = Vec::new();
+    let mut pattern_excluded = 0;
+    let in_progress = [zip_path.as_path(), work_path.as_path()];
 
     for (uuid, original_path) in &folder_uuid {
-        if original_path.is_file() {
-            total_files += 1;
-            all_entries.push((*uuid, original_path, Vec::new()));
-        } else {
-            let entries: Vec<_> = WalkDir::new(original_path)
-                .into_iter()
-                .filter_map(Result::ok)
-                .collect();
-            total_files += entries.iter().filter(|e| e.file_type().is_file()).count() as u32;
-            all_entries.push((*uuid, original_path, entries));
+        if progress.is_cancelled() {
+            break;
+        }
+        if !original_path.exists() {
+            elog!("ERROR: selected path not found: {}", original_path.display());
+            failed_roots.push(((*original_path).clone(), "path not found".into()));
+            continue;
+        }
+        if let Some(kind) = fs::metadata(original_path)
+            .ok()
+            .and_then(|m| special_file_kind(m.file_type()))
+        {
+            elog!(
+                "ERROR: {} is a {kind}, not packing it",
+                original_path.display()
+            );
+            failed_roots.push((
+                (*original_path).clone(),
+                format!("{kind}, only regular files and folders can be backed up"),
+            ));
+            continue;
         }
-    }
-    let total_files = total_files.max(1);
 
-    // actually building the archive now
-    for (uuid, original_path, walk_entries) in all_entries {
+        let source = shadows
+            .map(original_path)
+            .unwrap_or_else(|| (*original_path).clone());
         if original_path.is_file() {
-            if verbose {
-                dlog!("[DEBUG] Adding single file: {}", original_path.display());
+            if opts.is_excluded(original_path) {
+                continue;
             }
-
-            let metadata = match original_path.metadata() {
-                Ok(m) => m,
-                Err(e) => {
-                    if skip_locked {
-                        done += 1;
-                        progress.set(done * 100 / total_files);
-                        continue;
-                    }
-                    elog!("ERROR: cannot stat file {}: {e}", original_path.display());
-                    return Err(e.to_string());
+            total_files += 1;
+            total_bytes += fs::metadata(original_path).map_or(0, |m| m.len());
+            all_entries.push((*uuid, original_path, source, Vec::new()));
+        } else {
+            progress.working_on(format!("scanning {}", original_path.display()));
+            let mut entries = Vec::new();
+            let mut root_err = None;
+            // filter_entry so unchecked folders don't get walked at all
+            let keep = |e: &walkdir::DirEntry| {
+                let path = live_path(e.path(), &source, original_path);
+                if opts.is_excluded(&path) {
+                    return false;
                 }
-            };
-            let mut header = Header::new_gnu();
-            header.set_metadata(&metadata);
-            header.set_cksum();
-
-            let mut f = match File::open(original_path) {
-                Ok(f) => f,
-                Err(e) => {
-                    if skip_locked {
-                        dlog!(
-                            "[WARN] Skipping inaccessible file {}: {e}",
-                            original_path.display()
-                        );
-                        done += 1;
-                        progress.set(done * 100 / total_files);
-                        continue;
+                if let Some(pattern) =
+                    opts.matching_pattern(original_path, &path, e.file_type().is_dir())
+                {
+                    jlog!("[skip]   {} (matches {pattern})", path.display());
+                    pattern_excluded += 1;
+                    return false;
+                }
+                let is_dir = e.file_type().is_dir();
+                let why = special_file_kind(e.file_type())
+                    .or_else(|| auto_exclusion(&path, is_dir, &in_progress));
+                match why {
+                    Some(why) if e.depth() > 0 => {
+                        jlog!("[skip]   {} ({why})", path.display());
+                        auto_excluded.push((path, why));
+                        false
                     }
-                    elog!("ERROR: cannot open file {}: {e}", original_path.display());
-                    return Err(e.to_string());
+                    _ => true,
                 }
             };
-
-            let entry_name = match original_path.extension().and_then(|e| e.to_str()) {
-                Some(ext) => format!("{uuid}.{ext}"),
-                None => uuid.to_string(),
-            };
-            if verbose {
-                dlog!("[DEBUG] -> Entry name in tar: {entry_name}");
+            for entry in WalkDir::new(&source).into_iter().filter_entry(keep) {
+                if progress.is_cancelled() {
+                    break;
+                }
+                match entry {
+                    Ok(e) => entries.push(e),
+                    // can't even read the root, nothing under it is getting in
+                    Err(e) if e.depth() == 0 => root_err = Some(e.to_string()),
+                    Err(_) => {}
+                }
+            }
+            if let Some(e) = root_err {
+                elog!("ERROR: cannot read {}: {e}", original_path.display());
+                failed_roots.push(((*original_path).clone(), e));
+                continue;
+            }
+            for e in entries.iter().filter(|e| e.file_type().is_file()) {
+                total_files += 1;
+                total_bytes += e.metadata().map_or(0, |m| m.len());
             }
+            all_entries.push((*uuid, original_path, source, entries));
+        }
+    }
+    if progress.is_cancelled() {
+        drop(tar_builder);
+        return Err(abandon(&work_path, progress));
+    }
 
-            if let Err(e) = tar_builder.append_data(&mut header, entry_name, &mut f) {
-                if skip_locked {
-                    dlog!(
-                        "[WARN] Skipping file {} (write error: {e})",
-                        original_path.display()
-                    );
-                    done += 1;
-                    progress.set(done * 100 / total_files);
-                    continue;
-                }
+    progress.add_total_bytes(total_bytes);
+
+    let mut state = PackState {
+        tar_builder,
+        progress,
+        done: 0,
+        total_files: total_files.max(1),
+        #[cfg(unix)]
+        ids: (!opts.obfuscate_manifest).then(UnixIds::load),
+        parent_state,
+        path_map: folder_uuid
+            .iter()
+            .map(|(uuid, path)| (uuid.to_string(), (*path).clone()))
+            .collect(),
+        unchanged: Vec::new(),
+        checksums: Vec::new(),
+        file_info: HashMap::new(),
+        skipped: Vec::new(),
+    };
+
+    let mut empty_roots: Vec<PathBuf> = Vec::new();
+
+    // actually building the archive now
+    for (uuid, original_path, source, walk_entries) in all_entries {
+        let packed = pack_root(&mut state, uuid, original_path, &source, walk_entries, opts);
+        if progress.is_cancelled() {
+            drop(state);
+            return Err(abandon(&work_path, progress));
+        }
+        match packed {
+            // fully unchecked in the backup tree is on purpose, don't nag about it. a picked file
+            // that couldn't be read is already in skipped_files with why
+            Ok(0)
+                if !opts.is_excluded(original_path)
+                    && !state.skipped.iter().any(|(p, _)| p == original_path) =>
+            {
+                dlog!(
+                    "[WARN] {} is fingerprinted as {uuid} but no files got packed",
+                    original_path.display()
+                );
+                empty_roots.push(original_path.clone());
+            }
+            Ok(_) => {}
+            Err(RootError::Skipped(e)) => {
                 elog!(
-                    "ERROR: failed to write {} to archive: {e}",
+                    "ERROR: backing up {} failed, continuing with the rest: {e}",
                     original_path.display()
                 );
-                return Err(e.to_string());
+                failed_roots.push((original_path.clone(), e));
             }
+            // part of it is in the archive already, everything after would be read out of step
+            Err(RootError::Broken(e)) => {
+                drop(state);
+                let why = format!("writing {} failed: {e}", original_path.display());
+                return Err(discard(&work_path, progress, why));
+            }
+        }
+    }
+
+    if parent_link.is_some() {
+        let unchanged = render_unchanged(&state.unchanged, opts.obfuscate_manifest);
+        if let Err(e) = state.append_text(UNCHANGED_ENTRY, &unchanged) {
+            drop(state);
+            let why = format!("can't write {UNCHANGED_ENTRY}: {e}");
+            return Err(discard(&work_path, progress, why));
+        }
+        jlog!("{} files unchanged since the parent", state.unchanged.len());
+    }
+    let checksums = render_checksums(&state.checksums, opts.obfuscate_manifest);
+    if let Err(e) = state.append_text(CHECKSUMS_ENTRY, &checksums) {
+        drop(state);
+        let why = format!("can't write {CHECKSUMS_ENTRY}: {e}");
+        return Err(discard(&work_path, progress, why));
+    }
 
-            done += 1;
-            progress.set(done * 100 / total_files);
+    // only written once the archive is in place, like the checksum file
+    let snapshot = opts.snapshot_json.then(|| {
+        snapshot_json(
+            &archive_id,
+            folders,
+            &state,
+            parent_link.as_ref(),
+            opts.obfuscate_manifest,
+        )
+    });
 
-            continue;
+    // into_inner finishes the tar, the sink then flushes so the file is complete before a move
+    if let Err(e) = state.tar_builder.into_inner().and_then(ArchiveSink::finish) {
+        let why = format!("failed to finalize archive {}: {e}", work_path.display());
+        return Err(discard(&work_path, progress, why));
+    }
+    if work_path != zip_path {
+        move_file(&work_path, &zip_path).map_err(|e| {
+            let msg = format!(
+                "ERROR: failed to move {} to {}: {e}",
+                work_path.display(),
+                zip_path.display()
+            );
+            elog!("{msg}");
+            msg
+        })?;
+        jlog!("moved {} into place", work_path.display());
+    }
+    // the archive is fine without it, so a failed write is only logged
+    if let Some(text) = opts.checksum_export.render(&state.checksums) {
+        let sums_path = checksum_export_path(&zip_path);
+        match fs::write(&sums_path, text) {
+            Ok(()) => jlog!("checksums written to {}", sums_path.display()),
+            Err(e) => elog!(
+                "ERROR: failed to write checksum file {}: {e}",
+                sums_path.display()
+            ),
         }
+    }
+    if let Some(snapshot) = snapshot {
+        let mut name = zip_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".snapshot.json");
+        let path = zip_path.with_file_name(name);
+        let written = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => jlog!("snapshot summary written to {}", path.display()),
+            Err(e) => elog!(
+                "ERROR: failed to write snapshot summary {}: {e}",
+                path.display()
+            ),
+        }
+    }
+    if verbose {
+        dlog!("[DEBUG] Archive finished: {}", zip_path.display());
+    }
 
-        if verbose {
-            dlog!("[DEBUG] Walking folder: {}", original_path.display());
+    // rotation only ever runs after a backup that made it, so there's always a newer one to keep
+    let mut pruned = Vec::new();
+    let mut prune_error = None;
+    if !opts.retention.is_off() {
+        if let Some(passphrase) = &opts.passphrase {
+            crypt::remember_passphrase(&zip_path, passphrase.clone());
         }
+        let protect: Vec<PathBuf> = std::iter::once(zip_path.clone())
+            .chain(opts.parent.clone())
+            .collect();
+        match prune_archives(output_dir, opts.retention, &protect, verbose) {
+            Ok(deleted) => {
+                for p in &deleted {
+                    jlog!("rotated out {}", p.display());
+                }
+                pruned = deleted;
+            }
+            Err(e) => {
+                elog!("ERROR: retention skipped for {}: {e}", output_dir.display());
+                prune_error = Some(e);
+            }
+        }
+    }
 
-        for entry in walk_entries {
-            let entry_path = entry.path();
-            let metadata = match entry.metadata() {
-                Ok(m) => m,
-                Err(e) => {
-                    if skip_locked {
-                        continue;
-                    }
-                    elog!("ERROR: cannot stat {}: {e}", entry_path.display());
-                    return Err(e.to_string());
+    progress.done();
+    jlog!(
+        "backup finished, {} files, {} skipped, {} failed roots, {} empty roots",
+        state.done,
+        state.skipped.len(),
+        failed_roots.len(),
+        empty_roots.len()
+    );
+
+    Ok(BackupReport {
+        bytes: fs::metadata(&zip_path).map_or(0, |m| m.len()),
+        archive: zip_path,
+        failed_roots,
+        empty_roots,
+        skipped_files: std::mem::take(&mut state.skipped),
+        shadow_failed,
+        merged_roots,
+        log: job_log.path.clone(),
+        incremental: parent_link.map(|link| (link, state.unchanged.len())),
+        parent_fallback,
+        auto_excluded,
+        pattern_excluded,
+        pruned,
+        prune_error,
+        took: started.elapsed(),
+    })
+}
+
+/// contents of `<archive>.snapshot.json`: roughly what `restic snapshots --json` says about a
+/// snapshot, plus every file with size, mtime and sha-256, so coverage can be lined up against
+/// restic/borg listings of the same folders. files left to the parent chain have no hash here.
+/// with `hide_paths` (obfuscated manifest) original paths, host and user are left out, files
+/// only go by their archive entry
+fn snapshot_json<W: Write>(
+    archive_id: &str,
+    folders: &[PathBuf],
+    state: &PackState<'_, W>,
+    parent: Option<&ParentLink>,
+    hide_paths: bool,
+) -> serde_json::Value {
+    let rfc3339 = |secs: u64| {
+        chrono::DateTime::from_timestamp(secs as i64, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default()
+    };
+    let file = |key: &String, sha256: Option<&String>| {
+        let (size, mtime) = state.file_info.get(key).copied().unwrap_or_default();
+        let mut entry = serde_json::json!({
+            "archive_entry": key,
+            "size": size,
+            "mtime": rfc3339(mtime),
+            "sha256": sha256,
+        });
+        if !hide_paths {
+            entry["path"] = entry_human_path(key, &state.path_map)
+                .unwrap_or_else(|| key.clone())
+                .into();
+        }
+        entry
+    };
+    let mut files: Vec<serde_json::Value> = state
+        .checksums
+        .iter()
+        .map(|(key, digest)| file(key, Some(digest)))
+        .chain(state.unchanged.iter().map(|key| file(key, None)))
+        .collect();
+    files.sort_by_cached_key(|f| {
+        let key = f.get("path").unwrap_or(&f["archive_entry"]);
+        key.as_str().map(str::to_string)
+    });
+    let total_bytes: u64 = files.iter().filter_map(|f| f["size"].as_u64()).sum();
+
+    let mut snapshot = serde_json::json!({
+        "id": archive_id,
+        // utc, so snapshots from machines in other time zones sort and compare as they should
+        "time": chrono::Utc::now().to_rfc3339(),
+        "tags": ["konserve"],
+        "parent": parent.map(|p| &p.name),
+        "program_version": format!("konserve {}", env!("CARGO_PKG_VERSION")),
+        "summary": {
+            "total_files_processed": files.len(),
+            "total_bytes_processed": total_bytes,
+            "files_unmodified": state.unchanged.len(),
+        },
+        "files": files,
+    });
+    // the sidecar sits next to the archive in plain text, it mustn't give away what the
+    // obfuscated manifest hides
+    if !hide_paths {
+        let hostname = std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .ok()
+            .or_else(|| {
+                fs::read_to_string("/etc/hostname")
+                    .ok()
+                    .map(|h| h.trim().to_string())
+            });
+        snapshot["hostname"] = hostname.into();
+        snapshot["username"] = std::env::var("USERNAME")
+            .or_else(|_| std::env::var("USER"))
+            .ok()
+            .into();
+        snapshot["paths"] = folders
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .into();
+    }
+    snapshot
+}
+
+/// cleanup after a cancel, the half-written archive goes away instead of looking like a backup
+fn abandon(work_path: &Path, progress: &Progress) -> String {
+    match fs::remove_file(work_path) {
+        Ok(()) => jlog!("backup cancelled, removed {}", work_path.display()),
+        Err(e) => elog!(
+            "ERROR: backup cancelled but {} couldn't be removed: {e}",
+            work_path.display()
+        ),
+    }
+    progress.done();
+    CANCELLED.into()
+}
+
+/// writing into the archive failed partway, so nothing after that point could be read back.
+/// deletes it rather than leave a broken archive around looking like a backup
+fn discard(work_path: &Path, progress: &Progress, why: String) -> String {
+    jlog!("backup failed: {why}");
+    match fs::remove_file(work_path) {
+        Ok(()) => jlog!("backup failed, removed {}", work_path.display()),
+        Err(e) => elog!(
+            "ERROR: backup failed but {} couldn't be removed: {e}",
+            work_path.display()
+        ),
+    }
+    progress.done();
+    format!("{why}. The partial archive was deleted.")
+}
+
+/// rename, or copy + delete when the scratch dir is on another drive
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// why a file couldn't be opened, in words for the skipped-files report
+fn unreadable_reason(e: &io::Error) -> String {
+    // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION, the usual open browser profile
+    if cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)) {
+        return "in use by another program".into();
+    }
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => "no permission".into(),
+        io::ErrorKind::NotFound => "deleted while backing up".into(),
+        _ => e.to_string(),
+    }
+}
+
+/// `path` from a walk of `source` (a root's shadow copy, or the root itself) as it is under the
+/// live `root`, what exclusions match against and what the logs show
+fn live_path(path: &Path, source: &Path, root: &Path) -> PathBuf {
+    match path.strip_prefix(source) {
+        Ok(rel) if !rel.as_os_str().is_empty() => root.join(rel),
+        _ => root.to_path_buf(),
+    }
+}
+
+/// why pack_root gave up on a root
+enum RootError {
+    /// before any of it got written, the archive is fine without it
+    Skipped(String),
+    /// partway through writing an entry, the archive is broken from there on
+    Broken(String),
+}
+
+/// appends one selected root (single file or walked folder) to the archive, returns how many files
+/// made it in. `source` is where it gets read from, the root itself or its shadow copy
+fn pack_root<W: Write>(
+    state: &mut PackState<'_, W>,
+    uuid: Uuid,
+    original_path: &Path,
+    source: &Path,
+    walk_entries: Vec<walkdir::DirEntry>,
+    opts: &BackupOptions,
+) -> Result<u32, RootError> {
+    let verbose = opts.verbose;
+    let skip_locked = opts.skip_locked;
+    let mut packed = 0u32;
+
+    if original_path.is_file() {
+        jlog!("[DEBUG] Adding single file: {}", original_path.display());
+        state
+            .progress
+            .working_on(original_path.display().to_string());
+
+        let metadata = match source.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                if skip_locked {
+                    state.skip_file(original_path, &e);
+                    return Ok(0);
                 }
-            };
+                elog!("ERROR: cannot stat file {}: {e}", original_path.display());
+                return Err(RootError::Skipped(e.to_string()));
+            }
+        };
+        let entry_name = match original_path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!("{uuid}.{ext}"),
+            None => uuid.to_string(),
+        };
+        if state.skip_unchanged(&entry_name, &metadata) {
+            return Ok(1);
+        }
 
-            let relative_path = match entry_path.strip_prefix(original_path) {
-                Ok(p) => p,
-                Err(_) => {
-                    if verbose {
-                        dlog!(
-                            "[WARN] skipping entry outside original_path: {}",
-                            entry_path.display()
-                        );
-                    }
-                    continue;
+        let mut header = Header::new_gnu();
+        header.set_metadata(&metadata);
+        state.tag_owner(&mut header);
+        header.set_cksum();
+
+        let progress = state.progress;
+        let mut f = match File::open(source) {
+            Ok(inner) => HashRead {
+                inner: ProgressRead { inner, progress },
+                hasher: Sha256::new(),
+            },
+            Err(e) => {
+                if skip_locked {
+                    state.skip_file(original_path, &e);
+                    return Ok(0);
                 }
-            };
-            let tar_entry_path = Path::new(&uuid.to_string()).join(relative_path);
+                elog!("ERROR: cannot open file {}: {e}", original_path.display());
+                return Err(RootError::Skipped(e.to_string()));
+            }
+        };
 
-            let mut header = Header::new_gnu();
-            header.set_metadata(&metadata);
-            header.set_cksum();
+        if verbose {
+            dlog!("[DEBUG] -> Entry name in tar: {entry_name}");
+        }
+
+        // the header may be out already, so this can't be skipped like a file that won't open
+        if let Err(e) = state
+            .tar_builder
+            .append_data(&mut header, &entry_name, &mut f)
+        {
+            elog!(
+                "ERROR: failed to write {} to archive: {e}",
+                original_path.display()
+            );
+            return Err(RootError::Broken(e.to_string()));
+        }
+
+        state
+            .checksums
+            .push((entry_name, to_hex(&f.hasher.finalize())));
+        state.file_done();
+        return Ok(1);
+    }
+
+    if verbose {
+        dlog!("[DEBUG] Walking folder: {}", original_path.display());
+    }
+
+    for entry in walk_entries {
+        if state.progress.is_cancelled() {
+            return Err(RootError::Skipped(CANCELLED.into()));
+        }
+        let entry_path = entry.path();
+        let live = live_path(entry_path, source, original_path);
+        let metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                state.skip_file(&live, &e.into());
+                continue;
+            }
+        };
 
-            if metadata.is_file() {
+        let relative_path = match entry_path.strip_prefix(source) {
+            Ok(p) => p,
+            Err(_) => {
                 if verbose {
-                    dlog!("[DEBUG] Adding file: {}", entry_path.display());
-                }
-                let mut file = match File::open(entry_path) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        if skip_locked {
-                            dlog!(
-                                "[WARN] Skipping inaccessible file {}: {e}",
-                                entry_path.display()
-                            );
-                            done += 1;
-                            progress.set(done * 100 / total_files);
-                            continue;
-                        }
-                        elog!("ERROR: cannot open file {}: {e}", entry_path.display());
-                        return Err(e.to_string());
-                    }
-                };
-                if let Err(e) = tar_builder.append_data(&mut header, tar_entry_path, &mut file) {
-                    if skip_locked {
-                        dlog!(
-                            "[WARN] Skipping file {} (write error: {e})",
-                            entry_path.display()
-                        );
-                        done += 1;
-                        progress.set(done * 100 / total_files);
-                        continue;
-                    }
-                    elog!(
-                        "ERROR: failed to write {} to archive: {e}",
+                    dlog!(
+                        "[WARN] skipping entry outside original_path: {}",
                         entry_path.display()
                     );
-                    return Err(e.to_string());
                 }
+                continue;
+            }
+        };
+        let tar_entry_path = Path::new(&uuid.to_string()).join(relative_path);
 
-                done += 1;
-                progress.set(done * 100 / total_files);
-            } else if metadata.is_dir() {
-                if verbose {
-                    dlog!("[DEBUG] Adding directory: {}", entry_path.display());
+        let mut header = Header::new_gnu();
+        header.set_metadata(&metadata);
+        state.tag_owner(&mut header);
+        header.set_cksum();
+
+        if metadata.is_file() {
+            // joined per component like tar names it, a backslash in a unix file name stays put
+            let rel: Vec<_> = relative_path.iter().map(|c| c.to_string_lossy()).collect();
+            let key = format!("{uuid}/{}", rel.join("/"));
+            if state.skip_unchanged(&key, &metadata) {
+                packed += 1;
+                continue;
+            }
+            jlog!("[DEBUG] Adding file: {}", live.display());
+            state.progress.working_on(live.display().to_string());
+            let progress = state.progress;
+            let mut file = match File::open(entry_path) {
+                Ok(inner) => HashRead {
+                    inner: ProgressRead { inner, progress },
+                    hasher: Sha256::new(),
+                },
+                Err(e) => {
+                    state.skip_file(&live, &e);
+                    continue;
                 }
-                if let Err(e) = tar_builder.append_data(&mut header, tar_entry_path, io::empty())
-                    && !skip_locked
-                {
-                    return Err(e.to_string());
+            };
+            if let Err(e) = state
+                .tar_builder
+                .append_data(&mut header, tar_entry_path, &mut file)
+            {
+                elog!("ERROR: failed to write {} to archive: {e}", live.display());
+                return Err(RootError::Broken(e.to_string()));
+            }
+
+            state.checksums.push((key, to_hex(&file.hasher.finalize())));
+            packed += 1;
+            state.file_done();
+        } else if metadata.file_type().is_symlink() {
+            // packed as the link itself, restore makes it again (or copies what it points at)
+            let target = match fs::read_link(entry_path) {
+                Ok(t) => t,
+                Err(e) => {
+                    state.skip_file(&live, &e);
+                    continue;
                 }
+            };
+            jlog!(
+                "[DEBUG] Adding symlink: {} → {}",
+                live.display(),
+                target.display()
+            );
+            if let Err(e) = state
+                .tar_builder
+                .append_link(&mut header, tar_entry_path, &target)
+            {
+                elog!("ERROR: failed to write {} to archive: {e}", live.display());
+                return Err(RootError::Broken(e.to_string()));
+            }
+            packed += 1;
+        } else if metadata.is_dir() {
+            if verbose {
+                dlog!("[DEBUG] Adding directory: {}", live.display());
+            }
+            if let Err(e) = state
+                .tar_builder
+                .append_data(&mut header, tar_entry_path, io::empty())
+            {
+                elog!("ERROR: failed to write {} to archive: {e}", live.display());
+                return Err(RootError::Broken(e.to_string()));
             }
         }
     }
 
-    tar_builder.finish().map_err(|e| {
-        let msg = format!(
-            "ERROR: failed to finalize archive {}: {e}",
-            zip_path.display()
-        );
-        elog!("{msg}");
-        msg
-    })?;
-    if verbose {
-        dlog!("[DEBUG] Archive finished: {}", zip_path.display());
-    }
-
-    progress.done();
-
-    Ok(zip_path)
+    Ok(packed)
 }
@@ -0,0 +1,267 @@
+//! passphrase-encrypted archives. the tar stream gets cut into 64 KiB chunks, each sealed with
+//! AES-256-GCM under a PBKDF2-SHA256 key. chunk nonces carry a counter + last-chunk flag so
+//! reordered, dropped or truncated chunks fail to decrypt instead of restoring garbage
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use sha2::Sha256;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// first bytes of an encrypted archive, a plain tar never starts like this
+const MAGIC: &[u8; 8] = b"KNSVAES1";
+const SALT_LEN: usize = 16;
+const PREFIX_LEN: usize = 7;
+const CHUNK: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+/// stored in the header, so it can go up later without breaking old archives
+const ITERATIONS: u32 = 600_000;
+/// most iterations a header may ask for. leaves room to raise ITERATIONS, but a damaged header
+/// can't keep the key derivation busy for hours before "wrong passphrase" comes up
+const MAX_ITERATIONS: u32 = ITERATIONS * 16;
+
+/// passphrases typed in this session, per archive. never written anywhere
+static PASSPHRASES: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+pub fn remember_passphrase(archive: &Path, passphrase: String) {
+    let mut known = PASSPHRASES.lock().unwrap_or_else(|e| e.into_inner());
+    known.retain(|(p, _)| p != archive);
+    known.push((archive.to_path_buf(), passphrase));
+}
+
+/// drops a passphrase that turned out wrong so the prompt comes back
+pub fn forget_passphrase(archive: &Path) {
+    let mut known = PASSPHRASES.lock().unwrap_or_else(|e| e.into_inner());
+    known.retain(|(p, _)| p != archive);
+}
+
+fn passphrase_for(archive: &Path) -> Option<String> {
+    let known = PASSPHRASES.lock().unwrap_or_else(|e| e.into_inner());
+    known
+        .iter()
+        .find(|(p, _)| p == archive)
+        .map(|(_, pass)| pass.clone())
+}
+
+/// lets an incremental archive's parent be opened with the child's passphrase, unless it has its own
+pub fn inherit_passphrase(from: &Path, to: &Path) {
+    if !has_passphrase(to)
+        && let Some(passphrase) = passphrase_for(from)
+    {
+        remember_passphrase(to, passphrase);
+    }
+}
+
+pub fn has_passphrase(archive: &Path) -> bool {
+    passphrase_for(archive).is_some()
+}
+
+/// checks the magic only, says nothing about whether the rest is intact
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut magic = [0u8; MAGIC.len()];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+/// opens an archive for reading, decrypting on the fly if it's encrypted. the passphrase has to
+/// have been remembered for this path first
+pub fn open_archive_reader(path: &Path) -> Result<Box<dyn Read>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    if !is_encrypted(path) {
+        return Ok(Box::new(BufReader::new(file)));
+    }
+    let Some(passphrase) = passphrase_for(path) else {
+        return Err("archive is encrypted, enter its passphrase first".into());
+    };
+    let reader =
+        DecryptReader::new(BufReader::new(file), &passphrase).map_err(|e| e.to_string())?;
+    Ok(Box::new(reader))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    Aes256Gcm::new(&key.into())
+}
+
+/// nonce prefix, big-endian chunk counter, then 1 on the final chunk
+fn chunk_nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Write adapter that encrypts everything going through it. finish() has to be called,
+/// it seals the last chunk, without it the archive reads as truncated
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    cipher: Aes256Gcm,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    pub fn new(mut inner: W, passphrase: &str) -> io::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut prefix = [0u8; PREFIX_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut prefix);
+
+        inner.write_all(MAGIC)?;
+        inner.write_all(&salt)?;
+        inner.write_all(&ITERATIONS.to_le_bytes())?;
+        inner.write_all(&prefix)?;
+
+        Ok(Self {
+            inner,
+            cipher: derive_key(passphrase, &salt, ITERATIONS),
+            prefix,
+            counter: 0,
+            buf: Vec::with_capacity(CHUNK),
+        })
+    }
+
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), self.buf.as_slice())
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        self.inner.write_all(&sealed)?;
+        self.buf.clear();
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("archive too large to encrypt"))?;
+        Ok(())
+    }
+
+    /// seals whatever is buffered as the final chunk (possibly empty) and hands back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.seal(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let take = (CHUNK - self.buf.len()).min(data.len());
+        self.buf.extend_from_slice(&data[..take]);
+        // full chunks go out right away, so the final one is always short and the reader can tell
+        if self.buf.len() == CHUNK {
+            self.seal(false)?;
+        }
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Read adapter for EncryptWriter's output
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    cipher: Aes256Gcm,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    plain: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    pub fn new(mut inner: R, passphrase: &str) -> io::Result<Self> {
+        let mut magic = [0u8; MAGIC.len()];
+        let mut salt = [0u8; SALT_LEN];
+        let mut iterations = [0u8; 4];
+        let mut prefix = [0u8; PREFIX_LEN];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an encrypted Konserve archive",
+            ));
+        }
+        inner.read_exact(&mut salt)?;
+        inner.read_exact(&mut iterations)?;
+        inner.read_exact(&mut prefix)?;
+        let iterations = u32::from_le_bytes(iterations);
+        if iterations == 0 || iterations > MAX_ITERATIONS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("damaged archive header ({iterations} key iterations)"),
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            cipher: derive_key(passphrase, &salt, iterations),
+            prefix,
+            counter: 0,
+            plain: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    fn open_next(&mut self) -> io::Result<()> {
+        let mut sealed = vec![0u8; CHUNK + TAG_LEN];
+        let mut len = 0;
+        while len < sealed.len() {
+            match self.inner.read(&mut sealed[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        if len < TAG_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "encrypted archive is truncated",
+            ));
+        }
+        sealed.truncate(len);
+        let last = len < CHUNK + TAG_LEN;
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        self.plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), sealed.as_slice())
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "wrong passphrase or damaged archive",
+                )
+            })?;
+        self.pos = 0;
+        self.counter = self.counter.wrapping_add(1);
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.plain.len() {
+                let n = (self.plain.len() - self.pos).min(out.len());
+                out[..n].copy_from_slice(&self.plain[self.pos..self.pos + n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if self.done {
+                return Ok(0);
+            }
+            self.open_next()?;
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! synthetic archives laid out like backup_gui writes them, for reproducing archive bugs people
+//! report without needing their files. the diagnostics tab generates these on demand
+use crate::helpers::{
+    CHECKSUMS_ENTRY, get_fingered, note_line, render_checksums, render_manifest, to_hex,
+};
+use chrono::Local;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use tar::{Builder, Header};
+use uuid::Uuid;
+
+/// what's odd about a generated archive
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum FixtureKind {
+    /// a folder tree plus a single-file root, nothing wrong with it
+    #[default]
+    Plain,
+    /// same-named roots under different parents, and one path picked twice
+    Collisions,
+    /// manifest from some other build's fingerprint
+    WrongFingerprint,
+    /// roots recorded with the other OS's paths (C:\Users\… here, /home/… on windows)
+    OtherOs,
+    /// manifest and checksum list obfuscated
+    Obfuscated,
+    /// one file's recorded checksum doesn't match what's packed
+    BadChecksum,
+    /// archive cut off halfway through
+    Truncated,
+}
+
+impl FixtureKind {
+    pub const ALL: [Self; 7] = [
+        Self::Plain,
+        Self::Collisions,
+        Self::WrongFingerprint,
+        Self::OtherOs,
+        Self::Obfuscated,
+        Self::BadChecksum,
+        Self::Truncated,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Plain => "Plain tree",
+            Self::Collisions => "Colliding roots",
+            Self::WrongFingerprint => "Wrong fingerprint",
+            Self::OtherOs => "Paths from the other OS",
+            Self::Obfuscated => "Obfuscated manifest",
+            Self::BadChecksum => "Checksum mismatch",
+            Self::Truncated => "Truncated archive",
+        }
+    }
+
+    fn slug(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Collisions => "collisions",
+            Self::WrongFingerprint => "wrong_fingerprint",
+            Self::OtherOs => "other_os",
+            Self::Obfuscated => "obfuscated",
+            Self::BadChecksum => "bad_checksum",
+            Self::Truncated => "truncated",
+        }
+    }
+}
+
+/// how big the generated folder trees get: `width` subfolders per level, `depth` levels deep,
+/// `files` files in every folder
+#[derive(Clone, Copy)]
+pub struct FixtureShape {
+    pub width: usize,
+    pub depth: usize,
+    pub files: usize,
+}
+
+impl Default for FixtureShape {
+    fn default() -> Self {
+        Self {
+            width: 2,
+            depth: 2,
+            files: 3,
+        }
+    }
+}
+
+/// one root of a generated archive, `path` is what the manifest records
+pub struct FixtureRoot {
+    pub path: PathBuf,
+    /// packed as `uuid.ext` like a picked file, otherwise `uuid/rel` entries
+    pub single_file: bool,
+    /// `/`-separated path below the root -> contents
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// the roots a fixture of this kind holds
+pub fn fixture_roots(kind: FixtureKind, shape: FixtureShape) -> Vec<FixtureRoot> {
+    let home = match kind {
+        FixtureKind::OtherOs if cfg!(windows) => PathBuf::from("/home/someone"),
+        FixtureKind::OtherOs => PathBuf::from(r"C:\Users\someone"),
+        // restoring one of these shouldn't touch anything real
+        _ => fixtures_dir().join("home"),
+    };
+    // joined by hand so windows paths stay windows paths on linux and the other way round
+    let sep = match kind {
+        FixtureKind::OtherOs if cfg!(windows) => '/',
+        FixtureKind::OtherOs => '\\',
+        _ => std::path::MAIN_SEPARATOR,
+    };
+    let under = |rel: &[&str]| {
+        PathBuf::from(format!(
+            "{}{sep}{}",
+            home.display(),
+            rel.join(&sep.to_string())
+        ))
+    };
+
+    let tree = |root: &str| {
+        let mut files = Vec::new();
+        fill_tree(&mut files, root, "", shape, 0);
+        files
+    };
+    let folder = |rel: &[&str]| FixtureRoot {
+        files: tree(rel.last().copied().unwrap_or_default()),
+        path: under(rel),
+        single_file: false,
+    };
+    let single = |rel: &[&str]| FixtureRoot {
+        path: under(rel),
+        single_file: true,
+        files: vec![(String::new(), b"a single picked file\n".to_vec())],
+    };
+
+    match kind {
+        FixtureKind::Collisions => vec![
+            folder(&["AppData", "Roaming", "zen"]),
+            folder(&["AppData", "Local", "zen"]),
+            folder(&["Documents"]),
+            folder(&["Documents"]),
+        ],
+        FixtureKind::OtherOs => vec![
+            folder(&["AppData", "Roaming", "Fixture App"]),
+            single(&["Documents", "notes.txt"]),
+        ],
+        _ => vec![folder(&["Konserve fixture"]), single(&["notes.txt"])],
+    }
+}
+
+/// `shape.files` files per folder and `shape.width` subfolders until `shape.depth`
+fn fill_tree(
+    files: &mut Vec<(String, Vec<u8>)>,
+    root: &str,
+    prefix: &str,
+    shape: FixtureShape,
+    level: usize,
+) {
+    for i in 0..shape.files {
+        let rel = format!("{prefix}file_{i}.txt");
+        let body = format!("{root}/{rel}\n").repeat(i + 1);
+        files.push((rel, body.into_bytes()));
+    }
+    if level >= shape.depth {
+        return;
+    }
+    for i in 0..shape.width {
+        fill_tree(
+            files,
+            root,
+            &format!("{prefix}folder_{i}/"),
+            shape,
+            level + 1,
+        );
+    }
+}
+
+/// writes a fixture archive of this kind to `out`, in the same layout backup_gui produces
+pub fn write_fixture(kind: FixtureKind, shape: FixtureShape, out: &Path) -> Result<(), String> {
+    let roots = fixture_roots(kind, shape);
+    let obfuscate = kind == FixtureKind::Obfuscated;
+
+    let mut path_lines = String::new();
+    let mut note_lines = String::new();
+    let uuids: Vec<String> = roots.iter().map(|_| Uuid::new_v4().to_string()).collect();
+    for (uuid, root) in uuids.iter().zip(&roots) {
+        path_lines.push_str(&format!("{uuid}: {}\n", root.path.display()));
+    }
+    if let Some(first) = uuids.first() {
+        note_lines.push_str(&note_line(first, "generated fixture"));
+    }
+    let mut manifest = render_manifest(
+        &path_lines,
+        &note_lines,
+        &Uuid::new_v4().to_string(),
+        None,
+        obfuscate,
+    );
+    if kind == FixtureKind::WrongFingerprint {
+        manifest = manifest.replacen(get_fingered(), "SOME_OTHER_BUILD", 1);
+    }
+
+    if let Some(dir) = out.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {e}", dir.display()))?;
+    }
+    let file = File::create(out).map_err(|e| format!("can't create {}: {e}", out.display()))?;
+    let mut tar = Builder::new(BufWriter::new(file));
+    let mut append = |name: &str, data: &[u8]| {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Local::now().timestamp() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, name, data)
+            .map_err(|e| format!("can't write {name} into {}: {e}", out.display()))
+    };
+    append("fingerprint.txt", manifest.as_bytes())?;
+
+    let mut sums = Vec::new();
+    for (uuid, root) in uuids.iter().zip(&roots) {
+        for (rel, data) in &root.files {
+            let key = if root.single_file {
+                let name = root.path.to_string_lossy();
+                let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+                match name.rsplit_once('.') {
+                    Some((_, ext)) => format!("{uuid}.{ext}"),
+                    None => uuid.clone(),
+                }
+            } else {
+                format!("{uuid}/{rel}")
+            };
+            append(&key, data)?;
+            sums.push((key, to_hex(&Sha256::digest(data))));
+        }
+    }
+    if kind == FixtureKind::BadChecksum
+        && let Some((_, digest)) = sums.first_mut()
+    {
+        *digest = to_hex(&Sha256::digest(b"not what got packed"));
+    }
+    append(
+        CHECKSUMS_ENTRY,
+        render_checksums(&sums, obfuscate).as_bytes(),
+    )?;
+
+    tar.into_inner()
+        .and_then(|w| w.into_inner().map_err(|e| e.into_error()))
+        .map_err(|e| format!("can't finish {}: {e}", out.display()))?;
+
+    if kind == FixtureKind::Truncated {
+        let len = fs::metadata(out).map_err(|e| e.to_string())?.len();
+        File::options()
+            .write(true)
+            .open(out)
+            .and_then(|f| f.set_len(len / 2))
+            .map_err(|e| format!("can't truncate {}: {e}", out.display()))?;
+    }
+    Ok(())
+}
+
+fn fixtures_dir() -> PathBuf {
+    std::env::temp_dir().join("konserve-fixtures")
+}
+
+/// where the diagnostics tab puts generated fixtures, `fixture_<kind>.tar` in the temp dir
+pub fn fixture_path(kind: FixtureKind) -> PathBuf {
+    fixtures_dir().join(format!("fixture_{}.tar", kind.slug()))
+}
@@ -1,21 +1,26 @@
 ﻿//! grab bag of shared stuff: config, progress, path helpers, tree rendering, icon loading
 use crate::FolderTreeNode;
-use chrono::Local;
+use crate::crypt::open_archive_reader;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
 use eframe::egui;
 use eframe::egui::IconData;
 use egui::CollapsingHeader;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fs::{self, File, OpenOptions},
-    io::{Read, Write},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     sync::{
-        Arc, Mutex,
-        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
     },
+    time::{Duration, Instant},
 };
 use tar::Archive;
+use walkdir::WalkDir;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::ffi::OsStrExt;
@@ -29,6 +34,10 @@ use windows::Win32::System::RestartManager::{
 use windows::core::PCWSTR;
 
 static DEBUG_LOG: Mutex<Option<File>> = Mutex::new(None);
+thread_local! {
+    /// per job thread, so a backup and a restore running side by side keep separate logs
+    static JOB_LOG: RefCell<Option<File>> = const { RefCell::new(None) };
+}
 static CRASH_LOG: Mutex<Option<File>> = Mutex::new(None);
 
 pub fn verbose_log_path() -> PathBuf {
@@ -99,6 +108,9 @@ pub fn write_error_log(msg: &str) {
             let _ = f.flush();
         }
     }
+    append_job_log(msg);
+    push_log_panel(LogLevel::Error, msg);
+    count_error(msg);
 }
 
 #[macro_export]
@@ -108,6 +120,189 @@ macro_rules! elog {
     }
 }
 
+/// how bad a log panel line is, going by the prefix the log macros' callers put on it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn of(msg: &str) -> Self {
+        if msg.starts_with("[DEBUG]") {
+            LogLevel::Debug
+        } else if msg.starts_with("[WARN]") {
+            LogLevel::Warn
+        } else if msg.starts_with("ERROR") {
+            LogLevel::Error
+        } else {
+            LogLevel::Info
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "Debug",
+            LogLevel::Info => "Info",
+            LogLevel::Warn => "Warnings",
+            LogLevel::Error => "Errors",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LogLine {
+    pub time: String,
+    pub level: LogLevel,
+    pub msg: String,
+}
+
+/// lines the in-app log panel keeps, oldest go first
+const LOG_PANEL_LINES: usize = 2000;
+static LOG_PANEL: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+
+fn push_log_panel(level: LogLevel, msg: &str) {
+    let mut lines = LOG_PANEL.lock().unwrap_or_else(|e| e.into_inner());
+    if lines.len() == LOG_PANEL_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(LogLine {
+        time: Local::now().format("%H:%M:%S").to_string(),
+        level,
+        msg: msg.to_string(),
+    });
+}
+
+/// dlog/jlog lines for the panel. debug lines (one per packed file and the like) only while
+/// verbose logging is on, same as the verbose log file
+fn push_log_panel_checked(msg: &str, verbose: bool) {
+    let level = LogLevel::of(msg);
+    if level != LogLevel::Debug || verbose {
+        push_log_panel(level, msg);
+    }
+}
+
+/// what the log panel shows, `min` and worse, oldest first
+pub fn log_panel_lines(min: LogLevel) -> Vec<LogLine> {
+    let lines = LOG_PANEL.lock().unwrap_or_else(|e| e.into_inner());
+    lines.iter().filter(|l| l.level >= min).cloned().collect()
+}
+
+pub fn clear_log_panel() {
+    LOG_PANEL.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// writes a line to the verbose log file if it's open, false if verbose logging is off
+fn write_verbose_file(msg: &str) -> bool {
+    let Ok(mut guard) = DEBUG_LOG.lock() else {
+        return false;
+    };
+    let Some(f) = guard.as_mut() else {
+        return false;
+    };
+    let ts = Local::now().format("%Y-%m-%d %H:%M:%S");
+    let _ = writeln!(f, "[{ts}] {msg}");
+    true
+}
+
+/// opt-in, off until the config says otherwise
+static ERROR_STATS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_error_stats(on: bool) {
+    ERROR_STATS.store(on, Ordering::Relaxed);
+}
+
+/// category -> count, lives next to the config and never leaves the machine on its own
+pub fn error_stats_path() -> PathBuf {
+    KonserveConfig::config_path()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("error-stats.json")
+}
+
+/// category -> bits of an error line that put it there, first match wins
+const ERROR_CATEGORIES: [(&str, &[&str]); 9] = [
+    ("archive damaged", &["damaged", "unexpected eof", "corrupt"]),
+    ("encryption", &["passphrase", "decrypt", "authentication"]),
+    ("fingerprint or manifest", &["fingerprint", "obfuscated"]),
+    (
+        "incremental chain",
+        &["base backup", "unchanged files", "parent"],
+    ),
+    (
+        "permission denied",
+        &[
+            "permission denied",
+            "access is denied",
+            "os error 13)",
+            "os error 5)",
+        ],
+    ),
+    (
+        "file locked",
+        &["used by another process", "os error 32)", "os error 33)"],
+    ),
+    (
+        "disk full",
+        &["no space", "disk full", "os error 28)", "os error 112)"],
+    ),
+    ("not found", &["not found", "os error 2)", "os error 3)"]),
+    ("template", &["template"]),
+];
+
+/// rough bucket for an error line. only the bucket gets counted, never the message, so no
+/// paths or file names end up in the stats
+fn error_category(msg: &str) -> &'static str {
+    let msg = msg.to_lowercase();
+    ERROR_CATEGORIES
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|n| msg.contains(n)))
+        .map_or("other", |(category, _)| category)
+}
+
+/// bumps the error's category when error stats are switched on
+fn count_error(msg: &str) {
+    if !ERROR_STATS.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut stats = error_stats();
+    *stats.entry(error_category(msg).to_string()).or_default() += 1;
+    if let Ok(json) = serde_json::to_string_pretty(&stats) {
+        let _ = fs::write(error_stats_path(), json);
+    }
+}
+
+pub fn error_stats() -> BTreeMap<String, u64> {
+    fs::read_to_string(error_stats_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn clear_error_stats() {
+    let _ = fs::remove_file(error_stats_path());
+}
+
+/// the counters as text to paste into a bug report, with the version and OS since that's the
+/// first thing anyone asks
+pub fn error_stats_report() -> String {
+    let mut txt = format!(
+        "Konserve {} on {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS
+    );
+    let stats = error_stats();
+    if stats.is_empty() {
+        txt.push_str("no errors counted\n");
+    }
+    for (category, count) in stats {
+        txt.push_str(&format!("{category}: {count}\n"));
+    }
+    txt
+}
+
 /// opens (and wipes) the verbose log next to the config, called on startup or when the checkbox gets ticked
 pub fn init_verbose_log() {
     let path = verbose_log_path();
@@ -138,15 +333,24 @@ pub fn set_status(status: &Mutex<String>, msg: impl Into<String>) {
     *guard = msg.into();
 }
 
-/// prints to stdout and timestamps into the log file
+/// whether dlog echoes to stdout. on in debug builds, in release only with KONSERVE_LOG_STDOUT=1
+/// (release on windows has no console anyway, and on linux it was just spamming the terminal)
+fn dlog_to_stdout() -> bool {
+    static ECHO: OnceLock<bool> = OnceLock::new();
+    *ECHO.get_or_init(|| {
+        cfg!(debug_assertions)
+            || std::env::var("KONSERVE_LOG_STDOUT").is_ok_and(|v| !v.is_empty() && v != "0")
+    })
+}
+
+/// timestamps into the log file, and prints to stdout when dlog_to_stdout says so
 pub fn write_dlog(msg: &str) {
-    println!("{msg}");
-    if let Ok(mut guard) = DEBUG_LOG.lock()
-        && let Some(ref mut f) = *guard
-    {
-        let ts = Local::now().format("%Y-%m-%d %H:%M:%S");
-        let _ = writeln!(f, "[{ts}] {msg}");
+    if dlog_to_stdout() {
+        println!("{msg}");
     }
+    let verbose = write_verbose_file(msg);
+    append_job_log(msg);
+    push_log_panel_checked(msg, verbose);
 }
 
 #[macro_export]
@@ -156,6 +360,167 @@ macro_rules! dlog {
     }
 }
 
+/// per-job logs go in konserve/logs next to the config
+pub fn job_log_dir() -> PathBuf {
+    KonserveConfig::config_path()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("logs")
+}
+
+/// reserved top-level archive folder for konserve's own settings, never a uuid so it can't clash with a root
+pub const KONSERVE_CONFIG_ENTRY: &str = "konserve-config";
+
+/// konserve's own files as (name in the archive, where it lives on disk)
+/// templates saved through the file dialog can be anywhere, so only the exe-dir one is known
+fn konserve_config_locations() -> [(&'static str, PathBuf); 2] {
+    [
+        ("config.json", KonserveConfig::config_path()),
+        ("template.json", exe_dir().join("template.json")),
+    ]
+}
+
+/// the settings files that exist right now, for the "include konserve settings" backup option
+pub fn konserve_config_files() -> Vec<(&'static str, PathBuf)> {
+    konserve_config_locations()
+        .into_iter()
+        .filter(|(_, p)| p.is_file())
+        .collect()
+}
+
+/// where a settings file from the archive goes back to, None for names this build doesn't know
+pub fn konserve_config_target(name: &str) -> Option<PathBuf> {
+    konserve_config_locations()
+        .into_iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, p)| p)
+}
+
+/// log file for one backup/restore run, closes itself on drop so early `?` returns don't leave it open.
+/// belongs to the thread that started it, every job runs on its own thread
+pub struct JobLog {
+    pub path: Option<PathBuf>,
+}
+
+impl JobLog {
+    /// opens logs/{kind}_{timestamp}_{id}.log, best effort, path is None if it couldn't be created
+    pub fn start(kind: &str) -> Self {
+        let dir = job_log_dir();
+        let _ = fs::create_dir_all(&dir);
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let name = format!(
+            "{kind}_{}_{}.log",
+            Local::now().format("%Y-%m-%d_%H-%M-%S"),
+            &id[..8]
+        );
+        let path = dir.join(name);
+        let file = OpenOptions::new().create(true).append(true).open(&path);
+        let path = file.is_ok().then_some(path);
+        JOB_LOG.with_borrow_mut(|log| *log = file.ok());
+        Self { path }
+    }
+}
+
+impl Drop for JobLog {
+    fn drop(&mut self) {
+        JOB_LOG.with_borrow_mut(|log| *log = None);
+    }
+}
+
+/// one past backup/restore/verify run, read back from its job log
+pub struct JobRecord {
+    /// backup, restore or verify
+    pub kind: String,
+    /// local start time, from the log's name
+    pub started: String,
+    /// the log's first line, what got backed up to or restored from
+    pub summary: String,
+    /// the log's last line, how it ended. empty while it's still running
+    pub outcome: String,
+    pub log: PathBuf,
+}
+
+/// every job that left a log, newest first
+pub fn job_history() -> Vec<JobRecord> {
+    let Ok(dir) = fs::read_dir(job_log_dir()) else {
+        return Vec::new();
+    };
+    let mut jobs: Vec<JobRecord> = dir
+        .filter_map(Result::ok)
+        .filter_map(|e| {
+            let log = e.path();
+            if log.extension()? != "log" {
+                return None;
+            }
+            // {kind}_{date}_{time}_{id}, see JobLog::start
+            let name = log.file_stem()?.to_str()?.to_string();
+            let mut parts = name.splitn(4, '_');
+            let kind = parts.next()?.to_string();
+            let started = format!("{} {}", parts.next()?, parts.next()?.replace('-', ":"));
+            let (summary, outcome) = log_ends(&log);
+            Some(JobRecord {
+                kind,
+                started,
+                summary,
+                outcome,
+                log,
+            })
+        })
+        .collect();
+    jobs.sort_by(|a, b| b.started.cmp(&a.started));
+    jobs
+}
+
+/// first and last line of a job log minus their timestamps. only the end of the file gets read,
+/// a big restore's log runs to tens of megabytes
+fn log_ends(path: &Path) -> (String, String) {
+    let strip = |line: &str| {
+        line.split_once("] ")
+            .map_or(line, |(_, rest)| rest)
+            .trim()
+            .to_string()
+    };
+    let Ok(mut file) = File::open(path) else {
+        return Default::default();
+    };
+    let mut head = String::new();
+    let _ = BufReader::new(&file).read_line(&mut head);
+    let len = file.metadata().map_or(0, |m| m.len());
+    let mut tail = Vec::new();
+    if file.seek(SeekFrom::Start(len.saturating_sub(4096))).is_ok() {
+        let _ = file.read_to_end(&mut tail);
+    }
+    let tail = String::from_utf8_lossy(&tail);
+    let last = tail.lines().last().unwrap_or_default();
+    if last.trim() == head.trim() {
+        return (strip(&head), String::new());
+    }
+    (strip(&head), strip(last))
+}
+
+fn append_job_log(msg: &str) {
+    JOB_LOG.with_borrow_mut(|log| {
+        if let Some(f) = log {
+            let ts = Local::now().format("%Y-%m-%d %H:%M:%S");
+            let _ = writeln!(f, "[{ts}] {msg}");
+        }
+    });
+}
+
+/// job log + verbose log file, no stdout. for per-path lines that belong in the job log even with verbose off
+pub fn write_jlog(msg: &str) {
+    let verbose = write_verbose_file(msg);
+    append_job_log(msg);
+    push_log_panel_checked(msg, verbose);
+}
+
+#[macro_export]
+macro_rules! jlog {
+    ($($arg:tt)*) => {
+        $crate::helpers::write_jlog(&format!($($arg)*))
+    }
+}
+
 /// user settings, saved to konserve/config.json
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct KonserveConfig {
@@ -164,7 +529,7 @@ pub struct KonserveConfig {
     #[serde(default)]
     pub conflict_resolution_enabled: bool,
     #[serde(default)]
-    pub conflict_resolution_mode: super::ConflictResolutionMode,
+    pub conflict_resolution_mode: ConflictResolutionMode,
     #[serde(default)]
     pub default_backup_location: Option<PathBuf>,
     #[serde(default)]
@@ -179,89 +544,729 @@ pub struct KonserveConfig {
     pub load_templates_from_exe_dir: bool,
     #[serde(default)]
     pub backup_name_mode: BackupNameMode,
+    #[serde(default)]
+    pub obfuscate_manifest: bool,
+    #[serde(default)]
+    pub include_konserve_config: bool,
+    /// warn on the destinations panel after this many days without a backup, 0 = never
+    #[serde(default)]
+    pub stale_after_days: u32,
+    #[serde(default)]
+    pub ownership_mode: OwnershipMode,
+    /// leave owners alone when the restore isn't running as root, chown would fail anyway
+    #[serde(default)]
+    pub ownership_root_only: bool,
+    #[serde(default)]
+    pub dir_permissions: DirPermissions,
+    /// max GB of .tar files per destination folder, backups into a full one get refused
+    #[serde(default)]
+    pub destination_quotas: HashMap<PathBuf, u32>,
+    /// per destination folder, how many old timestamped backups to keep around
+    #[serde(default)]
+    pub retention: HashMap<PathBuf, Retention>,
+    /// where in-progress archives get written before being moved into place, None = straight into the destination
+    #[serde(default)]
+    pub scratch_dir: Option<PathBuf>,
+    /// warn under the progress bar when one entry takes longer than this many seconds, 0 = never
+    #[serde(default)]
+    pub stall_warn_secs: u32,
+    /// ask for a passphrase on the home tab and write encrypted archives
+    #[serde(default)]
+    pub encrypt_backups: bool,
+    /// only pack what changed since the newest archive already in the destination
+    #[serde(default)]
+    pub incremental_backups: bool,
+    /// pack only what changed since this archive, for backups into its folder. beats incremental
+    #[serde(default)]
+    pub differential_baseline: Option<PathBuf>,
+    /// skipped in every backup, see exclude_pattern_matches. templates can add their own
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// how many backups/restores may run at once, 0 (old configs) counts as 1
+    #[serde(default)]
+    pub max_jobs: u32,
+    /// progress bar / drop zone / primary button color
+    #[serde(default)]
+    pub accent_color: AccentColor,
+    /// write a <archive>.sha256 next to every backup
+    #[serde(default)]
+    pub checksum_export: ChecksumExport,
+    #[serde(default)]
+    pub theme: Theme,
+    /// write a restic-style <archive>.snapshot.json next to every backup
+    #[serde(default)]
+    pub snapshot_json: bool,
+    /// count error categories locally, see count_error
+    #[serde(default)]
+    pub error_stats: bool,
+    /// windows: back up from volume shadow copies, see vss
+    #[serde(default)]
+    pub shadow_copy: bool,
+    /// backups started on battery wait until the laptop is plugged in
+    #[serde(default)]
+    pub hold_on_battery: bool,
+    /// restore symlinks as copies of their targets instead of as links
+    #[serde(default)]
+    pub materialize_symlinks: bool,
+    /// restored files get the time of the restore as their mtime, not the archived one
+    #[serde(default)]
+    pub reset_mtimes: bool,
 }
 
-pub fn exe_dir() -> PathBuf {
-    std::env::current_exe()
+/// which roots of each template existed at its last backup, keyed by template file
+fn template_snapshots_path() -> PathBuf {
+    KonserveConfig::config_path()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("template_snapshots.json")
+}
+
+fn read_template_snapshots() -> HashMap<PathBuf, Vec<PathBuf>> {
+    fs::read_to_string(template_snapshots_path())
         .ok()
-        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
-        .unwrap_or(PathBuf::from("."))
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
 }
 
-#[cfg(target_os = "windows")]
-pub fn processes_locking_paths(
-    paths: &[PathBuf],
-    verbose: bool,
-) -> std::collections::HashSet<String> {
-    use std::collections::HashSet;
-    let mut locked_by: HashSet<String> = HashSet::new();
+/// roots that existed the last time this template got backed up, empty if it never was
+pub fn load_template_snapshot(template: &Path) -> Vec<PathBuf> {
+    read_template_snapshots()
+        .remove(template)
+        .unwrap_or_default()
+}
 
-    // restart manager only works on files so flatten folders down to their contents
-    let mut files: Vec<PathBuf> = Vec::new();
-    for p in paths {
-        if p.is_file() {
-            files.push(p.clone());
-        } else if p.is_dir() {
-            for entry in walkdir::WalkDir::new(p).into_iter().filter_map(Result::ok) {
-                if entry.file_type().is_file() {
-                    files.push(entry.path().to_path_buf());
-                }
+/// records which template roots made it into a successful backup
+pub fn save_template_snapshot(template: &Path, present: &[PathBuf]) {
+    let mut all = read_template_snapshots();
+    all.insert(template.to_path_buf(), present.to_vec());
+    let path = template_snapshots_path();
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    match serde_json::to_string_pretty(&all) {
+        Ok(json) => {
+            if let Err(e) = fs::write(&path, json) {
+                write_error_log(&format!(
+                    "ERROR: failed to save template snapshot {}: {e}",
+                    path.display()
+                ));
             }
         }
+        Err(e) => write_error_log(&format!(
+            "ERROR: failed to serialize template snapshot: {e}"
+        )),
     }
+}
 
-    if files.is_empty() {
-        return locked_by;
-    }
+/// how many templates the home tab's recent list holds
+const RECENT_TEMPLATES_KEEP: usize = 8;
 
-    if verbose {
-        dlog!(
-            "[DEBUG] RestartManager: scanning {} files for locks",
-            files.len()
-        );
+/// templates opened or saved lately, newest first
+fn recent_templates_path() -> PathBuf {
+    KonserveConfig::config_path()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("recent_templates.json")
+}
+
+/// templates opened or saved lately, newest first, empty if there's no list yet
+pub fn recent_templates() -> Vec<PathBuf> {
+    fs::read_to_string(recent_templates_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_recent_templates(recent: &[PathBuf]) {
+    let path = recent_templates_path();
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    match serde_json::to_string_pretty(recent) {
+        Ok(json) => {
+            if let Err(e) = fs::write(&path, json) {
+                write_error_log(&format!(
+                    "ERROR: failed to save recent templates {}: {e}",
+                    path.display()
+                ));
+            }
+        }
+        Err(e) => write_error_log(&format!("ERROR: failed to serialize recent templates: {e}")),
     }
+}
 
-    // build null-terminated wide strings for each path, gotta stay alive for the whole unsafe block
-    let wide_paths: Vec<Vec<u16>> = files
-        .iter()
-        .map(|p| {
-            let mut w: Vec<u16> = p.as_os_str().encode_wide().collect();
-            w.push(0);
-            w
+/// puts `template` at the top of the recent list, the oldest falls off past
+/// RECENT_TEMPLATES_KEEP. hands back the new list
+pub fn remember_template(template: &Path) -> Vec<PathBuf> {
+    let mut recent = recent_templates();
+    recent.retain(|p| p != template);
+    recent.insert(0, template.to_path_buf());
+    recent.truncate(RECENT_TEMPLATES_KEEP);
+    save_recent_templates(&recent);
+    recent
+}
+
+/// takes `template` off the recent list, for ones that aren't there anymore
+pub fn forget_template(template: &Path) -> Vec<PathBuf> {
+    let mut recent = recent_templates();
+    recent.retain(|p| p != template);
+    save_recent_templates(&recent);
+    recent
+}
+
+/// how many earlier versions of each template are kept
+const TEMPLATE_HISTORY_KEEP: u32 = 20;
+
+/// konserve/template-history/<name>-<hash of its path>/, so same-named templates in different
+/// folders don't share a history
+fn template_history_dir(template: &Path) -> PathBuf {
+    let real = fs::canonicalize(template).unwrap_or_else(|_| template.to_path_buf());
+    let hash = to_hex(&Sha256::digest(real.to_string_lossy().as_bytes()));
+    let stem = template.file_stem().unwrap_or_default().to_string_lossy();
+    KonserveConfig::config_path()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("template-history")
+        .join(format!("{stem}-{}", &hash[..8]))
+}
+
+/// one kept version of a template
+pub struct TemplateVersion {
+    pub number: u32,
+    pub saved: Option<std::time::SystemTime>,
+    pub path: PathBuf,
+}
+
+/// earlier versions of a template, newest first
+pub fn template_versions(template: &Path) -> Vec<TemplateVersion> {
+    let Ok(dir) = fs::read_dir(template_history_dir(template)) else {
+        return Vec::new();
+    };
+    let mut out: Vec<TemplateVersion> = dir
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let number = path.file_stem()?.to_str()?.parse().ok()?;
+            let saved = e.metadata().and_then(|m| m.modified()).ok();
+            Some(TemplateVersion {
+                number,
+                saved,
+                path,
+            })
         })
         .collect();
+    out.sort_by_key(|v| std::cmp::Reverse(v.number));
+    out
+}
 
-    // PCWSTR is just a const pointer cast from the wide vec
-    let pcwstrs: Vec<PCWSTR> = wide_paths.iter().map(|w| PCWSTR(w.as_ptr())).collect();
+/// keeps what's in the template file right now as the next numbered version, called before it
+/// gets overwritten. nothing happens for a new file or one identical to the newest version
+pub fn save_template_version(template: &Path) {
+    let Ok(current) = fs::read_to_string(template) else {
+        return;
+    };
+    let versions = template_versions(template);
+    if let Some(newest) = versions.first()
+        && fs::read_to_string(&newest.path).is_ok_and(|v| v == current)
+    {
+        return;
+    }
+    let dir = template_history_dir(template);
+    let number = versions.first().map_or(1, |v| v.number + 1);
+    let path = dir.join(format!("{number}.json"));
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, current)) {
+        write_error_log(&format!(
+            "ERROR: failed to keep template version {}: {e}",
+            path.display()
+        ));
+        return;
+    }
+    for old in versions.iter().skip(TEMPLATE_HISTORY_KEEP as usize - 1) {
+        let _ = fs::remove_file(&old.path);
+    }
+}
 
-    unsafe {
-        let mut session: u32 = 0;
-        let mut session_key = [0u16; 33];
+/// line diff for the template history view: ' ' kept, '-' only in old, '+' only in new.
+/// plain LCS, templates are a few hundred lines at most
+pub fn line_diff(old: &str, new: &str) -> Vec<(char, String)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    // lcs[i][j] = common lines of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push((' ', a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(('-', a[i].to_string()));
+            i += 1;
+        } else {
+            out.push(('+', b[j].to_string()));
+            j += 1;
+        }
+    }
+    out
+}
 
-        if RmStartSession(
-            &mut session,
-            Some(0u32),
-            windows::core::PWSTR(session_key.as_mut_ptr()),
-        )
-        .is_err()
+/// what the destinations panel shows for one backup folder
+pub struct DestinationHealth {
+    pub path: PathBuf,
+    pub reachable: bool,
+    pub archives: usize,
+    pub total_bytes: u64,
+    /// mtime of the newest .tar in there
+    pub last_backup: Option<std::time::SystemTime>,
+}
+
+/// looks at the .tar files sitting directly in `path`, doesn't open them
+pub fn destination_health(path: &Path) -> DestinationHealth {
+    let mut health = DestinationHealth {
+        path: path.to_path_buf(),
+        reachable: false,
+        archives: 0,
+        total_bytes: 0,
+        last_backup: None,
+    };
+    let Ok(dir) = fs::read_dir(path) else {
+        return health;
+    };
+    health.reachable = true;
+    for entry in dir.flatten() {
+        let p = entry.path();
+        if p.extension().is_none_or(|e| !e.eq_ignore_ascii_case("tar")) {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        health.archives += 1;
+        health.total_bytes += meta.len();
+        if let Ok(modified) = meta.modified()
+            && health.last_backup.is_none_or(|last| modified > last)
         {
-            if verbose {
-                dlog!("[DEBUG] RestartManager: RmStartSession failed");
-            }
-            return locked_by;
+            health.last_backup = Some(modified);
         }
+    }
+    health
+}
 
-        if RmRegisterResources(session, Some(pcwstrs.as_slice()), None, None).is_err() {
-            if verbose {
-                dlog!("[DEBUG] RestartManager: RmRegisterResources failed");
-            }
-            let _ = RmEndSession(session);
-            return locked_by;
-        }
+/// what "Check" on the destinations panel finds in one folder, mostly leftovers of archives that
+/// got moved or deleted by hand
+#[derive(Default)]
+pub struct DestinationCheck {
+    pub path: PathBuf,
+    /// `.sha256` / `.snapshot.json` files whose archive isn't next to them any more
+    pub orphan_sidecars: Vec<PathBuf>,
+    /// incremental/differential archives that can't be restored as they are, with why (parent
+    /// missing, or replaced by a different backup of the same name)
+    pub broken_chains: Vec<(PathBuf, String)>,
+    /// archives whose manifest couldn't be read (encrypted with an unknown passphrase, damaged,
+    /// not Konserve's), their chains weren't checked
+    pub unreadable: usize,
+}
 
-        let mut needed: u32 = 0;
-        let mut count: u32 = 0;
-        let mut reboot_reasons: u32 = 0;
+impl DestinationCheck {
+    pub fn is_clean(&self) -> bool {
+        self.orphan_sidecars.is_empty() && self.broken_chains.is_empty()
+    }
+}
+
+/// opens every archive in `dir` to follow its parent link, so slower than destination_health
+pub fn check_destination(dir: &Path, verbose: bool) -> DestinationCheck {
+    let mut check = DestinationCheck {
+        path: dir.to_path_buf(),
+        ..Default::default()
+    };
+    let Ok(read) = fs::read_dir(dir) else {
+        return check;
+    };
+    for entry in read.flatten() {
+        let p = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(archive) = ARCHIVE_SIDECARS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            && !dir.join(archive).is_file()
+        {
+            check.orphan_sidecars.push(p);
+        }
+    }
+    check.orphan_sidecars.sort();
+
+    // parent ids get read once each, a full backup can have a long list of children
+    let mut ids: HashMap<PathBuf, Option<String>> = HashMap::new();
+    for archive in archives_in(&[dir.to_path_buf()]) {
+        let manifest = match read_manifest(&archive, verbose) {
+            Ok(m) => m,
+            Err(e) => {
+                dlog!("[WARN] check: can't read {}: {e}", archive.display());
+                check.unreadable += 1;
+                continue;
+            }
+        };
+        ids.entry(archive.clone()).or_insert(manifest.id);
+        let Some(parent) = manifest.parent else {
+            continue;
+        };
+        let parent_path = dir.join(&parent.name);
+        if !parent_path.is_file() {
+            check
+                .broken_chains
+                .push((archive, format!("{} is missing", parent.name)));
+            continue;
+        }
+        let parent_id = ids
+            .entry(parent_path.clone())
+            .or_insert_with(|| read_manifest(&parent_path, verbose).ok().and_then(|m| m.id));
+        if parent.id.is_some() && parent_id.is_some() && parent.id != *parent_id {
+            check.broken_chains.push((
+                archive,
+                format!("{} was replaced by a different backup", parent.name),
+            ));
+        }
+    }
+    check.broken_chains.sort();
+    check
+}
+
+/// the archive would end up inside one of the source folders (and try to pack itself while it
+/// grows), or a source folder is inside the destination. Some(message) if so
+pub fn destination_overlap(folders: &[PathBuf], dest: &Path) -> Option<String> {
+    // canonical where possible so `..`, symlinks and drive letter case don't hide an overlap
+    let real = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let dest_real = real(dest);
+    folders.iter().find_map(|folder| {
+        let folder_real = real(folder);
+        // a single file can't swallow anything, only folders count
+        if !folder_real.is_dir() {
+            None
+        } else if dest_real.starts_with(&folder_real) {
+            Some(format!(
+                "The destination {} is inside {}, the backup would try to pack itself.\nPick a destination outside the selected folders.",
+                dest.display(),
+                folder.display()
+            ))
+        } else if folder_real.starts_with(&dest_real) {
+            Some(format!(
+                "{} is inside the destination {}, it would end up packing earlier backups.\nPick a destination outside the selected folders.",
+                folder.display(),
+                dest.display()
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+/// selected paths that sit inside another selected folder or are picked twice, as indexes (nested,
+/// the selected folder it's in). a path picked twice counts as nested in its first pick. compared
+/// canonical so `..` and drive letter case don't hide it
+pub fn nested_selections(paths: &[PathBuf]) -> Vec<(usize, usize)> {
+    let real: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| fs::canonicalize(p).unwrap_or_else(|_| p.clone()))
+        .collect();
+    let mut out = Vec::new();
+    for (i, child) in real.iter().enumerate() {
+        let outer = real.iter().enumerate().find(|(j, parent)| {
+            *j != i
+                && child.starts_with(parent)
+                && (child != *parent || *j < i)
+                && (child == *parent || parent.is_dir())
+        });
+        if let Some((j, _)) = outer {
+            out.push((i, j));
+        }
+    }
+    out
+}
+
+/// the first path of `a` that is, contains or sits inside one of `b`, for keeping jobs that run at
+/// the same time off each other's files
+pub fn shared_path<'a>(a: &'a [PathBuf], b: &[PathBuf]) -> Option<&'a PathBuf> {
+    // restore targets might not exist yet, those compare as they are
+    let real = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let b: Vec<PathBuf> = b.iter().map(|p| real(p)).collect();
+    a.iter().find(|p| {
+        let p = real(p);
+        b.iter().any(|q| p.starts_with(q) || q.starts_with(&p))
+    })
+}
+
+/// 1536 -> "1.5 KB", base 1024 like explorer
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// 192s -> "3m 12s", only the two biggest units, nobody cares about seconds after an hour
+pub fn format_duration(d: std::time::Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        3_600..86_400 => format!("{}h {:02}m", secs / 3_600, secs % 3_600 / 60),
+        _ => format!("{}d {}h", secs / 86_400, secs % 86_400 / 3_600),
+    }
+}
+
+/// "2 hours ago", "yesterday", ... past two months it's just the date
+pub fn format_ago(t: std::time::SystemTime) -> String {
+    // clock skew on a network share can put mtimes slightly in the future
+    let secs = t.elapsed().map_or(0, |d| d.as_secs());
+    match secs {
+        0..60 => "just now".into(),
+        60..3_600 => format!("{} ago", format_count((secs / 60) as usize, "minute")),
+        3_600..86_400 => format!("{} ago", format_count((secs / 3_600) as usize, "hour")),
+        86_400..172_800 => "yesterday".into(),
+        172_800..1_209_600 => format!("{} days ago", secs / 86_400),
+        1_209_600..5_184_000 => format!("{} weeks ago", secs / 604_800),
+        _ => chrono::DateTime::<Local>::from(t)
+            .format("%Y-%m-%d")
+            .to_string(),
+    }
+}
+
+/// exact local time, for tooltips next to format_ago
+pub fn format_timestamp(t: std::time::SystemTime) -> String {
+    chrono::DateTime::<Local>::from(t)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// "1 file", "3 files". only for nouns that take a plain s
+pub fn format_count(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+pub fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        .unwrap_or(PathBuf::from("."))
+}
+
+/// wayland app id / x11 WM_CLASS, matches the konserve.desktop entry so the compositor finds its icon
+/// and groups the window under it
+pub const APP_ID: &str = "konserve";
+
+/// the 256x256 app icon, also installed next to the desktop entry on linux
+const ICON_PNG: &[u8] = include_bytes!("../assets/icon.png");
+
+/// writes the "open .tar with konserve" association for this exe, returns what got written
+/// windows gets a .reg next to the exe to double-click (per-user, no admin), linux a .desktop entry
+#[cfg(target_os = "windows")]
+pub fn write_file_association() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    // .reg strings escape backslashes and quotes
+    let exe = exe.display().to_string().replace('\\', "\\\\");
+    let reg = format!(
+        "Windows Registry Editor Version 5.00\r\n\r\n\
+         [HKEY_CURRENT_USER\\Software\\Classes\\.tar\\OpenWithProgids]\r\n\
+         \"Konserve.Archive\"=\"\"\r\n\r\n\
+         [HKEY_CURRENT_USER\\Software\\Classes\\Konserve.Archive]\r\n\
+         @=\"Konserve backup\"\r\n\r\n\
+         [HKEY_CURRENT_USER\\Software\\Classes\\Konserve.Archive\\DefaultIcon]\r\n\
+         @=\"\\\"{exe}\\\",0\"\r\n\r\n\
+         [HKEY_CURRENT_USER\\Software\\Classes\\Konserve.Archive\\shell\\open\\command]\r\n\
+         @=\"\\\"{exe}\\\" \\\"%1\\\"\"\r\n"
+    );
+    let path = exe_dir().join("konserve-open-with.reg");
+    // regedit wants utf-16 for non-ascii paths
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(reg.encode_utf16().flat_map(|u| u.to_le_bytes()));
+    fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(target_os = "linux")]
+pub fn write_file_association() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let data = dirs::data_dir().ok_or("no data dir for this user")?;
+    let apps = data.join("applications");
+    fs::create_dir_all(&apps).map_err(|e| e.to_string())?;
+    // Icon= is looked up by name in the icon theme, a missing icon only costs the picture
+    let icons = data.join("icons/hicolor/256x256/apps");
+    if let Err(e) = fs::create_dir_all(&icons)
+        .and_then(|()| fs::write(icons.join(format!("{APP_ID}.png")), ICON_PNG))
+    {
+        dlog!("[WARN] couldn't install the app icon: {e}");
+    }
+    // desktop entry Exec quoting: double quotes, with " ` $ \ escaped
+    let mut quoted = String::from("\"");
+    for c in exe.display().to_string().chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Konserve\n\
+         Comment=Restore a Konserve backup\n\
+         Exec={quoted} %f\n\
+         Icon={APP_ID}\n\
+         StartupWMClass={APP_ID}\n\
+         Terminal=false\n\
+         MimeType=application/x-tar;\n\
+         Categories=Utility;Archiving;\n"
+    );
+    let path = apps.join(format!("{APP_ID}.desktop"));
+    fs::write(&path, entry).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn write_file_association() -> Result<PathBuf, String> {
+    Err("file association isn't supported on this platform yet".into())
+}
+
+/// drops a launcher into the file manager's "send to" spot that opens konserve with the picked paths
+/// windows: a .cmd in the SendTo folder, linux: a nautilus script. returns what got written
+#[cfg(target_os = "windows")]
+pub fn write_send_to() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let send_to = dirs::data_dir()
+        .ok_or("no AppData folder for this user")?
+        .join("Microsoft")
+        .join("Windows")
+        .join("SendTo");
+    fs::create_dir_all(&send_to).map_err(|e| e.to_string())?;
+    // %* passes the picked paths through, start so the console window closes right away
+    let script = format!("@echo off\r\nstart \"\" \"{}\" %*\r\n", exe.display());
+    let path = send_to.join("Konserve.cmd");
+    fs::write(&path, script).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(target_os = "linux")]
+pub fn write_send_to() -> Result<PathBuf, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let scripts = dirs::data_dir()
+        .ok_or("no data dir for this user")?
+        .join("nautilus")
+        .join("scripts");
+    fs::create_dir_all(&scripts).map_err(|e| e.to_string())?;
+    let exe = exe.display().to_string().replace('\'', "'\\''");
+    let script = format!("#!/bin/sh\nexec '{exe}' \"$@\"\n");
+    let path = scripts.join("Send to Konserve");
+    fs::write(&path, script).map_err(|e| e.to_string())?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn write_send_to() -> Result<PathBuf, String> {
+    Err("send to integration isn't supported on this platform yet".into())
+}
+
+#[cfg(target_os = "windows")]
+pub fn processes_locking_paths(
+    paths: &[PathBuf],
+    verbose: bool,
+) -> std::collections::HashSet<String> {
+    use std::collections::HashSet;
+    let mut locked_by: HashSet<String> = HashSet::new();
+
+    // restart manager only works on files so flatten folders down to their contents
+    let mut files: Vec<PathBuf> = Vec::new();
+    for p in paths {
+        if p.is_file() {
+            files.push(p.clone());
+        } else if p.is_dir() {
+            for entry in walkdir::WalkDir::new(p).into_iter().filter_map(Result::ok) {
+                if entry.file_type().is_file() {
+                    files.push(entry.path().to_path_buf());
+                }
+            }
+        }
+    }
+
+    if files.is_empty() {
+        return locked_by;
+    }
+
+    if verbose {
+        dlog!(
+            "[DEBUG] RestartManager: scanning {} files for locks",
+            files.len()
+        );
+    }
+
+    // build null-terminated wide strings for each path, gotta stay alive for the whole unsafe block
+    let wide_paths: Vec<Vec<u16>> = files
+        .iter()
+        .map(|p| {
+            let mut w: Vec<u16> = p.as_os_str().encode_wide().collect();
+            w.push(0);
+            w
+        })
+        .collect();
+
+    // PCWSTR is just a const pointer cast from the wide vec
+    let pcwstrs: Vec<PCWSTR> = wide_paths.iter().map(|w| PCWSTR(w.as_ptr())).collect();
+
+    unsafe {
+        let mut session: u32 = 0;
+        let mut session_key = [0u16; 33];
+
+        if RmStartSession(
+            &mut session,
+            Some(0u32),
+            windows::core::PWSTR(session_key.as_mut_ptr()),
+        )
+        .is_err()
+        {
+            if verbose {
+                dlog!("[DEBUG] RestartManager: RmStartSession failed");
+            }
+            return locked_by;
+        }
+
+        if RmRegisterResources(session, Some(pcwstrs.as_slice()), None, None).is_err() {
+            if verbose {
+                dlog!("[DEBUG] RestartManager: RmRegisterResources failed");
+            }
+            let _ = RmEndSession(session);
+            return locked_by;
+        }
+
+        let mut needed: u32 = 0;
+        let mut count: u32 = 0;
+        let mut reboot_reasons: u32 = 0;
 
         // first call with no buffer, just to find out how big needed is
         let res = RmGetList(
@@ -321,8 +1326,12 @@ pub fn processes_locking_paths(
 }
 
 impl KonserveConfig {
-    /// resolves konserve/config.json next to the exe
+    /// resolves konserve/config.json next to the exe, or in KONSERVE_CONFIG_DIR when that's set
+    /// (tests point it at a temp dir so their job logs don't land in the real history)
     fn config_path() -> PathBuf {
+        if let Some(dir) = std::env::var_os("KONSERVE_CONFIG_DIR").filter(|d| !d.is_empty()) {
+            return PathBuf::from(dir).join("config.json");
+        }
         let base = std::env::current_exe()
             .ok()
             .and_then(|p| p.parent().map(|d| d.to_path_buf()))
@@ -388,17 +1397,402 @@ pub enum ConflictResolutionMode {
     Rename,
 }
 
+/// who owns restored files on unix, ignored on windows
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OwnershipMode {
+    /// whoever runs the restore, same as before
+    #[default]
+    Restorer,
+    /// owner from the archive, matched by user/group name first so the old box's uid maps to the same user here
+    FromArchive,
+    /// everything gets this uid/gid
+    Fixed { uid: u32, gid: u32 },
+}
+
+/// permissions for directories a restore has to create, unix only
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DirPermissions {
+    /// folders stored in the archive keep their archived mode, parents created on the way get the umask.
+    /// same as before
+    #[default]
+    FromArchive,
+    /// copy the mode of the closest existing parent
+    InheritParent,
+    /// always this mode, e.g. 0o700 for config dirs
+    Fixed(u32),
+}
+
+/// standalone checksum file written next to each archive, so it can be checked without konserve
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ChecksumExport {
+    #[default]
+    Off,
+    /// `<hex>  <path>`, for `sha256sum -c`
+    Sha256sum,
+    /// `SHA256 (<path>) = <hex>`, for `shasum -c` and the BSD tools
+    Bsd,
+}
+
+impl ChecksumExport {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChecksumExport::Off => "Off",
+            ChecksumExport::Sha256sum => "sha256sum",
+            ChecksumExport::Bsd => "BSD style",
+        }
+    }
+
+    /// file contents for `sums` (entry name, hex digest). names are archive entries, so the
+    /// file checks a copy of the archive extracted with plain `tar -xf`. None when Off
+    pub fn render(self, sums: &[(String, String)]) -> Option<String> {
+        let line = |(name, digest): &(String, String)| match self {
+            ChecksumExport::Off => String::new(),
+            // coreutils marks names with a backslash or newline by a leading backslash
+            ChecksumExport::Sha256sum if name.contains(['\\', '\n']) => format!(
+                "\\{digest}  {}\n",
+                name.replace('\\', "\\\\").replace('\n', "\\n")
+            ),
+            ChecksumExport::Sha256sum => format!("{digest}  {name}\n"),
+            ChecksumExport::Bsd => format!("SHA256 ({name}) = {digest}\n"),
+        };
+        (self != ChecksumExport::Off).then(|| sums.iter().map(line).collect())
+    }
+}
+
+/// `<archive>.sha256`, right next to it
+pub fn checksum_export_path(archive: &Path) -> PathBuf {
+    let mut name = archive.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    archive.with_file_name(name)
+}
+
+/// light or dark visuals
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Theme {
+    /// whatever the os is set to, egui's default
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "Follow system",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+
+    pub fn preference(self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+/// where the accent (progress bar, drop zone highlight, primary buttons) comes from
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum AccentColor {
+    /// konserve's own blue, same as before
+    #[default]
+    Default,
+    /// the desktop's accent color, falls back to Default if the os doesn't have one
+    System,
+    Custom([u8; 3]),
+}
+
+impl AccentColor {
+    /// (highlight, button fill). System asks the os, so call this once and keep the result around
+    pub fn colors(self) -> (egui::Color32, egui::Color32) {
+        let rgb = match self {
+            AccentColor::Default => None,
+            AccentColor::System => system_accent(),
+            AccentColor::Custom(rgb) => Some(rgb),
+        };
+        match rgb {
+            // the fill is darker so white button text stays readable
+            Some([r, g, b]) => (
+                egui::Color32::from_rgb(r, g, b),
+                egui::Color32::from_rgb(r / 2, g / 2 + g / 8, b / 2 + b / 4),
+            ),
+            None => (
+                egui::Color32::from_rgb(80, 160, 240),
+                egui::Color32::from_rgb(40, 100, 180),
+            ),
+        }
+    }
+}
+
+/// windows keeps the accent as ABGR in the DWM key
+#[cfg(target_os = "windows")]
+pub fn system_accent() -> Option<[u8; 3]> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\DWM",
+            "/v",
+            "AccentColor",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let hex = text
+        .lines()
+        .find(|l| l.contains("AccentColor"))?
+        .split_whitespace()
+        .last()?
+        .trim_start_matches("0x");
+    let abgr = u32::from_str_radix(hex, 16).ok()?;
+    Some([abgr as u8, (abgr >> 8) as u8, (abgr >> 16) as u8])
+}
+
+/// AppleAccentColor is a palette index, no key at all means the default blue
+#[cfg(target_os = "macos")]
+pub fn system_accent() -> Option<[u8; 3]> {
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleAccentColor"])
+        .output()
+        .ok()?;
+    let index = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<i32>()
+        .unwrap_or(4);
+    Some(match index {
+        -1 => [140, 140, 140],
+        0 => [255, 82, 89],
+        1 => [247, 130, 27],
+        2 => [255, 199, 38],
+        3 => [98, 186, 70],
+        5 => [165, 80, 167],
+        6 => [247, 79, 158],
+        _ => [0, 122, 255],
+    })
+}
+
+/// gnome 47+ accent names (libadwaita's palette), other desktops don't expose one we can read
+#[cfg(target_os = "linux")]
+pub fn system_accent() -> Option<[u8; 3]> {
+    let output = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "accent-color"])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout);
+    Some(match name.trim().trim_matches('\'') {
+        "blue" => [53, 132, 228],
+        "teal" => [33, 144, 164],
+        "green" => [58, 148, 74],
+        "yellow" => [200, 136, 0],
+        "orange" => [237, 91, 0],
+        "red" => [230, 45, 66],
+        "pink" => [213, 97, 153],
+        "purple" => [145, 65, 172],
+        "slate" => [111, 131, 150],
+        _ => return None,
+    })
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+pub fn system_accent() -> Option<[u8; 3]> {
+    None
+}
+
+/// running off a battery right now. desktops and anything that can't be read count as plugged in,
+/// so a guess never holds a backup back
+#[cfg(target_os = "windows")]
+pub fn on_battery() -> bool {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    // ACLineStatus: 0 offline, 1 online, 255 unknown
+    unsafe { GetSystemPowerStatus(&mut status) }.is_ok() && status.ACLineStatus == 0
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains("'Battery Power'"))
+}
+
+/// any mains adapter online means plugged in, otherwise a discharging battery means battery
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let read = |dir: &Path, name: &str| {
+        fs::read_to_string(dir.join(name))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut discharging = false;
+    for supply in supplies.flatten() {
+        let dir = supply.path();
+        match read(&dir, "type").as_str() {
+            "Mains" if read(&dir, "online") == "1" => return false,
+            "Battery" if read(&dir, "status") == "Discharging" => discharging = true,
+            _ => {}
+        }
+    }
+    discharging
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+pub fn on_battery() -> bool {
+    false
+}
+
+/// name <-> id tables from /etc/passwd and /etc/group so archives can carry owner names
+#[cfg(unix)]
+#[derive(Default)]
+pub struct UnixIds {
+    user_ids: HashMap<String, u32>,
+    user_names: HashMap<u32, String>,
+    group_ids: HashMap<String, u32>,
+    group_names: HashMap<u32, String>,
+}
+
+#[cfg(unix)]
+impl UnixIds {
+    /// reads both files once, missing/unreadable ones just leave that table empty
+    pub fn load() -> Self {
+        let (user_ids, user_names) = Self::read_table("/etc/passwd");
+        let (group_ids, group_names) = Self::read_table("/etc/group");
+        Self {
+            user_ids,
+            user_names,
+            group_ids,
+            group_names,
+        }
+    }
+
+    /// both files are `name:x:id:...`, first entry wins on duplicates like getpwnam
+    fn read_table(path: &str) -> (HashMap<String, u32>, HashMap<u32, String>) {
+        let mut ids = HashMap::new();
+        let mut names = HashMap::new();
+        for line in fs::read_to_string(path).unwrap_or_default().lines() {
+            let mut fields = line.split(':');
+            let (Some(name), Some(id)) = (fields.next(), fields.nth(1)) else {
+                continue;
+            };
+            let Ok(id) = id.parse::<u32>() else {
+                continue;
+            };
+            ids.entry(name.to_string()).or_insert(id);
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+        (ids, names)
+    }
+
+    pub fn user_id(&self, name: &str) -> Option<u32> {
+        self.user_ids.get(name).copied()
+    }
+
+    pub fn user_name(&self, uid: u32) -> Option<&str> {
+        self.user_names.get(&uid).map(String::as_str)
+    }
+
+    pub fn group_id(&self, name: &str) -> Option<u32> {
+        self.group_ids.get(name).copied()
+    }
+
+    pub fn group_name(&self, gid: u32) -> Option<&str> {
+        self.group_names.get(&gid).map(String::as_str)
+    }
+}
+
+/// whether konserve runs as root. no libc bindings here, /proc/self belongs to the effective uid.
+/// without /proc (macos) it says no, restores then keep ownership with whoever runs them
+#[cfg(unix)]
+pub fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata("/proc/self").is_ok_and(|m| m.uid() == 0)
+}
+
 /// thread-safe progress counter, 0-100, 101 = done
 #[derive(Clone)]
 pub struct Progress {
     inner: Arc<AtomicU32>,
+    /// what the job is busy with and since when, so the gui can tell a stall from a slow job
+    current: Arc<Mutex<(Instant, String)>>,
+    /// set by the gui's cancel button, the job checks it between (and inside) entries
+    cancelled: Arc<AtomicBool>,
+    /// bytes through so far / expected, the percentage follows these once a total is known
+    bytes_done: Arc<AtomicU64>,
+    bytes_total: Arc<AtomicU64>,
+    /// when the first byte went through, scanning time would drag the speed down otherwise
+    bytes_since: Arc<Mutex<Option<Instant>>>,
 }
 
 impl Progress {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(AtomicU32::new(0)),
+            current: Arc::new(Mutex::new((Instant::now(), String::new()))),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            bytes_done: Arc::new(AtomicU64::new(0)),
+            bytes_total: Arc::new(AtomicU64::new(0)),
+            bytes_since: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// called by the job before each entry, resets the stall timer
+    pub fn working_on(&self, what: impl Into<String>) {
+        *self.current.lock().unwrap_or_else(|e| e.into_inner()) = (Instant::now(), what.into());
+    }
+    /// how long the current entry has been going, and what it is
+    pub fn stalled(&self) -> (Duration, String) {
+        let cur = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        (cur.0.elapsed(), cur.1.clone())
+    }
+
+    /// more bytes to go, restore calls this again for each parent archive it has to pull from
+    pub fn add_total_bytes(&self, n: u64) {
+        self.bytes_total.fetch_add(n, Ordering::Relaxed);
+    }
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_total.load(Ordering::Relaxed)
+    }
+    pub fn add_bytes(&self, n: u64) {
+        self.bytes_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(Instant::now);
+        let done = self.bytes_done.fetch_add(n, Ordering::Relaxed) + n;
+        let total = self.total_bytes();
+        if let Some(pct) = (done.min(total) * 100).checked_div(total) {
+            self.set(pct as u32);
+        }
+    }
+    /// bytes per second and time left, None for the first second while the speed means nothing
+    pub fn speed(&self) -> Option<(u64, Duration)> {
+        let since = (*self.bytes_since.lock().unwrap_or_else(|e| e.into_inner()))?;
+        let secs = since.elapsed().as_secs_f64();
+        if secs < 1.0 {
+            return None;
         }
+        let done = self.bytes_done.load(Ordering::Relaxed);
+        let rate = done as f64 / secs;
+        let left = self.total_bytes().saturating_sub(done) as f64;
+        let eta = if rate > 0.0 { left / rate } else { 0.0 };
+        Some((rate as u64, Duration::from_secs_f64(eta.min(1e7))))
     }
 
     pub fn set(&self, pct: u32) {
@@ -418,18 +1812,41 @@ impl Default for Progress {
     }
 }
 
-/// loads the icon (embedded at compile time) into whatever eframe wants, panics if the png is busted
-pub fn load_icon_image() -> Arc<IconData> {
-    let image_bytes = include_bytes!("../assets/icon.png");
-    let decoder = png::Decoder::new(std::io::Cursor::new(image_bytes));
-    let mut reader = decoder.read_info().expect("Icon PNG couldn't be read");
-    let mut buf = vec![
-        0u8;
-        reader
-            .output_buffer_size()
-            .expect("Icon PNG buffer size unknown")
-    ];
-    let info = reader.next_frame(&mut buf).expect("Icon PNG frame error");
+/// reader for the job's data: feeds the byte counter and errors out once the job is cancelled,
+/// so one huge file neither freezes the bar nor holds up a cancel until it's fully copied
+pub struct ProgressRead<'a, R: Read> {
+    pub inner: R,
+    pub progress: &'a Progress,
+}
+
+impl<R: Read> Read for ProgressRead<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(std::io::Error::other("cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.progress.add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+/// the window icon, None (and a line in the error log) if it can't be decoded. the window
+/// just opens without one then, no reason to die over an icon
+pub fn load_icon_image() -> Option<Arc<IconData>> {
+    let decoder = png::Decoder::new(std::io::Cursor::new(ICON_PNG));
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| elog!("ERROR: icon png couldn't be read: {e}"))
+        .ok()?;
+    let Some(size) = reader.output_buffer_size() else {
+        elog!("ERROR: icon png buffer size unknown");
+        return None;
+    };
+    let mut buf = vec![0u8; size];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| elog!("ERROR: icon png frame error: {e}"))
+        .ok()?;
     let bytes = &buf[..info.buffer_size()];
 
     let rgba = match info.color_type {
@@ -438,14 +1855,17 @@ pub fn load_icon_image() -> Arc<IconData> {
             .chunks_exact(3)
             .flat_map(|p| [p[0], p[1], p[2], 255])
             .collect(),
-        _ => panic!("Unsupported icon color type"),
+        other => {
+            elog!("ERROR: unsupported icon color type {other:?}");
+            return None;
+        }
     };
 
-    Arc::new(IconData {
+    Some(Arc::new(IconData {
         rgba,
         width: info.width,
         height: info.height,
-    })
+    }))
 }
 
 /// checks/unchecks a node and everything under it
@@ -466,28 +1886,118 @@ fn set_all_checked(node: &mut FolderTreeNode, checked: bool, verbose: bool) {
     }
 }
 
-/// draws the collapsible checkbox tree for picking what to restore
+/// what a tree search leaves visible, keyed by the same "/"-joined paths render_tree uses as ids
+#[derive(Default)]
+pub struct TreeFilter {
+    /// matches, everything under them and the folders leading to them
+    pub shown: HashSet<String>,
+    /// folders with a match somewhere below, drawn expanded
+    pub open: HashSet<String>,
+}
+
+/// works out which nodes match `query` (case-insensitive name substring). done once per
+/// query change rather than every frame, restore trees can have tens of thousands of entries
+pub fn filter_tree(root: &FolderTreeNode, query: &str) -> TreeFilter {
+    fn visit(
+        node: &FolderTreeNode,
+        path: &mut Vec<String>,
+        query: &str,
+        under_match: bool,
+        out: &mut TreeFilter,
+    ) -> bool {
+        let mut any = false;
+        for (name, child) in &node.children {
+            path.push(name.clone());
+            let label = child.label.as_deref().unwrap_or(name);
+            let hit = under_match || label.to_lowercase().contains(query);
+            let below = visit(child, path, query, hit, out);
+            let key = path.join("/");
+            if below && !under_match {
+                out.open.insert(key.clone());
+            }
+            if hit || below {
+                out.shown.insert(key);
+                any = true;
+            }
+            path.pop();
+        }
+        any
+    }
+
+    let mut out = TreeFilter::default();
+    let query = query.to_lowercase();
+    visit(root, &mut Vec::new(), &query, false, &mut out);
+    out
+}
+
+/// "3.1 MB" for a file, "12 files, 40.2 MB" for a folder. None for nodes without sizes (backup tree)
+fn size_text(node: &FolderTreeNode) -> Option<String> {
+    if node.files == 0 {
+        return None;
+    }
+    // unchanged files of an incremental backup only have their size in the parent archive
+    let at_least = if node.size_partial { "≥ " } else { "" };
+    if node.children.is_empty() {
+        return Some(if node.size_partial {
+            "in an earlier backup".to_string()
+        } else {
+            format_size(node.size)
+        });
+    }
+    Some(format!(
+        "{}, {at_least}{}",
+        format_count(node.files, "file"),
+        format_size(node.size)
+    ))
+}
+
+/// draws the collapsible checkbox tree for picking what to restore, only what `filter` shows
+/// when there is one
 pub fn render_tree(
     ui: &mut egui::Ui,
     path: &mut Vec<String>,
     node: &mut FolderTreeNode,
+    filter: Option<&TreeFilter>,
     verbose: bool,
 ) {
     for (name, child) in node.children.iter_mut() {
-        let mut label = name.clone();
+        path.push(name.clone());
+        let current_path = path.join("/");
+        if filter.is_some_and(|f| !f.shown.contains(&current_path)) {
+            path.pop();
+            continue;
+        }
+
+        let mut label = child.label.clone().unwrap_or_else(|| name.clone());
         if !child.is_file {
             label.push('/');
         }
+        // custom labels are shortened, so the full key goes in the tooltip, with the root's note
+        let hover = child.label.as_ref().map(|_| match &child.note {
+            Some(note) => format!("{name}\n📝 {note}"),
+            None => name.clone(),
+        });
+        if child.note.is_some() {
+            label.push_str("  📝");
+        }
 
-        path.push(name.clone());
-        let current_path = path.join("/");
+        let size = size_text(child);
 
         if child.children.is_empty() {
             ui.horizontal(|ui| {
                 ui.checkbox(&mut child.checked, "");
-                ui.label(label);
+                let resp = ui.label(label);
+                if let Some(hover) = hover {
+                    resp.on_hover_text(hover);
+                }
+                if let Some(size) = size {
+                    ui.weak(size);
+                }
             });
         } else {
+            if let Some(size) = size {
+                label.push_str(&format!("  ({size})"));
+            }
             ui.horizontal(|ui| {
                 if ui.checkbox(&mut child.checked, "").changed() {
                     if verbose {
@@ -499,86 +2009,849 @@ pub fn render_tree(
                     }
                     set_all_checked(child, child.checked, verbose);
                 }
-                CollapsingHeader::new(label)
-                    .default_open(false)
-                    .show(ui, |ui| {
-                        // recurse into the children
-                        render_tree(ui, path, child, verbose);
-                    });
+                let mut header = CollapsingHeader::new(label)
+                    .id_salt(&current_path)
+                    .default_open(false);
+                if filter.is_some_and(|f| f.open.contains(&current_path)) {
+                    header = header.open(Some(true));
+                }
+                let resp = header.show(ui, |ui| {
+                    // recurse into the children
+                    render_tree(ui, path, child, filter, verbose);
+                });
+                if let Some(hover) = hover {
+                    resp.header_response.on_hover_text(hover);
+                }
+            });
+
+            // keep parent checked if any child still is
+            child.checked = child.children.values().any(|c| c.checked);
+        }
+
+        path.pop();
+    }
+}
+
+/// drive letter up front, so case doesn't matter for it
+pub fn is_windows_path(s: &str) -> bool {
+    s.as_bytes().get(1) == Some(&b':')
+}
+
+/// loose form of a path for the fallback matcher: `/` only, no doubled or trailing
+/// separators, lowercased when the root it belongs to is a windows path
+pub fn loose(s: &str, fold_case: bool) -> String {
+    let mut out = s.replace('\\', "/");
+    while out.contains("//") {
+        out = out.replace("//", "/");
+    }
+    let out = out.trim_end_matches('/');
+    if fold_case {
+        out.to_lowercase()
+    } else {
+        out.to_string()
+    }
+}
+
+/// `a`, `a/b`, `a/b/c` for `a/b/c`: the path itself and every folder above it. selections
+/// get matched by looking these up in a set instead of prefix-testing every selected path
+pub fn path_prefixes(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/')
+        .map(move |(i, _)| &path[..i])
+        .chain(std::iter::once(path))
+}
+
+/// restore tree leaves with their original path, root key (minus the " [uuid]" duplicate tag)
+/// joined with the path below it. parents get re-derived from their children on the way out.
+/// `human` is one buffer grown and cut back while walking, not a new string per node
+fn visit_restore_leaves(
+    node: &mut FolderTreeNode,
+    human: &mut String,
+    sep: char,
+    f: &mut dyn FnMut(&str, &mut FolderTreeNode),
+) {
+    if node.children.is_empty() {
+        f(human, node);
+        return;
+    }
+    for (name, child) in node.children.iter_mut() {
+        let len = human.len();
+        human.push(sep);
+        human.push_str(name);
+        visit_restore_leaves(child, human, sep, f);
+        human.truncate(len);
+    }
+    node.checked = node.children.values().any(|c| c.checked);
+}
+
+fn restore_root_base(key: &str, node: &FolderTreeNode) -> String {
+    node.uuid
+        .as_ref()
+        .and_then(|u| key.strip_suffix(&format!(" [{u}]")))
+        .unwrap_or(key)
+        .to_string()
+}
+
+/// the checked files of a restore tree as original paths, so a saved selection works on other archives too
+pub fn collect_human_paths(root: &mut FolderTreeNode) -> Vec<String> {
+    let mut out = Vec::new();
+    for (key, child) in root.children.iter_mut() {
+        let mut base = restore_root_base(key, child);
+        let sep = if is_windows_path(&base) { '\\' } else { '/' };
+        visit_restore_leaves(child, &mut base, sep, &mut |human, leaf| {
+            if leaf.checked {
+                out.push(human.to_string());
+            }
+        });
+    }
+    out.sort();
+    out
+}
+
+/// checks exactly the leaves whose original path (or anything above it) is in `wanted`,
+/// same loose matching as restore. returns how many of `wanted` matched something
+pub fn apply_human_paths(root: &mut FolderTreeNode, wanted: &[String]) -> usize {
+    let mut matched = vec![false; wanted.len()];
+    for (key, child) in root.children.iter_mut() {
+        let mut base = restore_root_base(key, child);
+        let fold = is_windows_path(&base);
+        // loose path -> which of `wanted` it came from, several can fold to the same one
+        let mut wanted_loose: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, w) in wanted.iter().enumerate() {
+            wanted_loose.entry(loose(w, fold)).or_default().push(i);
+        }
+        visit_restore_leaves(child, &mut base, '/', &mut |human, leaf| {
+            let human = loose(human, fold);
+            leaf.checked = false;
+            for hits in path_prefixes(&human).filter_map(|p| wanted_loose.get(p)) {
+                leaf.checked = true;
+                for &i in hits {
+                    matched[i] = true;
+                }
+            }
+        });
+    }
+    matched.iter().filter(|m| **m).count()
+}
+
+/// how deep an incremental chain may go before we assume it loops
+pub const MAX_CHAIN: usize = 64;
+
+/// original path -> (size, mtime) of every file an archive stands for, the unchanged ones
+/// looked up through its parent chain. incremental backups compare against this.
+/// also hands back the archive's own id so a child can point at it
+pub fn archive_file_state(archive: &Path, verbose: bool) -> Result<FileState, String> {
+    archive_file_state_at(archive, None, verbose, 0)
+}
+
+/// archive id + original path -> (size, mtime)
+pub type FileState = (Option<String>, HashMap<String, (u64, u64)>);
+
+fn archive_file_state_at(
+    archive: &Path,
+    expected_id: Option<&str>,
+    verbose: bool,
+    depth: usize,
+) -> Result<FileState, String> {
+    if depth > MAX_CHAIN {
+        return Err("incremental chain too long, probably loops".into());
+    }
+    let mut manifest = None;
+    let mut files: Vec<(String, u64, u64)> = Vec::new();
+    let mut unchanged = Vec::new();
+    let mut tar = Archive::new(open_archive_reader(archive)?);
+    for entry in tar.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let name = entry
+            .path()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .into_owned();
+        if name == "fingerprint.txt" || name == UNCHANGED_ENTRY {
+            let mut txt = String::new();
+            entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
+            if name == UNCHANGED_ENTRY {
+                unchanged = parse_unchanged(&txt);
+            } else {
+                manifest = Some(parse_manifest(&txt, verbose));
+            }
+        } else if entry.header().entry_type().is_file() {
+            let mtime = entry.header().mtime().unwrap_or(0);
+            files.push((name, entry.size(), mtime));
+        }
+    }
+    // some other program's tar, nothing in it lines up with original paths
+    let Some(manifest) = manifest else {
+        return Err("not a Konserve archive (no fingerprint.txt)".into());
+    };
+
+    let mut state: HashMap<String, (u64, u64)> = files
+        .into_iter()
+        .filter_map(|(name, size, mtime)| {
+            entry_human_path(&name, &manifest.paths).map(|h| (h, (size, mtime)))
+        })
+        .collect();
+    if expected_id.is_some() && manifest.id.as_deref() != expected_id {
+        return Err(format!(
+            "{} was replaced by a different backup",
+            archive.display()
+        ));
+    }
+    if let Some(parent) = &manifest.parent
+        && !unchanged.is_empty()
+    {
+        let (_, parent_state) = archive_file_state_at(
+            &archive.with_file_name(&parent.name),
+            parent.id.as_deref(),
+            verbose,
+            depth + 1,
+        )?;
+        for key in unchanged {
+            if let Some(human) = entry_human_path(&key, &manifest.paths)
+                && let Some(meta) = parent_state.get(&human)
+            {
+                state.insert(human, *meta);
+            }
+        }
+    }
+    Ok((manifest.id, state))
+}
+
+/// one archive that has entries matching a search, paths are where they originally lived
+pub struct SearchHit {
+    pub archive: PathBuf,
+    pub paths: Vec<String>,
+    /// see backup_time
+    pub when: Option<std::time::SystemTime>,
+}
+
+/// case-insensitive substring match where `*` stands for anything, "mozilla*prefs.js" etc
+fn wildcard_contains(haystack: &str, pattern: &str) -> bool {
+    let mut rest = haystack;
+    for part in pattern.split('*').filter(|p| !p.is_empty()) {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// `*` anything but `/`, `**` anything at all, `?` one character (not `/`)
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => match rest.split_first() {
+            Some(('*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+            _ => (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != '/')
+                .any(|i| glob_match(rest, &text[i..])),
+        },
+        Some(('?', rest)) => {
+            text.first().is_some_and(|c| *c != '/') && glob_match(rest, &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// a template path with `*` or `?` in it, see expand_glob
+pub fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?'])
+}
+
+/// everything a template path like `~/.config/*/settings.json` or `C:\Users\me\Documents\*.kdbx`
+/// matches on disk right now, sorted. wildcards only work inside one path component, no `**`
+pub fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let fold = |s: &str| -> Vec<char> {
+        if cfg!(windows) {
+            s.to_lowercase().chars().collect()
+        } else {
+            s.chars().collect()
+        }
+    };
+    let mut found = vec![PathBuf::new()];
+    for comp in pattern.components() {
+        let name = comp.as_os_str().to_string_lossy();
+        if !matches!(comp, std::path::Component::Normal(_)) || !name.contains(['*', '?']) {
+            for p in &mut found {
+                p.push(comp);
+            }
+            continue;
+        }
+        let pat = fold(&name);
+        found = found
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter(|e| glob_match(&pat, &fold(&e.file_name().to_string_lossy())))
+            .map(|e| e.path())
+            .collect();
+    }
+    found.retain(|p| p.exists());
+    found.sort();
+    found
+}
+
+/// gitignore-ish exclude pattern against `rel`, a path under a selected root with `/` separators.
+/// no slash (`*.tmp`, `Thumbs.db`) matches a name at any depth, with a slash (`build/*.o`) the
+/// relative path from any folder down, a leading `/` only from the root. trailing `/` (`Cache/`)
+/// only matches folders and `dir/**` is the folder with everything in it
+pub fn exclude_pattern_matches(pattern: &str, rel: &str, is_dir: bool) -> bool {
+    let mut pattern = pattern.trim().replace('\\', "/");
+    if pattern.is_empty() || pattern.starts_with('#') {
+        return false;
+    }
+    let mut dir_only = false;
+    if let Some(dir) = pattern.strip_suffix("/**") {
+        pattern = dir.to_string();
+        dir_only = true;
+    } else if let Some(dir) = pattern.strip_suffix('/') {
+        pattern = dir.to_string();
+        dir_only = true;
+    }
+    if dir_only && !is_dir {
+        return false;
+    }
+    let anchored = pattern.starts_with('/');
+    let pattern = pattern.trim_start_matches('/');
+    let pattern = pattern.strip_prefix("**/").unwrap_or(pattern);
+
+    // windows paths don't care about case, so patterns shouldn't either
+    let fold = |s: &str| -> Vec<char> {
+        if cfg!(windows) {
+            s.to_lowercase().chars().collect()
+        } else {
+            s.chars().collect()
+        }
+    };
+    let pat = fold(pattern);
+    let rel = fold(rel);
+
+    if !pattern.contains('/') && !anchored {
+        let name_start = rel.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+        return glob_match(&pat, &rel[name_start..]);
+    }
+    if anchored {
+        return glob_match(&pat, &rel);
+    }
+    (0..rel.len())
+        .filter(|&start| start == 0 || rel[start - 1] == '/')
+        .any(|start| glob_match(&pat, &rel[start..]))
+}
+
+/// one pattern per line, blanks dropped. what the settings + template editor text boxes hold
+pub fn parse_patterns(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// when the backup in `archive` ran: the manifest's `created=`, or the file's mtime for archives
+/// from before it was recorded (a copy that didn't keep mtimes gets that one wrong)
+pub fn backup_time(
+    archive: &Path,
+    created: Option<DateTime<Utc>>,
+) -> Option<std::time::SystemTime> {
+    created
+        .map(std::time::SystemTime::from)
+        .or_else(|| fs::metadata(archive).and_then(|m| m.modified()).ok())
+}
+
+/// true if `path` looks like something konserve wrote: encrypted (can't peek without the
+/// passphrase), or a tar whose first entry is fingerprint.txt. only reads the first header
+pub fn is_konserve_archive(path: &Path) -> bool {
+    if crate::crypt::is_encrypted(path) {
+        return true;
+    }
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut archive = Archive::new(file);
+    archive
+        .entries()
+        .ok()
+        .and_then(|mut entries| entries.next())
+        .and_then(Result::ok)
+        .and_then(|entry| entry.path().ok().map(|p| p == Path::new("fingerprint.txt")))
+        .unwrap_or(false)
+}
+
+/// every .tar directly inside `dirs`, newest first by mtime
+pub fn archives_in(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut archives: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+    for dir in dirs {
+        let Ok(read) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in read.flatten() {
+            let p = entry.path();
+            if p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("tar")) {
+                let modified = entry.metadata().and_then(|m| m.modified());
+                archives.push((modified.unwrap_or(std::time::UNIX_EPOCH), p));
+            }
+        }
+    }
+    archives.sort_by_key(|a| std::cmp::Reverse(a.0));
+    archives.into_iter().map(|(_, p)| p).collect()
+}
+
+/// how many timestamped backups a destination keeps, checked after every backup into it.
+/// an archive stays if either rule keeps it, both 0 = keep everything
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Retention {
+    /// the newest this many
+    pub keep_last: u32,
+    /// everything younger than this many days
+    pub keep_days: u32,
+}
+
+impl Retention {
+    pub fn is_off(self) -> bool {
+        self.keep_last == 0 && self.keep_days == 0
+    }
+}
+
+/// files konserve writes next to an archive, they go when it does
+pub const ARCHIVE_SIDECARS: [&str; 2] = [".sha256", ".snapshot.json"];
+
+/// deletes the `backup_*.tar` archives in `dir` that `keep` doesn't keep, newest first by mtime.
+/// fixed-name archives are never touched, and neither is anything in `protect` or that a kept
+/// archive builds on (incremental/differential parent chains). Err without deleting anything
+/// when a kept archive's parent can't be read (encrypted, passphrase unknown)
+pub fn prune_archives(
+    dir: &Path,
+    keep: Retention,
+    protect: &[PathBuf],
+    verbose: bool,
+) -> Result<Vec<PathBuf>, String> {
+    if keep.is_off() {
+        return Ok(Vec::new());
+    }
+    let now = std::time::SystemTime::now();
+    let max_age = Duration::from_secs(keep.keep_days as u64 * 86_400);
+    let mut doomed: Vec<PathBuf> = Vec::new();
+    let mut kept: Vec<PathBuf> = Vec::new();
+    let mut rank = 0;
+    for archive in archives_in(&[dir.to_path_buf()]) {
+        let name = archive.file_name().unwrap_or_default().to_string_lossy();
+        if !name.starts_with("backup_") {
+            kept.push(archive);
+            continue;
+        }
+        let age = fs::metadata(&archive)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .unwrap_or_default();
+        // protected ones still count towards keep_last, the backup that just finished is one of them
+        let by_count = keep.keep_last > 0 && rank < keep.keep_last;
+        let by_age = keep.keep_days > 0 && age < max_age;
+        rank += 1;
+        if by_count || by_age || protect.contains(&archive) {
+            kept.push(archive);
+        } else {
+            doomed.push(archive);
+        }
+    }
+
+    // whatever stays has to keep its whole chain, walk the parents of everything kept
+    let mut i = 0;
+    while i < kept.len() && !doomed.is_empty() {
+        let archive = kept[i].clone();
+        i += 1;
+        let parent = match read_manifest(&archive, verbose) {
+            Ok(manifest) => manifest.parent,
+            Err(e) if crate::crypt::is_encrypted(&archive) => {
+                return Err(format!(
+                    "can't tell what {} builds on ({e})",
+                    archive.file_name().unwrap_or_default().to_string_lossy()
+                ));
+            }
+            // a plain tar that isn't ours (or is damaged) has no parent we could break
+            Err(_) => None,
+        };
+        if let Some(parent) = parent
+            && let Some(pos) = doomed
+                .iter()
+                .position(|d| d.file_name() == Some(parent.name.as_ref()))
+        {
+            kept.push(doomed.remove(pos));
+        }
+    }
+
+    let mut deleted = Vec::new();
+    for archive in doomed {
+        match fs::remove_file(&archive) {
+            Ok(()) => {
+                for ext in ARCHIVE_SIDECARS {
+                    let mut name = archive.file_name().unwrap_or_default().to_os_string();
+                    name.push(ext);
+                    let _ = fs::remove_file(archive.with_file_name(name));
+                }
+                deleted.push(archive);
+            }
+            Err(e) => elog!(
+                "ERROR: couldn't remove old backup {}: {e}",
+                archive.display()
+            ),
+        }
+    }
+    Ok(deleted)
+}
+
+/// original path of an archive entry (`uuid/rel` or `uuid.ext`), None for settings and unknown uuids
+pub fn entry_human_path(name: &str, path_map: &HashMap<String, PathBuf>) -> Option<String> {
+    match name.split_once('/') {
+        Some((KONSERVE_CONFIG_ENTRY, _)) => None,
+        Some((uuid, rel)) => {
+            let base = path_map.get(uuid)?.display().to_string();
+            let rel = rel.trim_end_matches('/');
+            Some(if rel.is_empty() {
+                base
+            } else if is_windows_path(&base) {
+                format!("{base}\\{}", rel.replace('/', "\\"))
+            } else {
+                format!("{base}/{rel}")
+            })
+        }
+        None => {
+            let uuid = name.split('.').next().unwrap_or_default();
+            path_map.get(uuid).map(|file| file.display().to_string())
+        }
+    }
+}
+
+/// looks through every .tar directly inside `dirs` for entries whose original path matches `pattern`,
+/// archives that can't be read are skipped. newest backup first
+pub fn search_archives(dirs: &[PathBuf], pattern: &str, verbose: bool) -> Vec<SearchHit> {
+    let pattern = pattern.trim().to_lowercase();
+    let mut hits = Vec::new();
+    for archive in archives_in(dirs) {
+        let ArchiveListing {
+            entries,
+            path_map,
+            created,
+            ..
+        } = match list_archive(&archive, verbose) {
+            Ok(listing) => listing,
+            Err(e) => {
+                dlog!("[WARN] search: skipping {}: {e}", archive.display());
+                continue;
+            }
+        };
+        let mut paths: Vec<String> = entries
+            .iter()
+            .filter_map(|name| entry_human_path(name, &path_map))
+            .filter(|human| wildcard_contains(&human.to_lowercase(), &pattern))
+            .collect();
+        if !paths.is_empty() {
+            paths.sort();
+            paths.dedup();
+            let when = backup_time(&archive, created);
+            hits.push(SearchHit {
+                archive,
+                paths,
+                when,
             });
+        }
+    }
+    sort_hits(&mut hits);
+    hits
+}
+
+/// newest backup first, by when it ran rather than when the file was last touched
+fn sort_hits(hits: &mut [SearchHit]) {
+    hits.sort_by_key(|h| std::cmp::Reverse(h.when));
+}
+
+/// archives in `dirs` holding a byte-identical copy of `file`, whatever it was called back then.
+/// only entries of the same size get hashed, so this is mostly a header scan
+pub fn find_identical(
+    dirs: &[PathBuf],
+    file: &Path,
+    verbose: bool,
+) -> Result<Vec<SearchHit>, String> {
+    let meta = fs::metadata(file).map_err(|e| e.to_string())?;
+    // a pipe or device would block the read below (or hash a whole disk)
+    if !meta.is_file() {
+        return Err(format!("{} isn't a regular file", file.display()));
+    }
+    let size = meta.len();
+    let mut local = File::open(file).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut local, &mut hasher).map_err(|e| e.to_string())?;
+    let digest: [u8; 32] = hasher.finalize().into();
+
+    let mut hits = Vec::new();
+    for archive in archives_in(dirs) {
+        let (manifest, matches) = match entries_with_digest(&archive, size, &digest, verbose) {
+            Ok(found) => found,
+            Err(e) => {
+                dlog!(
+                    "[WARN] identical search: skipping {}: {e}",
+                    archive.display()
+                );
+                continue;
+            }
+        };
+        let mut paths: Vec<String> = matches
+            .iter()
+            .filter_map(|name| entry_human_path(name, &manifest.paths))
+            .collect();
+        if !paths.is_empty() {
+            paths.sort();
+            let when = backup_time(&archive, manifest.created);
+            hits.push(SearchHit {
+                archive,
+                paths,
+                when,
+            });
+        }
+    }
+    sort_hits(&mut hits);
+    Ok(hits)
+}
+
+/// how many archives a path has to show up in before it gets suggested for a template
+pub const SUGGEST_MIN_BACKUPS: usize = 3;
+
+/// paths that went into at least SUGGEST_MIN_BACKUPS archives in `dirs` but aren't in `known`,
+/// most backed up first. only manifests are read and paths that don't exist here are dropped
+pub fn suggest_template_paths(
+    dirs: &[PathBuf],
+    known: &[PathBuf],
+    verbose: bool,
+) -> Vec<(PathBuf, usize)> {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for archive in archives_in(dirs) {
+        let manifest = match read_manifest(&archive, verbose) {
+            Ok(m) => m,
+            Err(e) => {
+                dlog!("[WARN] suggestions: skipping {}: {e}", archive.display());
+                continue;
+            }
+        };
+        // the same root can be in one archive twice (picked as both folder and file), count it once
+        let mut roots: Vec<PathBuf> = manifest
+            .paths
+            .values()
+            .filter_map(|p| fix_skip(p, verbose))
+            .collect();
+        roots.sort();
+        roots.dedup();
+        for root in roots {
+            *counts.entry(root).or_default() += 1;
+        }
+    }
+    let mut out: Vec<(PathBuf, usize)> = counts
+        .into_iter()
+        .filter(|(p, n)| *n >= SUGGEST_MIN_BACKUPS && !known.contains(p))
+        .collect();
+    out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    out
+}
+
+/// one pass over an archive: its manifest, plus the names of file entries matching size + sha-256
+fn entries_with_digest(
+    archive: &Path,
+    size: u64,
+    digest: &[u8; 32],
+    verbose: bool,
+) -> Result<(Manifest, Vec<String>), String> {
+    let mut manifest = Manifest::default();
+    let mut matches = Vec::new();
+    let mut tar = Archive::new(open_archive_reader(archive)?);
+    for entry in tar.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let name = entry
+            .path()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .into_owned();
+        if name == "fingerprint.txt" {
+            let mut txt = String::new();
+            entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
+            manifest = parse_manifest(&txt, verbose);
+            continue;
+        }
+        if !entry.header().entry_type().is_file() || entry.size() != size {
+            continue;
+        }
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut entry, &mut hasher).map_err(|e| e.to_string())?;
+        if hasher.finalize().as_slice() == digest {
+            matches.push(name);
+        }
+    }
+    Ok((manifest, matches))
+}
+
+/// splits a stored path on both separators, archives from windows get restored on linux too
+fn path_parts(path: &str) -> Vec<&str> {
+    path.split(['\\', '/']).filter(|p| !p.is_empty()).collect()
+}
+
+/// display labels for restore roots, keyed by full path: just the item name, plus as much
+/// of the parent path as it takes to tell same-named roots apart (zen (Roaming) vs zen (Local))
+fn root_labels<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> HashMap<String, String> {
+    let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+    for p in paths {
+        let full = p.display().to_string();
+        if let Some(name) = path_parts(&full).last() {
+            by_name.entry(name.to_string()).or_default().push(full);
+        }
+    }
 
-            // keep parent checked if any child still is
-            child.checked = child.children.values().any(|c| c.checked);
+    let mut labels = HashMap::new();
+    for (name, mut fulls) in by_name {
+        fulls.sort();
+        fulls.dedup();
+        if fulls.len() == 1 {
+            labels.insert(fulls.remove(0), name);
+            continue;
         }
 
-        path.pop();
+        // parent components, nearest first
+        let parents: Vec<Vec<&str>> = fulls
+            .iter()
+            .map(|f| {
+                let mut parts = path_parts(f);
+                parts.pop();
+                parts.reverse();
+                parts
+            })
+            .collect();
+        let max_depth = parents.iter().map(Vec::len).max().unwrap_or(0).max(1);
+
+        let mut suffixes: Vec<String> = Vec::new();
+        for depth in 1..=max_depth {
+            suffixes = parents
+                .iter()
+                .map(|p| {
+                    let mut shown: Vec<&str> = p.iter().take(depth).copied().collect();
+                    shown.reverse();
+                    shown.join(std::path::MAIN_SEPARATOR_STR)
+                })
+                .collect();
+            let unique: std::collections::HashSet<&String> = suffixes.iter().collect();
+            if unique.len() == suffixes.len() {
+                break;
+            }
+        }
+
+        for (full, suffix) in fulls.into_iter().zip(suffixes) {
+            labels.insert(full, format!("{name}  ({suffix})"));
+        }
     }
+    labels
 }
 
-/// builds the human-readable restore tree from tar entries + the uuid -> path map
-pub fn build_human_tree(
-    entries: Vec<String>,
-    path_map: HashMap<String, PathBuf>,
-    verbose: bool,
-) -> FolderTreeNode {
+/// adds up sizes and file counts from the leaves into every folder above them
+fn total_sizes(node: &mut FolderTreeNode) {
+    if node.children.is_empty() {
+        return;
+    }
+    node.size = 0;
+    node.files = 0;
+    for child in node.children.values_mut() {
+        total_sizes(child);
+        node.size += child.size;
+        node.files += child.files;
+        node.size_partial |= child.size_partial;
+    }
+}
+
+/// `node`'s child called `name`, only allocates the key when it has to be created. most
+/// parts of a path name a folder some earlier entry already made
+fn child_node<'a>(node: &'a mut FolderTreeNode, name: &str) -> &'a mut FolderTreeNode {
+    if !node.children.contains_key(name) {
+        node.children
+            .insert(name.to_string(), FolderTreeNode::default());
+    }
+    node.children.get_mut(name).unwrap()
+}
+
+/// builds the human-readable restore tree from an archive listing
+pub fn build_human_tree(listing: ArchiveListing, verbose: bool) -> FolderTreeNode {
+    let ArchiveListing {
+        entries,
+        path_map,
+        notes,
+        sizes,
+        ..
+    } = listing;
+    // leaves get their own size, folders add theirs up at the end
+    let set_size = |node: &mut FolderTreeNode, entry: &str| match sizes.get(entry) {
+        Some(size) => {
+            node.files = 1;
+            node.size = size.unwrap_or(0);
+            node.size_partial = size.is_none();
+        }
+        None => node.files = 0,
+    };
     if verbose {
         dlog!("[DEBUG] build_human_tree: Start");
     }
     let mut root = FolderTreeNode::default();
 
     // group entries by uuid prefix up front so lookups are O(1) instead of scanning
-    // the whole entry list every time
-    let mut entries_by_uuid: HashMap<String, Vec<String>> = HashMap::new();
+    // the whole entry list every time. borrowed, six-figure archives are common
+    let mut entries_by_uuid: HashMap<&str, Vec<&str>> = HashMap::new();
+    // single-file roots are `uuid.ext`
+    let mut file_roots: HashMap<&str, &str> = HashMap::new();
     for e in &entries {
-        if let Some(slash) = e.find('/') {
-            entries_by_uuid
-                .entry(e[..slash].to_string())
-                .or_default()
-                .push(e.clone());
+        if let Some((uuid, _)) = e.split_once('/') {
+            entries_by_uuid.entry(uuid).or_default().push(e);
+        } else if let Some(uuid) = e.split('.').next() {
+            file_roots.insert(uuid, e);
         }
     }
 
+    let labels = root_labels(path_map.values());
+
     for (uuid, original_path) in path_map {
         if verbose {
             dlog!("[DEBUG] Processing UUID: {uuid}, Path: {original_path:?}");
         }
 
-        let parent_label = original_path
-            .parent()
-            .unwrap_or(&original_path)
-            .display()
-            .to_string();
-        let item_name = match original_path.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => {
-                if verbose {
-                    dlog!("[WARN] skipping malformed fingerprint entry: {original_path:?}");
-                }
-                continue;
+        // keyed by the full original path so same-named roots in different folders
+        // (zen under Roaming and Local) never end up merged into one node
+        let root_key = original_path.display().to_string();
+        let Some(label) = labels.get(&root_key) else {
+            if verbose {
+                dlog!("[WARN] skipping malformed fingerprint entry: {original_path:?}");
             }
+            continue;
         };
 
         if verbose {
-            dlog!("[DEBUG] parent_label = \"{parent_label}\", item_name = \"{item_name}\"");
+            dlog!("[DEBUG] root_key = \"{root_key}\", label = \"{label}\"");
         }
 
-        let parent_node = root
-            .children
-            .entry(parent_label.clone())
-            .or_insert_with(FolderTreeNode::default);
+        // same path picked twice in one backup, keep both roots apart
+        let (root_key, label) = if root.children.contains_key(&root_key) {
+            let short = uuid.get(..8).unwrap_or(&uuid);
+            (format!("{root_key} [{uuid}]"), format!("{label} [{short}]"))
+        } else {
+            (root_key, label.clone())
+        };
 
-        parent_node
+        let item_node = root
             .children
-            .entry(item_name.clone())
+            .entry(root_key)
             .or_insert_with(FolderTreeNode::default);
+        item_node.label = Some(label);
+        item_node.uuid = Some(uuid.clone());
+        item_node.note = notes.get(&uuid).cloned();
 
         let dir_prefix = format!("{uuid}/");
 
-        if let Some(uuid_entries) = entries_by_uuid.get(&uuid) {
+        if let Some(uuid_entries) = entries_by_uuid.get(uuid.as_str()) {
             if verbose {
                 dlog!("[DEBUG] Detected directory backup for UUID: {uuid}");
             }
-            parent_node.children.get_mut(&item_name).unwrap().is_file = false;
+            item_node.is_file = false;
 
             for tar_path in uuid_entries {
                 if verbose {
@@ -597,25 +2870,44 @@ pub fn build_human_tree(
                     dlog!("[DEBUG]   Rest path: \"{rest}\"");
                 }
 
-                let mut cursor = parent_node.children.get_mut(&item_name).unwrap();
+                let mut cursor = &mut *item_node;
                 for part in rest.split('/') {
                     if verbose {
                         dlog!("[DEBUG]     Descending into part: \"{part}\"");
                     }
-                    cursor = cursor
-                        .children
-                        .entry(part.to_string())
-                        .or_insert_with(FolderTreeNode::default);
+                    cursor = child_node(cursor, part);
                 }
                 cursor.is_file = true;
+                set_size(cursor, tar_path);
             }
         } else {
             if verbose {
                 dlog!("[DEBUG] Detected file (not dir) for UUID: {uuid}");
             }
-            parent_node.children.get_mut(&item_name).unwrap().is_file = true;
+            item_node.is_file = true;
+            if let Some(entry) = file_roots.get(uuid.as_str()) {
+                set_size(item_node, entry);
+            }
+        }
+    }
+
+    // konserve's own settings ride along under a reserved folder that isn't in the manifest
+    if let Some(cfg_entries) = entries_by_uuid.get(KONSERVE_CONFIG_ENTRY) {
+        let node = root
+            .children
+            .entry("Konserve settings".to_string())
+            .or_insert_with(FolderTreeNode::default);
+        node.uuid = Some(KONSERVE_CONFIG_ENTRY.to_string());
+        for e in cfg_entries {
+            let name = e[KONSERVE_CONFIG_ENTRY.len() + 1..].trim_end_matches('/');
+            if !name.is_empty() {
+                let leaf = child_node(node, name);
+                leaf.is_file = true;
+                set_size(leaf, e);
+            }
         }
     }
+    total_sizes(&mut root);
 
     if verbose {
         dlog!("[DEBUG] build_human_tree: Finished building tree");
@@ -623,7 +2915,82 @@ pub fn build_human_tree(
     root
 }
 
+/// walks the selected roots into a checkbox tree for picking what goes into a backup
+/// top level is keyed by the full root path, below that by path component.
+/// anything under `excluded` starts out unchecked so reopening keeps the old picks
+pub fn build_backup_tree(roots: &[PathBuf], excluded: &[PathBuf], verbose: bool) -> FolderTreeNode {
+    if verbose {
+        dlog!("[DEBUG] build_backup_tree: Start, {} roots", roots.len());
+    }
+    let is_excluded = |p: &Path| excluded.iter().any(|e| p.starts_with(e));
+    let mut root = FolderTreeNode::default();
+
+    for selected in roots {
+        let root_node = root
+            .children
+            .entry(selected.display().to_string())
+            .or_insert_with(FolderTreeNode::default);
+        root_node.checked = !is_excluded(selected);
+
+        if selected.is_file() {
+            root_node.is_file = true;
+            continue;
+        }
+
+        for entry in WalkDir::new(selected)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            let Ok(rel) = entry.path().strip_prefix(selected) else {
+                continue;
+            };
+            let mut cursor = &mut *root_node;
+            for part in rel.components() {
+                cursor = cursor
+                    .children
+                    .entry(part.as_os_str().to_string_lossy().into_owned())
+                    .or_insert_with(FolderTreeNode::default);
+            }
+            cursor.is_file = entry.file_type().is_file();
+            cursor.checked = !is_excluded(entry.path());
+        }
+    }
+
+    if verbose {
+        dlog!("[DEBUG] build_backup_tree: Done");
+    }
+    root
+}
+
+/// turns the unchecked nodes of a backup tree back into real paths, only the topmost
+/// unchecked node of a branch is returned since everything below it is excluded anyway
+pub fn collect_unchecked(root: &FolderTreeNode) -> Vec<PathBuf> {
+    fn walk(node: &FolderTreeNode, path: PathBuf, out: &mut Vec<PathBuf>) {
+        for (name, child) in &node.children {
+            let child_path = path.join(name);
+            if child.checked {
+                walk(child, child_path, out);
+            } else {
+                out.push(child_path);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for (root_label, root_node) in &root.children {
+        let root_path = PathBuf::from(root_label);
+        if root_node.checked {
+            walk(root_node, root_path, &mut out);
+        } else {
+            out.push(root_path);
+        }
+    }
+    out
+}
+
 /// recursively flattens all checked file paths into one list
+/// roots carry their archive uuid, so paths come out as `uuid/rel/path` rather than display names
 pub fn collect_recursive(
     node: &FolderTreeNode,
     path: &mut Vec<String>,
@@ -631,7 +2998,7 @@ pub fn collect_recursive(
     verbose: bool,
 ) {
     for (name, child) in &node.children {
-        path.push(name.clone());
+        path.push(child.uuid.clone().unwrap_or_else(|| name.clone()));
         if child.is_file && child.checked {
             let full_path = path.join("/");
             if verbose {
@@ -662,11 +3029,86 @@ pub fn collect_paths(root: &FolderTreeNode, verbose: bool) -> Vec<String> {
     result
 }
 
+/// pretty json of an archive's roots (uuid, original path, file count, bytes) for pasting into tickets or scripts.
+/// zero-byte files get listed per root, that's usually the "file is there but the content isn't" case.
+/// bytes are what's stored, the same as the originals since tar doesn't compress. files left to
+/// the parent chain count as files but not bytes
+pub fn manifest_summary_json(zip_path: &PathBuf, verbose: bool) -> Result<String, String> {
+    let ArchiveListing {
+        path_map,
+        notes,
+        created,
+        sizes,
+        ..
+    } = list_archive(zip_path, verbose)?;
+    let size = fs::metadata(zip_path).map(|m| m.len()).unwrap_or(0);
+
+    // folder roots are uuid/rel and standalone files uuid.ext
+    let mut files: HashMap<&str, usize> = HashMap::new();
+    let mut bytes: HashMap<&str, u64> = HashMap::new();
+    let mut empty: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, entry_size) in &sizes {
+        let root = match name.split_once('/') {
+            Some((root, _)) => root,
+            None => name.split('.').next().unwrap_or_default(),
+        };
+        *files.entry(root).or_default() += 1;
+        *bytes.entry(root).or_default() += entry_size.unwrap_or(0);
+        if *entry_size == Some(0) {
+            let rel = name.split_once('/').map_or(name.as_str(), |(_, rel)| rel);
+            empty.entry(root).or_default().push(rel);
+        }
+    }
+    for list in empty.values_mut() {
+        list.sort();
+    }
+
+    let mut roots: Vec<(&String, &PathBuf)> = path_map.iter().collect();
+    roots.sort_by(|a, b| a.1.cmp(b.1));
+    let roots: Vec<serde_json::Value> = roots
+        .into_iter()
+        .map(|(uuid, path)| {
+            serde_json::json!({
+                "uuid": uuid,
+                "path": path.display().to_string(),
+                "files": files.get(uuid.as_str()).copied().unwrap_or(0),
+                "bytes": bytes.get(uuid.as_str()).copied().unwrap_or(0),
+                "empty_files": empty.get(uuid.as_str()).cloned().unwrap_or_default(),
+                "note": notes.get(uuid),
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&serde_json::json!({
+        "archive": zip_path.display().to_string(),
+        "created": created.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        "size_bytes": size,
+        "roots": roots,
+    }))
+    .map_err(|e| e.to_string())
+}
+
 /// reads fingerprint.txt out of the archive, returns entry list + uuid map
 pub fn parse_fingerprint(
-    zip_path: &PathBuf,
+    zip_path: &Path,
     verbose: bool,
 ) -> Result<(Vec<String>, HashMap<String, PathBuf>), String> {
+    list_archive(zip_path, verbose).map(|l| (l.entries, l.path_map))
+}
+
+/// everything parse_fingerprint finds, plus entry sizes and root notes for the restore tree
+pub struct ArchiveListing {
+    pub entries: Vec<String>,
+    pub path_map: HashMap<String, PathBuf>,
+    /// uuid -> note, see Manifest::notes
+    pub notes: HashMap<String, String>,
+    /// see Manifest::created
+    pub created: Option<DateTime<Utc>>,
+    /// every non-directory entry: Some(bytes), or None when it lives in the parent chain
+    pub sizes: HashMap<String, Option<u64>>,
+}
+
+pub fn list_archive(zip_path: &Path, verbose: bool) -> Result<ArchiveListing, String> {
     if verbose {
         dlog!(
             "[DEBUG] parse_fingerprint: Opening archive at {}",
@@ -674,9 +3116,10 @@ pub fn parse_fingerprint(
         );
     }
 
-    let file = File::open(zip_path).map_err(|e| e.to_string())?;
-    let mut archive = Archive::new(file);
+    let mut archive = Archive::new(open_archive_reader(zip_path)?);
     let mut path_map = HashMap::new();
+    let mut notes = HashMap::new();
+    let mut created = None;
 
     if verbose {
         dlog!("[DEBUG] Scanning for fingerprint.txt…");
@@ -694,13 +3137,11 @@ pub fn parse_fingerprint(
             let mut txt = String::new();
             entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
 
-            for line in txt.lines().filter(|l| l.contains(": ")) {
-                let (uuid, p) = line.split_once(": ").unwrap();
-                if verbose {
-                    dlog!("[DEBUG]   Parsed fingerprint: {} → {}", uuid, p.trim());
-                }
-                path_map.insert(uuid.to_string(), PathBuf::from(p.trim()));
-            }
+            let manifest = parse_manifest(&txt, verbose);
+            manifest.check_version()?;
+            path_map = manifest.paths;
+            notes = manifest.notes;
+            created = manifest.created;
             break;
         }
     }
@@ -709,16 +3150,29 @@ pub fn parse_fingerprint(
         dlog!("[DEBUG] Re-opening archive to collect entries");
     }
 
-    let file = File::open(zip_path).map_err(|e| e.to_string())?;
-    let mut archive = Archive::new(file);
+    let mut archive = Archive::new(open_archive_reader(zip_path)?);
     let mut entries = Vec::new();
+    let mut sizes = HashMap::new();
 
     for entry in archive.entries().map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
+        let mut entry = entry.map_err(|e| e.to_string())?;
         let entry_path = entry.path().map_err(|e| e.to_string())?;
         let entry_name = entry_path.to_string_lossy().into_owned();
 
-        if entry_name != "fingerprint.txt" {
+        // incremental: files that live in the parent chain still belong in the tree
+        if entry_name == UNCHANGED_ENTRY {
+            let mut txt = String::new();
+            entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
+            for key in parse_unchanged(&txt) {
+                sizes.insert(key.clone(), None);
+                entries.push(key);
+            }
+            continue;
+        }
+        if entry_name != "fingerprint.txt" && entry_name != CHECKSUMS_ENTRY {
+            if !entry.header().entry_type().is_dir() {
+                sizes.insert(entry_name.clone(), Some(entry.size()));
+            }
             entries.push(entry_name.clone());
             if verbose {
                 dlog!("[DEBUG]   Found entry: {entry_name}");
@@ -734,7 +3188,13 @@ pub fn parse_fingerprint(
         );
     }
 
-    Ok((entries, path_map))
+    Ok(ArchiveListing {
+        entries,
+        path_map,
+        notes,
+        created,
+        sizes,
+    })
 }
 
 /// fingerprint baked in at compile time from the FINGERPRINT env var
@@ -746,6 +3206,442 @@ pub fn get_fingered() -> &'static str {
     }
 }
 
+/// section header for the uuid -> path list in fingerprint.txt
+const MANIFEST_SECTION: &str = "[Backup Info]";
+/// same list, but xored + hex'd so the original paths aren't readable from the tar
+const MANIFEST_SECTION_OBFUSCATED: &str = "[Backup Info:obfuscated]";
+/// `id=<uuid>`, every archive gets its own so chains notice a parent that got overwritten,
+/// `min_version=` of konserve that can restore it and `created=`, utc rfc3339 so it sorts the
+/// same whatever time zone or dst the archive gets read in
+const ARCHIVE_SECTION: &str = "[Archive]";
+/// oldest konserve that understands what this build writes. bump it along with any change to the
+/// archive layout older builds would get wrong, they check it before touching anything
+pub const ARCHIVE_MIN_VERSION: &str = "0.1.11";
+/// `<uuid>=<note>` for roots that had a note attached, newlines escaped as `\n`
+const NOTES_SECTION: &str = "[Notes]";
+/// same, obfuscated along with the path list
+const NOTES_SECTION_OBFUSCATED: &str = "[Notes:obfuscated]";
+/// `parent=<file name>`, `parent_id=<uuid>` and `kind=` of the archive an incremental or
+/// differential backup builds on
+const INCREMENTAL_SECTION: &str = "[Incremental]";
+/// trailing entry of an incremental archive, the `uuid/rel` keys that were left out because the
+/// parent chain already has them. written last since it's only known once packing is done
+pub const UNCHANGED_ENTRY: &str = "konserve-unchanged.txt";
+/// trailing entry with a sha-256 per packed file (`<hex>  <uuid/rel>` lines), what verify checks
+/// the archive against. last for the same reason as UNCHANGED_ENTRY
+pub const CHECKSUMS_ENTRY: &str = "konserve-checksums.txt";
+
+/// what fingerprint.txt told us about an archive
+#[derive(Default)]
+pub struct Manifest {
+    /// fingerprint matches this build
+    pub valid: bool,
+    /// uuid -> original path
+    pub paths: HashMap<String, PathBuf>,
+    /// None for archives from before ids were written
+    pub id: Option<String>,
+    /// konserve version the archive needs, None for archives from before it was recorded
+    pub min_version: Option<String>,
+    /// when the backup ran, None for archives from before it was recorded
+    pub created: Option<DateTime<Utc>>,
+    /// the archive this one builds on, if it's incremental or differential
+    pub parent: Option<ParentLink>,
+    /// uuid -> note the user attached to that root ("needs the app closed to restore")
+    pub notes: HashMap<String, String>,
+}
+
+/// the archive an incremental or differential backup leaves its unchanged files to
+#[derive(Clone, Default)]
+pub struct ParentLink {
+    /// file name, always in the same folder as the child
+    pub name: String,
+    /// the parent's archive id when it was used, a different one means it got replaced since
+    pub id: Option<String>,
+    /// parent was a hand-picked baseline rather than just the newest archive
+    pub differential: bool,
+}
+
+/// xors data in place with a sha-256 counter keystream keyed on the build fingerprint + nonce
+fn manifest_keystream_xor(data: &mut [u8], nonce: &str) {
+    for (block_idx, chunk) in data.chunks_mut(32).enumerate() {
+        let mut hasher = Sha256::new();
+        hasher.update(get_fingered().as_bytes());
+        hasher.update(nonce.as_bytes());
+        hasher.update((block_idx as u64).to_le_bytes());
+        let block = hasher.finalize();
+        for (b, k) in chunk.iter_mut().zip(block.iter()) {
+            *b ^= k;
+        }
+    }
+}
+
+fn fingerprint_hash() -> String {
+    to_hex(&Sha256::digest(get_fingered().as_bytes()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// `nonce=` + `data=` lines holding `text` run through the manifest keystream
+fn obfuscate_block(text: &str) -> String {
+    let nonce = uuid::Uuid::new_v4().to_string();
+    let mut data = text.as_bytes().to_vec();
+    manifest_keystream_xor(&mut data, &nonce);
+    format!("nonce={nonce}\ndata={}\n", to_hex(&data))
+}
+
+/// reverse of obfuscate_block
+fn deobfuscate_block(body: &str) -> Result<String, &'static str> {
+    let field = |key: &str| {
+        body.lines()
+            .find_map(|l| l.strip_prefix(key))
+            .map(str::trim)
+    };
+    let (Some(nonce), Some(data)) = (field("nonce="), field("data=")) else {
+        return Err("missing its nonce or data");
+    };
+    let Some(mut bytes) = from_hex(data) else {
+        return Err("data is not valid hex");
+    };
+    manifest_keystream_xor(&mut bytes, nonce);
+    String::from_utf8(bytes).map_err(|_| "decoded to invalid utf-8")
+}
+
+/// one `uuid=note` line for the notes section
+pub fn note_line(uuid: &str, note: &str) -> String {
+    let note = note.trim().replace('\\', "\\\\").replace('\n', "\\n");
+    format!("{uuid}={note}\n")
+}
+
+/// pulls `uuid=note` pairs out of a notes section, reverse of note_line
+fn parse_note_lines(lines: &str, notes: &mut HashMap<String, String>) {
+    for (uuid, note) in lines.lines().filter_map(|l| l.split_once('=')) {
+        let mut out = String::new();
+        let mut chars = note.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        }
+        notes.insert(uuid.to_string(), out);
+    }
+}
+
+/// builds fingerprint.txt from the `uuid: path` lines and the `uuid=note` lines
+/// obfuscated mode hides both the paths and the raw fingerprint, only builds with the
+/// same fingerprint can read it back. not encryption, just keeps paths off shared storage
+pub fn render_manifest(
+    path_lines: &str,
+    note_lines: &str,
+    id: &str,
+    parent: Option<&ParentLink>,
+    obfuscate: bool,
+) -> String {
+    let mut txt = if obfuscate {
+        format!(
+            "fp-sha256:{}\n{MANIFEST_SECTION_OBFUSCATED}\n{}",
+            fingerprint_hash(),
+            obfuscate_block(path_lines)
+        )
+    } else {
+        format!("{}\n{MANIFEST_SECTION}\n{path_lines}", get_fingered())
+    };
+    if !note_lines.is_empty() {
+        if obfuscate {
+            txt.push_str(&format!(
+                "{NOTES_SECTION_OBFUSCATED}\n{}",
+                obfuscate_block(note_lines)
+            ));
+        } else {
+            txt.push_str(&format!("{NOTES_SECTION}\n{note_lines}"));
+        }
+    }
+    txt.push_str(&format!(
+        "{ARCHIVE_SECTION}\nid={id}\nmin_version={ARCHIVE_MIN_VERSION}\ncreated={}\n",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    ));
+    if let Some(parent) = parent {
+        let kind = if parent.differential {
+            "differential"
+        } else {
+            "incremental"
+        };
+        txt.push_str(&format!(
+            "{INCREMENTAL_SECTION}\nparent={}\nkind={kind}\n",
+            parent.name
+        ));
+        if let Some(id) = &parent.id {
+            txt.push_str(&format!("parent_id={id}\n"));
+        }
+    }
+    txt
+}
+
+/// contents of UNCHANGED_ENTRY, obfuscated along with the manifest so it doesn't leak paths
+pub fn render_unchanged(keys: &[String], obfuscate: bool) -> String {
+    render_list(keys.iter().map(|k| format!("{k}\n")).collect(), obfuscate)
+}
+
+pub fn parse_unchanged(txt: &str) -> Vec<String> {
+    parse_list(txt, "unchanged-file list")
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// contents of CHECKSUMS_ENTRY from (key, hex digest) pairs, sha256sum's layout
+pub fn render_checksums(sums: &[(String, String)], obfuscate: bool) -> String {
+    let lines = sums.iter().map(|(k, d)| format!("{d}  {k}\n")).collect();
+    render_list(lines, obfuscate)
+}
+
+/// key -> hex digest
+pub fn parse_checksums(txt: &str) -> HashMap<String, String> {
+    parse_list(txt, "checksum list")
+        .lines()
+        .filter_map(|l| l.split_once("  "))
+        .map(|(d, k)| (k.to_string(), d.to_string()))
+        .collect()
+}
+
+/// `plain`/`obfuscated` first line, then the list
+fn render_list(lines: String, obfuscate: bool) -> String {
+    if obfuscate {
+        format!("obfuscated\n{}", obfuscate_block(&lines))
+    } else {
+        format!("plain\n{lines}")
+    }
+}
+
+fn parse_list(txt: &str, what: &str) -> String {
+    let (kind, body) = txt.split_once('\n').unwrap_or((txt, ""));
+    match kind.trim() {
+        "obfuscated" => deobfuscate_block(body).unwrap_or_else(|e| {
+            elog!("ERROR: {what} {e}");
+            String::new()
+        }),
+        _ => body.to_string(),
+    }
+}
+
+/// pulls `uuid: path` pairs out of a path section
+fn parse_path_lines(lines: &str, verbose: bool, map: &mut HashMap<String, PathBuf>) {
+    for line in lines.lines() {
+        if let Some((uuid, p)) = line.split_once(": ") {
+            if verbose {
+                dlog!("[DEBUG]   Parsed fingerprint: {} → {}", uuid, p.trim());
+            }
+            map.insert(uuid.to_string(), PathBuf::from(p.trim()));
+        }
+    }
+}
+
+/// value of a `key=value` line in a manifest section
+fn manifest_value(body: &str, key: &str) -> Option<String> {
+    body.lines()
+        .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+        .map(|v| v.trim().to_string())
+}
+
+/// just the fingerprint.txt of an archive, it's the first entry so this stops early
+pub fn read_manifest(zip_path: &Path, verbose: bool) -> Result<Manifest, String> {
+    let mut archive = Archive::new(open_archive_reader(zip_path)?);
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if entry.path().map_err(|e| e.to_string())?.to_string_lossy() == "fingerprint.txt" {
+            let mut txt = String::new();
+            entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
+            return Ok(parse_manifest(&txt, verbose));
+        }
+    }
+    Err("no fingerprint.txt in archive".into())
+}
+
+/// parses fingerprint.txt, handles both plain and obfuscated manifests
+pub fn parse_manifest(txt: &str, verbose: bool) -> Manifest {
+    let mut manifest = Manifest::default();
+    let mut section = "";
+    let mut section_body = String::new();
+    let mut sections: Vec<(&str, String)> = Vec::new();
+
+    for line in txt.lines() {
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push((section, std::mem::take(&mut section_body)));
+            section = line;
+            continue;
+        }
+        section_body.push_str(line);
+        section_body.push('\n');
+    }
+    sections.push((section, section_body));
+
+    for (name, body) in &sections {
+        match *name {
+            "" => {
+                manifest.valid = body.contains(get_fingered())
+                    || body.trim() == format!("fp-sha256:{}", fingerprint_hash());
+            }
+            MANIFEST_SECTION => parse_path_lines(body, verbose, &mut manifest.paths),
+            // can't decode it without the matching fingerprint anyway
+            MANIFEST_SECTION_OBFUSCATED if manifest.valid => match deobfuscate_block(body) {
+                Ok(lines) => parse_path_lines(&lines, verbose, &mut manifest.paths),
+                Err(e) => elog!("ERROR: obfuscated manifest {e}"),
+            },
+            NOTES_SECTION => parse_note_lines(body, &mut manifest.notes),
+            NOTES_SECTION_OBFUSCATED if manifest.valid => match deobfuscate_block(body) {
+                Ok(lines) => parse_note_lines(&lines, &mut manifest.notes),
+                Err(e) => elog!("ERROR: obfuscated notes {e}"),
+            },
+            ARCHIVE_SECTION => {
+                manifest.id = manifest_value(body, "id");
+                manifest.min_version = manifest_value(body, "min_version");
+                manifest.created = manifest_value(body, "created")
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc));
+            }
+            INCREMENTAL_SECTION => {
+                manifest.parent = manifest_value(body, "parent").map(|name| ParentLink {
+                    name,
+                    id: manifest_value(body, "parent_id"),
+                    differential: manifest_value(body, "kind").as_deref() == Some("differential"),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    manifest
+}
+
+impl Manifest {
+    /// Err("this backup needs Konserve ≥ X…") when the archive was written by a newer format
+    /// than this build reads, checked before anything gets extracted
+    pub fn check_version(&self) -> Result<(), String> {
+        let Some(needed) = &self.min_version else {
+            return Ok(());
+        };
+        let parse = |v: &str| -> Vec<u64> {
+            v.split(['.', '-', '+'])
+                .map_while(|p| p.parse().ok())
+                .collect()
+        };
+        let running = env!("CARGO_PKG_VERSION");
+        if parse(needed) > parse(running) {
+            return Err(format!(
+                "this backup needs Konserve ≥ {needed}, this is {running}. Update Konserve to restore it."
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// fills in `%APPDATA%`, `$HOME`, `${XDG_CONFIG_HOME}`, `{USER}` and a leading `~` so one template
+/// fits every machine. unset variables stay as written, the path then just doesn't exist
+pub fn expand_vars(path: &Path) -> PathBuf {
+    let raw = path.to_string_lossy();
+    if !raw.contains(['%', '$', '{', '~']) {
+        return path.to_path_buf();
+    }
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut out = String::new();
+    let mut windows_style = false;
+    let mut rest: &str = &raw;
+    if let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty() || after.starts_with(['/', '\\']))
+        && let Some(home) = dirs::home_dir()
+    {
+        out.push_str(&home.to_string_lossy());
+        rest = after;
+    }
+    while let Some(c) = rest.chars().next() {
+        // (variable name, how much of `rest` it takes up)
+        let var = match c {
+            '%' => rest[1..]
+                .find('%')
+                .map(|end| (&rest[1..1 + end], end + 2))
+                .filter(|(name, _)| {
+                    !name.is_empty() && name.chars().all(|c| is_name(c) || "()".contains(c))
+                }),
+            '$' if rest[1..].starts_with('{') => rest[2..]
+                .find('}')
+                .map(|end| (&rest[2..2 + end], end + 3))
+                .filter(|(name, _)| !name.is_empty() && name.chars().all(is_name)),
+            '$' => {
+                let len = rest[1..].find(|c| !is_name(c)).unwrap_or(rest.len() - 1);
+                (len > 0).then(|| (&rest[1..1 + len], len + 1))
+            }
+            '{' => rest[1..]
+                .find('}')
+                .map(|end| (&rest[1..1 + end], end + 2))
+                .filter(|(name, _)| !name.is_empty() && name.chars().all(is_name)),
+            _ => None,
+        };
+        match var.and_then(|(name, len)| Some((env_var(name)?, len))) {
+            Some((value, len)) => {
+                windows_style |= c == '%';
+                out.push_str(&value);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    // `%APPDATA%\MyApp` on linux, the backslashes came with the windows way of writing it
+    if windows_style && !cfg!(windows) {
+        out = out.replace('\\', "/");
+    }
+    PathBuf::from(out)
+}
+
+/// an environment variable, or for the usual per-user folders whatever this OS calls them, so
+/// `%APPDATA%` still means something on linux and `$HOME` on windows
+fn env_var(name: &str) -> Option<String> {
+    if let Ok(value) = std::env::var(name)
+        && !value.is_empty()
+    {
+        return Some(value);
+    }
+    let dir = match name.to_ascii_uppercase().as_str() {
+        "HOME" | "USERPROFILE" => dirs::home_dir(),
+        "APPDATA" | "XDG_CONFIG_HOME" => dirs::config_dir(),
+        "LOCALAPPDATA" | "XDG_DATA_HOME" => dirs::data_local_dir(),
+        "XDG_CACHE_HOME" => dirs::cache_dir(),
+        "USER" | "USERNAME" => {
+            return ["USER", "USERNAME"]
+                .iter()
+                .find_map(|v| std::env::var(v).ok().filter(|u| !u.is_empty()))
+                .or_else(|| {
+                    dirs::home_dir()?
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                });
+        }
+        _ => None,
+    };
+    dir.map(|d| d.to_string_lossy().into_owned())
+}
+
 /// swaps C:\Users\<old> for the current user's home dir if it matches
 pub fn adjust_path(original: &Path, current_home: &Path, verbose: bool) -> PathBuf {
     let og_str = original.to_string_lossy();
@@ -783,16 +3679,176 @@ pub fn adjust_path(original: &Path, current_home: &Path, verbose: bool) -> PathB
 }
 
 pub fn fix_skip(path: &Path, verbose: bool) -> Option<PathBuf> {
+    match fix_path(path, verbose) {
+        PathFix::Found(p) => Some(p),
+        PathFix::Choose(_) | PathFix::Missing => None,
+    }
+}
+
+/// what a template path turned into on this machine
+pub enum PathFix {
+    Found(PathBuf),
+    /// written on another OS and more than one folder here fits, the user gets to pick
+    Choose(Vec<PathBuf>),
+    Missing,
+}
+
+/// like fix_skip, but also translates paths from templates written on another OS
+pub fn fix_path(path: &Path, verbose: bool) -> PathFix {
+    let expanded = expand_vars(path);
+    if verbose && expanded != path {
+        dlog!(
+            "[DEBUG] fix_path: {} expands to {}",
+            path.display(),
+            expanded.display()
+        );
+    }
+    let path = expanded.as_path();
     if path.exists() {
-        return Some(path.to_path_buf());
+        return PathFix::Found(path.to_path_buf());
     }
-    let current_home = dirs::home_dir()?;
+    let Some(current_home) = dirs::home_dir() else {
+        return PathFix::Missing;
+    };
     let adjusted = adjust_path(path, &current_home, verbose);
     if adjusted.exists() {
-        Some(adjusted)
-    } else {
-        None
+        return PathFix::Found(adjusted);
+    }
+    let mut found: Vec<PathBuf> = translate_path(path, &current_home)
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect();
+    if verbose {
+        dlog!(
+            "[DEBUG] fix_path: {} translates to {} existing paths",
+            path.display(),
+            found.len()
+        );
+    }
+    match found.len() {
+        0 => PathFix::Missing,
+        1 => PathFix::Found(found.remove(0)),
+        _ => PathFix::Choose(found),
+    }
+}
+
+/// which OS a path was written on, going by where its home folder sits
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PathOs {
+    Windows,
+    Linux,
+    MacOs,
+}
+
+impl PathOs {
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            PathOs::Windows
+        } else if cfg!(target_os = "macos") {
+            PathOs::MacOs
+        } else {
+            PathOs::Linux
+        }
+    }
+}
+
+/// the same kind of folder on each OS, relative to home: (windows, linux, macos).
+/// a folder in more than one row (AppData/Local, Application Support) maps ambiguously
+const PATH_TRANSLATIONS: &[(&str, &str, &str)] = &[
+    ("AppData/Roaming", ".config", "Library/Application Support"),
+    (
+        "AppData/Local",
+        ".local/share",
+        "Library/Application Support",
+    ),
+    (
+        "AppData/LocalLow",
+        ".local/share",
+        "Library/Application Support",
+    ),
+    ("AppData/Local", ".cache", "Library/Caches"),
+];
+
+/// splits a path into the OS it came from and the part under its home folder, `/` separated.
+/// None if it isn't under a home folder
+fn split_home(path: &str) -> Option<(PathOs, String)> {
+    let path = path.replace('\\', "/");
+    let under = |rest: &str| -> String {
+        rest.split_once('/')
+            .map(|(_, rel)| rel.trim_matches('/').to_string())
+            .unwrap_or_default()
+    };
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[1] == b':' && path[2..].to_lowercase().starts_with("/users/") {
+        return Some((PathOs::Windows, under(&path["X:/Users/".len()..])));
+    }
+    if let Some(rest) = path.strip_prefix("/Users/") {
+        return Some((PathOs::MacOs, under(rest)));
+    }
+    if let Some(rest) = path.strip_prefix("/home/") {
+        return Some((PathOs::Linux, under(rest)));
+    }
+    if path == "/root" || path.starts_with("/root/") {
+        return Some((
+            PathOs::Linux,
+            path["/root".len()..].trim_matches('/').to_string(),
+        ));
+    }
+    None
+}
+
+/// where a path under someone's home folder (possibly on another OS) would be under
+/// `current_home`, using PATH_TRANSLATIONS for the folders that move around between systems.
+/// more than one entry = ambiguous, empty = not a home path at all
+pub fn translate_path(original: &Path, current_home: &Path) -> Vec<PathBuf> {
+    let Some((from, rel)) = split_home(&original.to_string_lossy()) else {
+        return Vec::new();
+    };
+    let to = PathOs::current();
+    let column = |row: &(&'static str, &'static str, &'static str), os| match os {
+        PathOs::Windows => row.0,
+        PathOs::Linux => row.1,
+        PathOs::MacOs => row.2,
+    };
+    let under_home = |rel: &str| {
+        let mut p = current_home.to_path_buf();
+        p.extend(rel.split('/').filter(|c| !c.is_empty()));
+        p
+    };
+
+    // same OS, only the user changed
+    if from == to {
+        return vec![under_home(&rel)];
+    }
+    // windows + macos homes don't care about case
+    let fold = |s: &str| {
+        if from == PathOs::Linux {
+            s.to_string()
+        } else {
+            s.to_lowercase()
+        }
+    };
+    let rel_folded = fold(&rel);
+    let prefix_len = |row| {
+        let prefix = fold(column(row, from));
+        let hit = rel_folded == prefix || rel_folded.starts_with(&format!("{prefix}/"));
+        hit.then_some(prefix.len())
+    };
+    let Some(longest) = PATH_TRANSLATIONS.iter().filter_map(prefix_len).max() else {
+        return vec![under_home(&rel)];
+    };
+
+    let mut out: Vec<PathBuf> = Vec::new();
+    for row in PATH_TRANSLATIONS {
+        if prefix_len(row) != Some(longest) {
+            continue;
+        }
+        let candidate = under_home(&format!("{}/{}", column(row, to), &rel[longest..]));
+        if !out.contains(&candidate) {
+            out.push(candidate);
+        }
     }
+    out
 }
 
 #[cfg(target_os = "windows")]
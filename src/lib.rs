@@ -0,0 +1,30 @@
+//! konserve's archive side: backing up, restoring, verifying and the helpers they share.
+//! the gui in main.rs sits on top of this, tests/ use it directly
+pub mod backup;
+pub mod crypt;
+pub mod fixtures;
+pub mod helpers;
+pub mod restore;
+pub mod verify;
+mod vss;
+
+use std::collections::HashMap;
+
+/// one node in the restore tree, either a file or a folder with kids
+#[derive(Default)]
+pub struct FolderTreeNode {
+    pub children: HashMap<String, FolderTreeNode>,
+    pub checked: bool,
+    pub is_file: bool,
+    /// shown instead of the map key when set, restore roots are keyed by full path
+    pub label: Option<String>,
+    /// archive uuid, only set on restore roots
+    pub uuid: Option<String>,
+    /// restore tree only: bytes and file count of this file, or everything below this folder
+    pub size: u64,
+    pub files: usize,
+    /// some of those files only live in an earlier archive of the chain, their size isn't known here
+    pub size_partial: bool,
+    /// restore roots only: the note attached to this root when it was backed up
+    pub note: Option<String>,
+}
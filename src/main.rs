@@ -1,25 +1,41 @@
 //! konserve, backs up your stuff and restores it later
 #![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
 
-mod backup;
-mod helpers;
-mod restore;
-
-use backup::backup_gui;
+use konserve::{FolderTreeNode, backup, crypt, dlog, elog, fixtures, helpers, restore, verify};
+use backup::{BackupOptions, BackupReport, backup_gui};
 use helpers::BackupNameMode;
 use helpers::ConflictResolutionMode;
+use helpers::OwnershipMode;
+use helpers::AccentColor;
+use helpers::ChecksumExport;
+use helpers::Theme;
+use helpers::DirPermissions;
 use helpers::Progress;
+use helpers::build_backup_tree;
 use helpers::build_human_tree;
 use helpers::collect_paths;
+use helpers::collect_unchecked;
+use helpers::DestinationHealth;
+use helpers::destination_health;
+use helpers::format_size;
+use helpers::apply_human_paths;
+use helpers::collect_human_paths;
+use helpers::SearchHit;
+use helpers::search_archives;
+use helpers::find_identical;
+use helpers::load_template_snapshot;
+use helpers::save_template_snapshot;
+use helpers::manifest_summary_json;
 use helpers::exe_dir;
 use helpers::fix_skip;
+use helpers::PathFix;
 use helpers::init_crash_log;
 use helpers::load_icon_image;
-use helpers::parse_fingerprint;
+use helpers::list_archive;
 use helpers::render_tree;
 use helpers::set_status;
 use helpers::verbose_log_path;
-use restore::{ConflictAnswer, restore_backup};
+use restore::{ConflictAnswer, RestoreOptions, ScriptKind, restore_backup, restore_script};
 
 use std::{
     collections::HashMap,
@@ -27,6 +43,7 @@ use std::{
     path::{Path, PathBuf},
     sync::{Arc, Mutex, mpsc},
     thread,
+    time::Instant,
 };
 
 use chrono::Local;
@@ -86,8 +103,26 @@ struct PendingBackup {
     detected: Vec<(usize, Option<PathBuf>)>,
 }
 
-/// restore preview result: tree + archive path on success, error string on fail
-type RestoreMsg = Result<(FolderTreeNode, PathBuf), String>;
+/// a backup waiting for the laptop to be plugged in
+struct HeldBackup {
+    folders: Vec<PathBuf>,
+    out_dir: PathBuf,
+    filename: String,
+    /// last time the power state was looked at
+    checked: Instant,
+}
+
+/// restore preview result: tree + archive path + when it was made + why it can't be exported as a
+/// script, error string on fail
+type RestoreMsg = Result<
+    (
+        FolderTreeNode,
+        PathBuf,
+        Option<std::time::SystemTime>,
+        Option<&'static str>,
+    ),
+    String,
+>;
 
 /// paths back from a background file dialog
 type FileDialogMsg = Vec<PathBuf>;
@@ -95,18 +130,169 @@ type FileDialogMsg = Vec<PathBuf>;
 /// result from the background app-detection thread
 type DetectResult = (Vec<(usize, Option<PathBuf>)>, Vec<PathBuf>, PathBuf, String);
 
+/// one path of a template. switched-off paths stay in the template but are left out of backups
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "TemplatePathJson", into = "TemplatePathJson")]
+struct TemplatePath {
+    path: PathBuf,
+    enabled: bool,
+    /// goes into the manifest of backups with this path, shown in the restore tree. empty = none
+    note: String,
+}
+
+/// on disk a template path is a plain string like before, only switched-off or noted ones become
+/// `{"path": …, "enabled": false, "note": …}`, so old templates and old builds keep working
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum TemplatePathJson {
+    Plain(PathBuf),
+    Toggled {
+        path: PathBuf,
+        #[serde(default = "switched_on")]
+        enabled: bool,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        note: String,
+    },
+}
+
+fn switched_on() -> bool {
+    true
+}
+
+impl From<TemplatePathJson> for TemplatePath {
+    fn from(json: TemplatePathJson) -> Self {
+        match json {
+            TemplatePathJson::Plain(path) => Self { path, enabled: true, note: String::new() },
+            TemplatePathJson::Toggled { path, enabled, note } => Self { path, enabled, note },
+        }
+    }
+}
+
+impl From<TemplatePath> for TemplatePathJson {
+    fn from(p: TemplatePath) -> Self {
+        if p.enabled && p.note.is_empty() {
+            Self::Plain(p.path)
+        } else {
+            Self::Toggled { path: p.path, enabled: p.enabled, note: p.note }
+        }
+    }
+}
+
+impl From<PathBuf> for TemplatePath {
+    fn from(path: PathBuf) -> Self {
+        Self { path, enabled: true, note: String::new() }
+    }
+}
+
 /// saved paths you can reload for later backups
 #[derive(Serialize, Deserialize)]
 struct BackupTemplate {
-    paths: Vec<PathBuf>,
+    paths: Vec<TemplatePath>,
+    /// where backups of this template go, skips the folder picker and the exe-dir setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    destination: Option<PathBuf>,
+    /// extra exclude globs for this template's backups, on top of the ones in settings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exclude_patterns: Vec<String>,
+    /// other templates whose paths and exclude patterns get pulled in, relative to this file's folder
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    includes: Vec<PathBuf>,
+}
+
+impl BackupTemplate {
+    /// reads a template with everything it includes merged in: paths and exclude patterns add up,
+    /// the destination stays this template's own. Err is the status line to show
+    fn load(path: &Path) -> Result<Self, String> {
+        Self::load_included(path, &mut Vec::new())
+    }
+
+    /// just what's in the file, includes listed but not merged in. Err is the status line to show
+    fn load_own(path: &Path) -> Result<Self, String> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let data = fs::read_to_string(path).map_err(|e| {
+            elog!("ERROR: failed to read template {}: {e}", path.display());
+            format!("❌ Couldn't read template {name}.")
+        })?;
+        serde_json::from_str(&data).map_err(|e| {
+            elog!("ERROR: failed to parse template {}: {e}", path.display());
+            format!("❌ Bad template format in {name}.")
+        })
+    }
+
+    /// `chain` = templates currently being loaded, one of them showing up again is a loop
+    fn load_included(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Self, String> {
+        let name = |p: &Path| p.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let real = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(start) = chain.iter().position(|p| *p == real) {
+            let names: Vec<String> = chain[start..].iter().chain([&real]).map(|p| name(p)).collect();
+            elog!("ERROR: template include loop: {}", names.join(" -> "));
+            return Err(format!("❌ Templates include each other: {}", names.join(" → ")));
+        }
+
+        let mut template = Self::load_own(path)?;
+
+        chain.push(real);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for include in template.includes.clone() {
+            let included = Self::load_included(&dir.join(include), chain)?;
+            for p in included.paths {
+                if !template.paths.iter().any(|t| t.path == p.path) {
+                    template.paths.push(p);
+                }
+            }
+            for pattern in included.exclude_patterns {
+                if !template.exclude_patterns.contains(&pattern) {
+                    template.exclude_patterns.push(pattern);
+                }
+            }
+        }
+        chain.pop();
+        Ok(template)
+    }
+
+    /// writes the template, whatever the file held before is kept as a numbered version first.
+    /// Err is the status line to show
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            elog!("ERROR: failed to serialize template: {e}");
+            "❌ Failed to serialize template.".to_string()
+        })?;
+        helpers::save_template_version(path);
+        fs::write(path, json).map_err(|e| {
+            elog!("ERROR: failed to write template {}: {e}", path.display());
+            "❌ Failed to write template.".to_string()
+        })
+    }
+}
+
+/// a template path from another OS that fits more than one folder here
+struct PathReview {
+    original: PathBuf,
+    options: Vec<PathBuf>,
+    /// the template's note for the path, carried over to whichever folder gets picked
+    note: String,
+    /// None = leave it out
+    chosen: Option<usize>,
 }
 
-/// one node in the restore tree, either a file or a folder with kids
-#[derive(Default)]
-struct FolderTreeNode {
-    children: HashMap<String, FolderTreeNode>,
-    checked: bool,
-    is_file: bool,
+/// a template path with wildcards, expanded when the template loads and again before every backup
+struct TemplateGlob {
+    /// as written in the template
+    path: PathBuf,
+    /// placeholders filled in, what gets matched against the disk
+    pattern: PathBuf,
+    /// what it matched last time, these are in selected_folders
+    matches: Vec<PathBuf>,
+    note: String,
+}
+
+/// checked restore items saved to json, as original paths so they apply to other archives too
+#[derive(Serialize, Deserialize)]
+struct RestoreSelection {
+    /// archive it was made from, just for reference
+    #[serde(default)]
+    archive: Option<PathBuf>,
+    paths: Vec<String>,
 }
 
 /// entry point, sets up env vars + icon + eframe and launches the gui
@@ -122,20 +308,28 @@ fn main() -> Result<(), eframe::Error> {
         eprintln!("PANIC: {msg}");
     }));
 
-    let icon = load_icon_image();
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size([460.0, 600.0])
+        .with_resizable(false)
+        .with_app_id(helpers::APP_ID);
+    if let Some(icon) = load_icon_image() {
+        viewport = viewport.with_icon(icon);
+    }
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([460.0, 600.0])
-            .with_resizable(false)
-            .with_icon(icon),
+        viewport,
         ..Default::default()
     };
 
     let result = eframe::run_native(
         "Konserve",
         options,
-        Box::new(|_cc| Ok(Box::new(GUIApp::default()))),
+        Box::new(|cc| {
+            let mut app = GUIApp::default();
+            cc.egui_ctx.set_theme(app.theme.preference());
+            app.take_paths(std::env::args_os().skip(1).map(PathBuf::from).collect(), "passed on the command line");
+            Ok(Box::new(app))
+        }),
     );
 
     if let Err(ref e) = result {
@@ -148,21 +342,129 @@ fn main() -> Result<(), eframe::Error> {
 #[derive(PartialEq)]
 enum MainTab {
     Home,
+    Search,
+    /// past backups, restores and verifies with their job logs
+    History,
     Settings,
+    /// hidden until Ctrl+Shift+D, fixture archives for reproducing reported archive bugs
+    Diagnostics,
+}
+
+/// how many matching paths the search tab lists per archive before "…and N more"
+const SEARCH_SHOWN: usize = 50;
+
+/// a job is running until its progress says done, or its thread is gone without saying so
+fn job_running(progress: &Option<Progress>, thread: &Option<thread::JoinHandle<()>>) -> bool {
+    progress.as_ref().is_some_and(|p| p.get() <= 100) && !thread.as_ref().is_some_and(|t| t.is_finished())
+}
+
+/// where a backup thread hands in backup_gui's result, same for every backup entry point
+struct BackupSink {
+    status: Arc<Mutex<String>>,
+    last_archive: Arc<Mutex<Option<PathBuf>>>,
+    /// (template file, its roots that exist right now), recorded once the backup succeeds
+    template_snapshot: Option<(PathBuf, Vec<PathBuf>)>,
+}
+
+impl BackupSink {
+    /// also marks `progress` done whatever happened, not every early error in backup_gui does
+    fn finish(self, progress: &Progress, result: Result<BackupReport, String>) {
+        match result {
+            Ok(report) => {
+                if let Some((template, present)) = &self.template_snapshot {
+                    save_template_snapshot(template, present);
+                }
+                set_status(&self.status, report.summary());
+                *self.last_archive.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.archive);
+            }
+            Err(e) if e == backup::CANCELLED => {
+                set_status(&self.status, "❌ Backup cancelled, the partial archive was deleted.");
+            }
+            Err(e) => {
+                elog!("ERROR: backup failed: {e}");
+                set_status(&self.status, format!("❌ Backup failed: {e}"));
+            }
+        }
+        progress.done();
+    }
 }
 
 /// all the app state: settings, selected paths, progress, active tab
 struct GUIApp {
     status: Arc<Mutex<String>>,
+    /// archive written by the last successful backup, for the copy buttons under the status
+    last_archive: Arc<Mutex<Option<PathBuf>>>,
+    /// "Copy manifest" reads the whole archive, the json comes back from a thread
+    manifest_copy_rx: Option<mpsc::Receiver<(PathBuf, Result<String, String>)>>,
     selected_folders: Vec<PathBuf>,
     template_editor: bool,
-    template_paths: Vec<PathBuf>,
+    template_paths: Vec<TemplatePath>,
+    template_edit_destination: Option<PathBuf>,
+    /// one pattern per line, same as the settings box
+    template_edit_excludes: String,
+    /// the edited template's includes, kept as written (not merged) so saving doesn't bake them in
+    template_edit_includes: Vec<PathBuf>,
+    /// file the editor was opened from, and its earlier versions
+    template_edit_path: Option<PathBuf>,
+    template_history: Vec<helpers::TemplateVersion>,
+    /// version number + its diff against the current file, when one is expanded
+    template_history_diff: Option<(u32, Vec<(char, String)>)>,
+    /// paths past backups keep picking that the edited template doesn't have, with how often
+    template_suggest_rx: Option<mpsc::Receiver<Vec<(PathBuf, usize)>>>,
+    template_suggestions: Option<Vec<(PathBuf, usize)>>,
+    /// destination of the currently loaded template, overrides the global save location
+    template_destination: Option<PathBuf>,
+    /// exclude patterns of the currently loaded template
+    template_excludes: Vec<String>,
+    /// the loaded template's own includes and exclude patterns, as written in its file
+    template_includes: Vec<PathBuf>,
+    template_own_excludes: Vec<String>,
+    /// paths the loaded template's includes brought in, as written and as selected. saving the
+    /// selection as a template leaves them to the includes instead of baking them in
+    template_inherited: Vec<PathBuf>,
+    /// template paths waiting for the user to pick where they map to
+    path_review: Vec<PathReview>,
+    /// file + (path-corrected) roots of the loaded template, for the last-backup snapshot
+    loaded_template: Option<(PathBuf, Vec<PathBuf>)>,
+    /// switched-off paths of the loaded template, kept so saving the selection as a template keeps them
+    template_disabled: Vec<TemplatePath>,
+    /// wildcard paths of the loaded template
+    template_globs: Vec<TemplateGlob>,
+    /// templates opened or saved lately, newest first, offered on the home tab
+    recent_templates: Vec<PathBuf>,
+    /// selection they were worked out for + (nested, folder it's in) indexes, see nested_selections
+    selection_overlaps: (Vec<PathBuf>, Vec<(usize, usize)>),
+    /// notes attached to selected roots, written into the backup's manifest
+    root_notes: HashMap<PathBuf, String>,
+    /// selected root whose note is being edited on the home tab
+    note_editing: Option<PathBuf>,
     restore_editor: bool,
     restore_zip_path: Option<PathBuf>,
+    /// why the open archive can't be exported as a tar script, see restore::script_blocker
+    restore_script_blocker: Option<&'static str>,
+    /// when the open archive's backup ran, see helpers::backup_time
+    restore_backed_up: Option<std::time::SystemTime>,
     restore_tree: FolderTreeNode,
+    /// search box above the restore tree, and what it matched
+    restore_filter: String,
+    restore_filter_hits: Option<helpers::TreeFilter>,
+    backup_tree_editor: bool,
+    backup_tree: FolderTreeNode,
+    backup_tree_rx: Option<mpsc::Receiver<FolderTreeNode>>,
+    /// stuff under the selected roots that got unchecked in the backup tree
+    backup_excluded: Vec<PathBuf>,
     _saved_path_map: Option<HashMap<String, PathBuf>>,
     backup_progress: Option<Progress>,
     restore_progress: Option<Progress>,
+    verify_progress: Option<Progress>,
+    /// threads of the last backup / restore, a job whose thread is gone isn't running whatever
+    /// its progress says
+    backup_thread: Option<thread::JoinHandle<()>>,
+    restore_thread: Option<thread::JoinHandle<()>>,
+    /// what the running backup reads (and writes the archive into) / the running restore writes to
+    backup_job_paths: Vec<PathBuf>,
+    restore_job_paths: Vec<PathBuf>,
+    max_jobs: u32,
     restore_opening: bool,
     restore_rx: Option<mpsc::Receiver<RestoreMsg>>,
     // async filedialog handling for linux being fuck and freezing.
@@ -172,20 +474,76 @@ struct GUIApp {
     default_backup_location: Option<PathBuf>,
     conflict_resolution_enabled: bool,
     conflict_resolution_mode: ConflictResolutionMode,
+    ownership_mode: OwnershipMode,
+    ownership_root_only: bool,
+    dir_permissions: DirPermissions,
     verbose_logging: bool,
+    /// count error categories (no paths) for bug reports, viewed on the diagnostics tab
+    error_stats: bool,
+    /// Ctrl+Shift+D shows the diagnostics tab
+    diagnostics: bool,
+    fixture_kind: fixtures::FixtureKind,
+    fixture_shape: fixtures::FixtureShape,
+    /// least severe line the home tab's log panel shows, follows the verbose setting
+    log_level: helpers::LogLevel,
     automatic_updates: bool,
     file_size_summary: bool,
     save_to_exe_dir: bool,
     save_template_exe_dir: bool,
     load_templates_from_exe_dir: bool,
     backup_name_mode: BackupNameMode,
+    obfuscate_manifest: bool,
+    include_konserve_config: bool,
+    stale_after_days: u32,
+    destination_quotas: HashMap<PathBuf, u32>,
+    retention: HashMap<PathBuf, helpers::Retention>,
+    scratch_dir: Option<PathBuf>,
+    stall_warn_secs: u32,
+    search_query: String,
+    search_rx: Option<mpsc::Receiver<Vec<SearchHit>>>,
+    search_results: Option<Vec<SearchHit>>,
+    /// read from the job log folder whenever the history tab gets opened
+    job_history: Vec<helpers::JobRecord>,
+    /// original paths to check once the archive being opened is loaded, instead of everything
+    pending_restore_selection: Option<Vec<String>>,
+    encrypt_backups: bool,
+    incremental_backups: bool,
+    differential_baseline: Option<PathBuf>,
+    /// one pattern per line, split up on save
+    exclude_patterns: String,
+    accent_color: AccentColor,
+    /// (highlight, button fill) resolved from accent_color, refreshed on save
+    accent: (egui::Color32, egui::Color32),
+    checksum_export: ChecksumExport,
+    snapshot_json: bool,
+    shadow_copy: bool,
+    hold_on_battery: bool,
+    materialize_symlinks: bool,
+    reset_mtimes: bool,
+    theme: Theme,
+    // session only, never saved
+    backup_passphrase: String,
+    backup_passphrase_confirm: String,
+    /// encrypted archive waiting for its passphrase before it can be opened
+    passphrase_prompt: Option<PathBuf>,
+    restore_passphrase: String,
+    /// cached scan for the destinations panel, None = rescan next frame
+    destination_health: Option<Vec<DestinationHealth>>,
+    /// the panel's "Check" for leftovers and broken chains, running or done
+    destination_check_rx: Option<mpsc::Receiver<Vec<helpers::DestinationCheck>>>,
+    destination_checks: Option<Vec<helpers::DestinationCheck>>,
     // scratch buffer for the name input in settings
     backup_name_input: String,
     overwrite_confirm: Option<PathBuf>,
+    /// the window got closed while a backup or restore was still writing, asking what to do
+    close_prompt: bool,
+    /// close the window by itself once the running jobs are done
+    close_when_done: bool,
     conflict_rx: Option<mpsc::Receiver<PathBuf>>,
     conflict_answer_tx: Option<mpsc::Sender<ConflictAnswer>>,
     conflict_file: Option<PathBuf>,
     pending_backup: Option<PendingBackup>,
+    held_backup: Option<HeldBackup>,
     detecting_apps: bool,
     detect_rx: Option<mpsc::Receiver<DetectResult>>,
     closed_apps: Vec<ClosedApp>,
@@ -200,15 +558,52 @@ impl Default for GUIApp {
         let config = helpers::KonserveConfig::load();
         let app = Self {
             status: Arc::new(Mutex::new("Waiting...".to_string())),
+            last_archive: Arc::new(Mutex::new(None)),
+            manifest_copy_rx: None,
             selected_folders: Vec::new(),
             template_editor: false,
             template_paths: Vec::new(),
+            template_edit_destination: None,
+            template_edit_excludes: String::new(),
+            template_edit_includes: Vec::new(),
+            template_edit_path: None,
+            template_history: Vec::new(),
+            template_history_diff: None,
+            template_suggest_rx: None,
+            template_suggestions: None,
+            template_destination: None,
+            template_excludes: Vec::new(),
+            template_includes: Vec::new(),
+            template_own_excludes: Vec::new(),
+            template_inherited: Vec::new(),
+            path_review: Vec::new(),
+            loaded_template: None,
+            template_globs: Vec::new(),
+            recent_templates: helpers::recent_templates(),
+            selection_overlaps: (Vec::new(), Vec::new()),
+            template_disabled: Vec::new(),
+            root_notes: HashMap::new(),
+            note_editing: None,
             restore_editor: false,
             restore_zip_path: None,
+            restore_script_blocker: None,
+            restore_backed_up: None,
             restore_tree: FolderTreeNode::default(),
+            restore_filter: String::new(),
+            restore_filter_hits: None,
+            backup_tree_editor: false,
+            backup_tree: FolderTreeNode::default(),
+            backup_tree_rx: None,
+            backup_excluded: Vec::new(),
             _saved_path_map: None,
             backup_progress: None,
             restore_progress: None,
+            verify_progress: None,
+            backup_thread: None,
+            restore_thread: None,
+            backup_job_paths: Vec::new(),
+            restore_job_paths: Vec::new(),
+            max_jobs: config.max_jobs.max(1),
             restore_opening: false,
             restore_rx: None,
             file_dialog_rx: None,
@@ -217,7 +612,15 @@ impl Default for GUIApp {
             default_backup_location: config.default_backup_location.clone(),
             conflict_resolution_enabled: config.conflict_resolution_enabled,
             conflict_resolution_mode: config.conflict_resolution_mode,
+            ownership_mode: config.ownership_mode,
+            ownership_root_only: config.ownership_root_only,
+            dir_permissions: config.dir_permissions,
             verbose_logging: config.verbose_logging,
+            error_stats: config.error_stats,
+            diagnostics: false,
+            fixture_kind: fixtures::FixtureKind::default(),
+            fixture_shape: fixtures::FixtureShape::default(),
+            log_level: if config.verbose_logging { helpers::LogLevel::Debug } else { helpers::LogLevel::Info },
             automatic_updates: config.automatic_updates,
             file_size_summary: false,
             save_to_exe_dir: config.save_to_exe_dir,
@@ -227,11 +630,46 @@ impl Default for GUIApp {
                 BackupNameMode::Timestamp(s) | BackupNameMode::Fixed(s) => s.clone(),
             },
             backup_name_mode: config.backup_name_mode.clone(),
+            obfuscate_manifest: config.obfuscate_manifest,
+            include_konserve_config: config.include_konserve_config,
+            stale_after_days: config.stale_after_days,
+            destination_quotas: config.destination_quotas.clone(),
+            retention: config.retention.clone(),
+            scratch_dir: config.scratch_dir.clone(),
+            stall_warn_secs: config.stall_warn_secs,
+            search_query: String::new(),
+            search_rx: None,
+            search_results: None,
+            job_history: Vec::new(),
+            pending_restore_selection: None,
+            encrypt_backups: config.encrypt_backups,
+            incremental_backups: config.incremental_backups,
+            differential_baseline: config.differential_baseline.clone(),
+            exclude_patterns: config.exclude_patterns.join("\n"),
+            accent_color: config.accent_color,
+            accent: config.accent_color.colors(),
+            checksum_export: config.checksum_export,
+            snapshot_json: config.snapshot_json,
+            shadow_copy: config.shadow_copy,
+            hold_on_battery: config.hold_on_battery,
+            materialize_symlinks: config.materialize_symlinks,
+            reset_mtimes: config.reset_mtimes,
+            theme: config.theme,
+            backup_passphrase: String::new(),
+            backup_passphrase_confirm: String::new(),
+            passphrase_prompt: None,
+            restore_passphrase: String::new(),
+            destination_health: None,
+            destination_check_rx: None,
+            destination_checks: None,
             overwrite_confirm: None,
+            close_prompt: false,
+            close_when_done: false,
             conflict_rx: None,
             conflict_answer_tx: None,
             conflict_file: None,
             pending_backup: None,
+            held_backup: None,
             detecting_apps: false,
             detect_rx: None,
             closed_apps: Vec::new(),
@@ -243,11 +681,745 @@ impl Default for GUIApp {
         if app.verbose_logging {
             helpers::init_verbose_log();
         }
+        helpers::set_error_stats(app.error_stats);
         app
     }
 }
 
 impl GUIApp {
+    /// snapshot of the settings a backup run needs, taken before the thread spawns
+    fn backup_options(&self, skip_locked: bool, out_dir: &Path, filename: &str) -> BackupOptions {
+        // a baseline only counts for backups going into its own folder, restore looks for it there
+        let output = out_dir.join(filename);
+        let baseline = self
+            .differential_baseline
+            .clone()
+            .filter(|b| b.parent() == Some(out_dir) && *b != output);
+        // incremental chains onto the newest archive already there, never the one being replaced,
+        // and never some other program's tar sitting in the same folder
+        let differential = baseline.is_some();
+        let parent = baseline.or_else(|| {
+            self.incremental_backups
+                .then(|| {
+                    helpers::archives_in(&[out_dir.to_path_buf()])
+                        .into_iter()
+                        .find(|p| *p != output && helpers::is_konserve_archive(p))
+                })
+                .flatten()
+        });
+        BackupOptions {
+            verbose: self.verbose_logging,
+            skip_locked,
+            obfuscate_manifest: self.obfuscate_manifest,
+            include_konserve_config: self.include_konserve_config,
+            excluded: self.backup_excluded.clone(),
+            exclude_patterns: helpers::parse_patterns(&self.exclude_patterns)
+                .into_iter()
+                .chain(self.template_excludes.iter().cloned())
+                .collect(),
+            scratch_dir: self.scratch_dir.clone(),
+            passphrase: self.encrypt_backups.then(|| self.backup_passphrase.clone()),
+            parent,
+            differential,
+            checksum_export: self.checksum_export,
+            snapshot_json: self.snapshot_json,
+            retention: self.retention.get(out_dir).copied().unwrap_or_default(),
+            notes: self.root_notes.clone(),
+            shadow_copy: self.shadow_copy,
+        }
+    }
+
+    /// the Add Folders / Add Files dialog. macos wants dialogs on the main thread, linux and
+    /// windows run it on a background thread (it freezes the ui otherwise) and file_dialog_rx
+    /// picks the result up
+    fn pick_paths(&mut self, folders: bool) {
+        let pick = move || {
+            let dialog = FileDialog::new().set_directory(exe_dir());
+            let picked = if folders { dialog.pick_folders() } else { dialog.pick_files() };
+            picked.unwrap_or_default()
+        };
+
+        #[cfg(target_os = "macos")]
+        {
+            self.selected_folders.extend(pick());
+            self.selected_folders.sort();
+            self.selected_folders.dedup();
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        if self.file_dialog_rx.is_none() {
+            self.file_dialog_opening = true;
+            let (tx, rx) = mpsc::channel::<FileDialogMsg>();
+            self.file_dialog_rx = Some(rx);
+            std::thread::spawn(move || {
+                let _ = tx.send(pick());
+            });
+        }
+    }
+
+    /// the restore editor gets its own window, so the main one stays usable for starting backups
+    /// while a big selection is picked through. closing that window is the same as Cancel
+    fn show_restore_window(&mut self, ctx: &egui::Context) {
+        let name = self
+            .restore_zip_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("restore_editor"),
+            egui::ViewportBuilder::default()
+                .with_title(format!("Restore – {name}"))
+                .with_inner_size([480.0, 560.0]),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.close_restore_editor();
+                    return;
+                }
+                // same margin the main window draws its tabs with
+                egui::Frame::new()
+                    .inner_margin(egui::Margin::symmetric(8, 4))
+                    .show(ui, |ui| self.restore_editor_ui(ui));
+            },
+        );
+    }
+
+    fn close_restore_editor(&mut self) {
+        self.restore_editor = false;
+        self.restore_opening = false;
+        self.restore_zip_path = None;
+        self.restore_script_blocker = None;
+        self.restore_backed_up = None;
+        self.restore_tree = FolderTreeNode::default();
+        *self.status.lock().unwrap() = String::new();
+    }
+
+    /// restore tree, filter and the buttons under it
+    fn restore_editor_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Restore Selection");
+        if let Some(t) = self.restore_backed_up {
+            ui.weak(format!("Backed up {} ({})", helpers::format_timestamp(t), helpers::format_ago(t)));
+        }
+
+        ui.add_space(4.0);
+
+        if ui
+            .add(
+                egui::TextEdit::singleline(&mut self.restore_filter)
+                    .hint_text("🔍 filter by name")
+                    .desired_width(f32::INFINITY),
+            )
+            .changed()
+        {
+            let query = self.restore_filter.trim();
+            self.restore_filter_hits = (!query.is_empty()).then(|| helpers::filter_tree(&self.restore_tree, query));
+        }
+        if self.restore_filter_hits.as_ref().is_some_and(|f| f.shown.is_empty()) {
+            ui.weak("Nothing in this archive matches.");
+        }
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                let mut current_path = vec![];
+                render_tree(
+                    ui,
+                    &mut current_path,
+                    &mut self.restore_tree,
+                    self.restore_filter_hits.as_ref(),
+                    self.verbose_logging,
+                )
+            });
+
+        ui.separator();
+
+        if ui.button("Restore selected").clicked()
+            && let Some(zip_path) = &self.restore_zip_path.clone()
+        {
+            let current_home = dirs::home_dir().unwrap_or_default();
+            let targets: Vec<PathBuf> = collect_human_paths(&mut self.restore_tree)
+                .iter()
+                .map(|p| helpers::adjust_path(Path::new(p), &current_home, false))
+                .collect();
+            if let Some(msg) = self.job_blocked("restore", &targets) {
+                set_status(&self.status, msg);
+                return;
+            }
+            let selected = collect_paths(&self.restore_tree, self.verbose_logging);
+            let zip_path = zip_path.clone();
+            let status = self.status.clone();
+
+            let progress = Progress::default();
+            self.restore_progress = Some(progress.clone());
+            self.restore_job_paths = targets;
+            self.restore_opening = false;
+            let mode = if self.conflict_resolution_enabled {
+                self.conflict_resolution_mode
+            } else {
+                ConflictResolutionMode::Overwrite
+            };
+
+            let conflict_ch = if mode == ConflictResolutionMode::Prompt {
+                let (ctx, crx) = mpsc::channel::<PathBuf>();
+                let (atx, arx) = mpsc::channel::<ConflictAnswer>();
+                self.conflict_rx = Some(crx);
+                self.conflict_answer_tx = Some(atx);
+                Some((ctx, arx))
+            } else {
+                self.conflict_rx = None;
+                self.conflict_answer_tx = None;
+                None
+            };
+
+            let opts = RestoreOptions {
+                verbose: self.verbose_logging,
+                mode,
+                ownership: self.ownership_mode,
+                ownership_root_only: self.ownership_root_only,
+                dir_permissions: self.dir_permissions,
+                materialize_symlinks: self.materialize_symlinks,
+                reset_mtimes: self.reset_mtimes,
+            };
+
+            self.restore_thread = Some(thread::spawn(move || {
+                match restore_backup(&zip_path, Some(selected), status.clone(), &progress, &opts, conflict_ch) {
+                    Ok(()) => {}
+                    // restore_backup already put how far it got in the status
+                    Err(e) if e == backup::CANCELLED => {}
+                    Err(e) => {
+                        elog!("ERROR: restore failed: {e}");
+                        set_status(&status, format!("❌ Restore failed: {e}"));
+                    }
+                }
+                // a bad fingerprint or damaged archive returns before restore_backup gets to it
+                progress.done();
+            }));
+
+            self.restore_editor = false;
+        }
+
+        if ui.button("Verify archive")
+            .on_hover_text("Read the whole archive back and compare every file with the checksum taken when it was packed")
+            .clicked()
+            && let Some(zip_path) = self.restore_zip_path.clone()
+        {
+            if let Some(msg) = self.job_blocked("verify", &[]) {
+                set_status(&self.status, msg);
+            } else {
+                let progress = Progress::default();
+                self.verify_progress = Some(progress.clone());
+                let status = self.status.clone();
+                let verbose = self.verbose_logging;
+                set_status(&status, "Verifying archive…");
+                thread::spawn(move || {
+                    match verify::verify_archive(&zip_path, &progress, verbose) {
+                        Ok(report) => set_status(&status, report.summary()),
+                        Err(e) => set_status(&status, format!("❌ Verification failed: {e}")),
+                    }
+                    progress.done();
+                });
+            }
+        }
+
+        let export = ui.add_enabled(self.restore_script_blocker.is_none(), egui::Button::new("Export as script"))
+            .on_hover_text("Save the checked items as a script that restores them with plain tar");
+        let export = match self.restore_script_blocker {
+            Some(why) => export.on_disabled_hover_text(why),
+            None => export,
+        };
+        if export.clicked()
+            && let Some(zip_path) = self.restore_zip_path.clone()
+        {
+            let selected = collect_paths(&self.restore_tree, self.verbose_logging);
+            let kind = ScriptKind::native();
+            let script_name = format!("konserve-restore.{}", kind.extension());
+            if let Some(path) = FileDialog::new()
+                .set_directory(exe_dir())
+                .set_file_name(&script_name)
+                .add_filter("Script", &[kind.extension()])
+                .save_file()
+            {
+                match restore_script(&zip_path, &selected, kind)
+                    .and_then(|script| fs::write(&path, script).map_err(|e| e.to_string()))
+                {
+                    Ok(()) => set_status(&self.status, format!("✅ Restore script saved:\n{}", path.display())),
+                    Err(e) => {
+                        elog!("ERROR: failed to export restore script {}: {e}", path.display());
+                        set_status(&self.status, format!("❌ Couldn't export script: {e}"));
+                    }
+                }
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Save selection").clicked()
+                && let Some(path) = FileDialog::new()
+                    .set_directory(exe_dir())
+                    .set_file_name("restore-selection.json")
+                    .add_filter("JSON", &["json"])
+                    .save_file()
+            {
+                let selection = RestoreSelection {
+                    archive: self.restore_zip_path.clone(),
+                    paths: collect_human_paths(&mut self.restore_tree),
+                };
+                match serde_json::to_string_pretty(&selection) {
+                    Ok(json) => match fs::write(&path, json) {
+                        Ok(()) => set_status(&self.status, "✅ Selection saved"),
+                        Err(e) => {
+                            elog!("ERROR: failed to write restore selection {}: {e}", path.display());
+                            set_status(&self.status, "❌ Couldn't write file.");
+                        }
+                    },
+                    Err(e) => {
+                        elog!("ERROR: failed to serialize restore selection: {e}");
+                        set_status(&self.status, "❌ Failed to serialize.");
+                    }
+                }
+            }
+
+            if ui.button("Load selection").clicked()
+                && let Some(path) = FileDialog::new()
+                    .set_directory(exe_dir())
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+            {
+                match fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| serde_json::from_str::<RestoreSelection>(&data).map_err(|e| e.to_string()))
+                {
+                    Ok(selection) => {
+                        let matched = apply_human_paths(&mut self.restore_tree, &selection.paths);
+                        let msg = if matched == selection.paths.len() {
+                            format!("✅ Selection loaded, {matched} paths")
+                        } else {
+                            format!(
+                                "⚠ Selection loaded, {} of {} paths aren't in this archive",
+                                selection.paths.len() - matched,
+                                selection.paths.len()
+                            )
+                        };
+                        set_status(&self.status, msg);
+                    }
+                    Err(e) => {
+                        elog!("ERROR: failed to load restore selection {}: {e}", path.display());
+                        set_status(&self.status, "❌ Bad selection file.");
+                    }
+                }
+            }
+        });
+
+        if ui.button("Cancel").clicked() {
+            self.close_restore_editor();
+        }
+
+        // export/selection feedback, the main window's status box is a window away
+        let status_text = self.status.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if !status_text.is_empty() {
+            ui.separator();
+            ui.label(status_text);
+        }
+    }
+
+    /// reads the archive's fingerprint on a thread, the restore editor opens once it's back
+    fn open_archive(&mut self, zip_file: PathBuf) {
+        if crypt::is_encrypted(&zip_file) && !crypt::has_passphrase(&zip_file) {
+            self.restore_passphrase.clear();
+            self.passphrase_prompt = Some(zip_file);
+            return;
+        }
+        self.restore_opening = true;
+        set_status(&self.status, "⚠ Only restore archives you created yourself — opening archive…");
+
+        let (tx, rx) = mpsc::channel::<RestoreMsg>();
+        self.restore_rx = Some(rx);
+        let verbose = self.verbose_logging;
+
+        thread::spawn(move || {
+            let result: RestoreMsg = list_archive(&zip_file, verbose).map(|listing| {
+                let when = helpers::backup_time(&zip_file, listing.created);
+                let blocker = restore::script_blocker(&zip_file);
+                (build_human_tree(listing, verbose), zip_file.clone(), when, blocker)
+            });
+            // most likely a mistyped passphrase, make the next open ask again
+            if result.is_err() {
+                crypt::forget_passphrase(&zip_file);
+            }
+            let _ = tx.send(result);
+        });
+    }
+
+    /// `konserve backup.tar` (or a double-click through the file association, or dropping one archive
+    /// on the window) opens the restore preview. anything else is paths from "Send to Konserve" or a
+    /// drop and lands in the selection, missing ones are skipped. `how` is for the log
+    fn take_paths(&mut self, paths: Vec<PathBuf>, how: &str) {
+        // the command line and the nautilus script hand over paths relative to where they ran,
+        // those would end up in the manifest and restore relative to wherever konserve runs then
+        let paths: Vec<PathBuf> = paths.into_iter().map(|p| std::path::absolute(&p).unwrap_or(p)).collect();
+        if let [single] = paths.as_slice()
+            && single.extension().is_some_and(|e| e.eq_ignore_ascii_case("tar"))
+        {
+            if single.is_file() {
+                self.open_archive(single.clone());
+            } else {
+                elog!("ERROR: archive {how} not found: {}", single.display());
+                set_status(&self.status, format!("❌ Archive not found: {}", single.display()));
+            }
+            return;
+        }
+
+        let (found, missing): (Vec<PathBuf>, Vec<PathBuf>) = paths.into_iter().partition(|p| p.exists());
+        for p in &missing {
+            elog!("ERROR: path {how} not found: {}", p.display());
+        }
+        if found.is_empty() && missing.is_empty() {
+            return;
+        }
+        let before = self.selected_folders.len();
+        self.selected_folders.extend(found);
+        self.selected_folders.sort();
+        self.selected_folders.dedup();
+        if missing.is_empty() {
+            set_status(&self.status, format!("✅ {} paths added", self.selected_folders.len() - before));
+        } else {
+            set_status(&self.status, format!("⚠ {} paths not found, skipped", missing.len()));
+        }
+    }
+
+    /// expands the loaded template's wildcard paths again, so what matches now gets backed up and
+    /// what's gone drops out
+    fn refresh_template_globs(&mut self) {
+        for glob in &mut self.template_globs {
+            let now = helpers::expand_glob(&glob.pattern);
+            for gone in glob.matches.iter().filter(|p| !now.contains(p)) {
+                self.selected_folders.retain(|s| s != gone);
+            }
+            for new in now.iter().filter(|p| !glob.matches.contains(p)) {
+                dlog!("[INFO] {} now also matches {}", glob.path.display(), new.display());
+                if !glob.note.is_empty() {
+                    self.root_notes.insert(new.clone(), glob.note.clone());
+                }
+                if !self.selected_folders.contains(new) {
+                    self.selected_folders.push(new.clone());
+                }
+            }
+            glob.matches = now;
+        }
+    }
+
+    /// selects a template's paths on the home tab, fixed up for this machine. ones that could be
+    /// several folders here go to review first
+    fn load_template(&mut self, path: PathBuf) {
+        match BackupTemplate::load(&path).and_then(|t| Ok((t, BackupTemplate::load_own(&path)?))) {
+            Ok((template, own)) => {
+                // everything in the merged template that isn't in the file itself came from includes
+                let mut inherited: Vec<PathBuf> = template
+                    .paths
+                    .iter()
+                    .filter(|p| !own.paths.iter().any(|o| o.path == p.path))
+                    .map(|p| p.path.clone())
+                    .collect();
+                let mut valid = Vec::new();
+                let mut skipped = Vec::new();
+                let mut review = Vec::new();
+                let (enabled, disabled): (Vec<TemplatePath>, Vec<TemplatePath>) =
+                    template.paths.into_iter().partition(|p| p.enabled);
+
+                let verbose = self.verbose_logging;
+                let mut notes = HashMap::new();
+                let mut globs = Vec::new();
+                for p in enabled {
+                    let pattern = helpers::expand_vars(&p.path);
+                    if helpers::is_glob(&pattern) {
+                        let matches = helpers::expand_glob(&pattern);
+                        dlog!("[INFO] {} matches {} paths", p.path.display(), matches.len());
+                        if inherited.contains(&p.path) {
+                            inherited.extend(matches.iter().cloned());
+                        }
+                        for m in &matches {
+                            if !p.note.is_empty() {
+                                notes.insert(m.clone(), p.note.clone());
+                            }
+                            valid.push(m.clone());
+                        }
+                        globs.push(TemplateGlob { path: p.path, pattern, matches, note: p.note });
+                        continue;
+                    }
+                    match helpers::fix_path(&p.path, verbose) {
+                        PathFix::Found(adjusted) => {
+                            if !p.note.is_empty() {
+                                notes.insert(adjusted.clone(), p.note);
+                            }
+                            if inherited.contains(&p.path) {
+                                inherited.push(adjusted.clone());
+                            }
+                            valid.push(adjusted);
+                        }
+                        PathFix::Choose(options) => review.push(PathReview {
+                            original: p.path,
+                            options,
+                            chosen: Some(0),
+                            note: p.note,
+                        }),
+                        PathFix::Missing => skipped.push(p.path),
+                    }
+                }
+                self.root_notes = notes;
+                self.note_editing = None;
+                self.template_globs = globs;
+                let to_review = review.len();
+                self.path_review = review;
+
+                // roots that made it into the last backup of this template but are gone now
+                let gone: Vec<PathBuf> = load_template_snapshot(&path)
+                    .into_iter()
+                    .filter(|p| !p.exists())
+                    .collect();
+
+                self.selected_folders = valid.clone();
+                self.template_destination = template.destination.map(|d| helpers::expand_vars(&d));
+                self.template_excludes = template.exclude_patterns;
+                self.template_includes = own.includes;
+                self.template_own_excludes = own.exclude_patterns;
+                self.loaded_template = Some((path.clone(), valid));
+                self.template_disabled = disabled;
+                self.template_inherited = inherited;
+                let msg = if !gone.is_empty() {
+                    for p in &gone {
+                        dlog!("[WARN] template path gone since last backup: {}", p.display());
+                    }
+                    let mut msg = format!(
+                        "⚠ Template loaded, {} paths from its last backup are gone:",
+                        gone.len()
+                    );
+                    for p in gone.iter().take(5) {
+                        msg.push_str(&format!("\n• {}", p.display()));
+                    }
+                    msg
+                } else if skipped.is_empty() {
+                    "✅ Template loaded".into()
+                } else {
+                    // tell them how many got skipped
+                    format!(
+                        "✅ Loaded with {} paths skipped",
+                        skipped.len()
+                    )
+                };
+
+                let msg = if self.template_disabled.is_empty() {
+                    msg
+                } else {
+                    format!(
+                        "{msg}\n{} switched off in the template.",
+                        helpers::format_count(self.template_disabled.len(), "path")
+                    )
+                };
+                let msg = if to_review > 0 {
+                    format!("{msg}\n{to_review} paths map to more than one folder here, pick them above.")
+                } else {
+                    msg
+                };
+                *self.status.lock().unwrap() = msg;
+                self.recent_templates = helpers::remember_template(&path);
+            }
+            Err(msg) => {
+                // gone since it was last opened, no point offering it again
+                if !path.exists() {
+                    self.recent_templates = helpers::forget_template(&path);
+                }
+                *self.status.lock().unwrap() = msg;
+            }
+        }
+    }
+
+    /// result handler for a backup thread, snapshots the loaded template's roots too
+    fn backup_sink(&self) -> BackupSink {
+        BackupSink {
+            status: self.status.clone(),
+            last_archive: self.last_archive.clone(),
+            template_snapshot: self.loaded_template.as_ref().map(|(file, roots)| {
+                (file.clone(), roots.iter().filter(|p| p.exists()).cloned().collect())
+            }),
+        }
+    }
+
+    /// Some(reason) if out_dir already holds more archives than its quota allows
+    fn quota_exceeded(&self, out_dir: &Path) -> Option<String> {
+        let limit_gb = *self.destination_quotas.get(out_dir).filter(|gb| **gb > 0)?;
+        let used = destination_health(out_dir).total_bytes;
+        (used >= limit_gb as u64 * 1024 * 1024 * 1024).then(|| {
+            format!(
+                "❌ {} is over its {limit_gb} GB limit ({} used).\nDelete old backups or raise the limit in Settings.",
+                out_dir.display(),
+                format_size(used)
+            )
+        })
+    }
+
+    /// backup and restore while they're still writing, quitting now would leave a half-written
+    /// archive or a half-restored folder. verify only reads, that one can just stop
+    fn writing_jobs(&self) -> Vec<&'static str> {
+        [
+            ("backup", &self.backup_progress, &self.backup_thread),
+            ("restore", &self.restore_progress, &self.restore_thread),
+        ]
+        .into_iter()
+        .filter(|(_, p, t)| job_running(p, t))
+        .map(|(kind, _, _)| kind)
+        .collect()
+    }
+
+    /// Some(reason) if a `kind` job (backup, restore, verify) touching `paths` can't start next to
+    /// the jobs already running: one of each kind at most, no more than max_jobs, and a restore
+    /// never shares paths with another job
+    fn job_blocked(&self, kind: &str, paths: &[PathBuf]) -> Option<String> {
+        let running: Vec<(&str, &[PathBuf])> = [
+            ("backup", job_running(&self.backup_progress, &self.backup_thread), self.backup_job_paths.as_slice()),
+            ("restore", job_running(&self.restore_progress, &self.restore_thread), self.restore_job_paths.as_slice()),
+            // only reads the archive, nothing another job could trip over
+            ("verify", job_running(&self.verify_progress, &None), &[]),
+        ]
+        .into_iter()
+        .filter(|(_, running, _)| *running)
+        .map(|(kind, _, paths)| (kind, paths))
+        .collect();
+        if running.iter().any(|(k, _)| *k == kind) {
+            return Some(format!("❌ A {kind} is already running, wait for it to finish."));
+        }
+        if running.len() >= self.max_jobs as usize {
+            return Some(format!(
+                "❌ {} already running, Settings allows {} at once.",
+                helpers::format_count(running.len(), "job"),
+                self.max_jobs
+            ));
+        }
+        running.iter().find_map(|(k, theirs)| {
+            helpers::shared_path(paths, theirs).map(|p| {
+                format!("❌ {} is in use by the running {k}, wait for it to finish.", p.display())
+            })
+        })
+    }
+
+    /// fills the template editor from a template file
+    fn open_template_editor(&mut self, path: &Path, template: BackupTemplate) {
+        self.template_paths = template
+            .paths
+            .into_iter()
+            .map(|p| TemplatePath {
+                // placeholders stay, they're what makes the template fit other machines
+                path: if helpers::expand_vars(&p.path) != p.path {
+                    p.path
+                } else {
+                    fix_skip(&p.path, self.verbose_logging).unwrap_or(p.path)
+                },
+                enabled: p.enabled,
+                note: p.note,
+            })
+            .collect();
+        self.template_edit_destination = template.destination;
+        self.template_edit_excludes = template.exclude_patterns.join("\n");
+        self.template_edit_includes = template.includes;
+        self.template_edit_path = Some(path.to_path_buf());
+        self.template_history = helpers::template_versions(path);
+        self.template_history_diff = None;
+        self.template_suggestions = None;
+        self.template_editor = true;
+    }
+
+    /// puts an earlier version back, the one it replaces becomes a version itself so this can be undone
+    fn roll_back_template(&mut self, path: &Path, number: u32) {
+        let Some(version) = self.template_history.iter().find(|v| v.number == number) else {
+            return;
+        };
+        let restored = fs::read_to_string(&version.path).map_err(|e| e.to_string()).and_then(|data| {
+            let template = serde_json::from_str::<BackupTemplate>(&data).map_err(|e| e.to_string())?;
+            helpers::save_template_version(path);
+            fs::write(path, &data).map_err(|e| e.to_string())?;
+            Ok(template)
+        });
+        match restored {
+            Ok(template) => {
+                self.open_template_editor(path, template);
+                set_status(&self.status, format!("✅ Template rolled back to version {number}, the replaced one is kept in the history."));
+            }
+            Err(e) => {
+                elog!("ERROR: failed to roll back {} to version {number}: {e}", path.display());
+                set_status(&self.status, "❌ Couldn't roll back the template.");
+            }
+        }
+    }
+
+    /// progress handle for a backup that's about to start, registered with what it touches
+    fn track_backup(&mut self, folders: &[PathBuf], out_dir: &Path) -> Progress {
+        let progress = Progress::default();
+        self.backup_progress = Some(progress.clone());
+        self.backup_job_paths = folders.to_vec();
+        self.backup_job_paths.push(out_dir.to_path_buf());
+        progress
+    }
+
+    /// every folder backups can currently land in, for the destinations panel
+    fn destinations(&self) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = Vec::new();
+        let candidates = [
+            self.default_backup_location.clone(),
+            self.save_to_exe_dir.then(exe_dir),
+            self.template_destination.clone(),
+        ];
+        for p in candidates.into_iter().flatten() {
+            if !out.contains(&p) {
+                out.push(p);
+            }
+        }
+        out
+    }
+
+    /// Some(reason) if out_dir can't take a backup of `folders`: over its quota, or inside one of them
+    fn destination_refused(&self, folders: &[PathBuf], out_dir: &Path) -> Option<String> {
+        if let Some(msg) = self.quota_exceeded(out_dir) {
+            return Some(msg);
+        }
+        std::iter::once(out_dir)
+            .chain(self.scratch_dir.as_deref())
+            .find_map(|dir| helpers::destination_overlap(folders, dir))
+            .map(|msg| format!("❌ {msg}"))
+    }
+
+    /// a held backup going ahead, checked again like "Create Backup" does since a restore may have
+    /// started or the destination filled up while it waited. a running job keeps it held
+    fn resume_held_backup(&mut self, held: HeldBackup) {
+        if let Some(msg) = self.job_blocked("backup", &held.folders) {
+            dlog!("[INFO] held backup waits for the running job: {msg}");
+            set_status(&self.status, msg);
+            self.held_backup = Some(HeldBackup { checked: Instant::now(), ..held });
+            return;
+        }
+        if let Some(msg) = self.destination_refused(&held.folders, &held.out_dir) {
+            dlog!("[WARN] held backup refused: {msg}");
+            set_status(&self.status, msg);
+            return;
+        }
+        set_status(&self.status, "Checking for open apps…");
+        self.spawn_detect_and_backup(held.folders, held.out_dir, held.filename);
+    }
+
+    /// checks for open apps and starts the backup, unless Settings says to hold it while on battery
+    fn begin_backup(&mut self, folders: Vec<PathBuf>, out_dir: PathBuf, filename: String) {
+        if self.hold_on_battery && helpers::on_battery() {
+            dlog!("[INFO] on battery, backup held until plugged in");
+            set_status(&self.status, "On battery, the backup starts once you plug in.");
+            self.held_backup = Some(HeldBackup {
+                folders,
+                out_dir,
+                filename,
+                checked: Instant::now(),
+            });
+            return;
+        }
+        set_status(&self.status, "Checking for open apps…");
+        self.spawn_detect_and_backup(folders, out_dir, filename);
+    }
+
     /// spawns a thread to check for conflicting apps then kicks off the backup
     fn spawn_detect_and_backup(
         &mut self,
@@ -294,16 +1466,16 @@ impl GUIApp {
         apps: Vec<ClosedApp>,
     ) {
         let status = self.status.clone();
-        let progress = Progress::default();
-        self.backup_progress = Some(progress.clone());
-        let verbose = self.verbose_logging;
+        let sink = self.backup_sink();
+        let progress = self.track_backup(&folders, &out_dir);
+        let opts = self.backup_options(false, &out_dir, &filename);
 
         set_status(&status, "Closing apps…");
 
         let (done_tx, done_rx) = mpsc::channel::<Vec<ClosedApp>>();
         self.relaunch_rx = Some(done_rx);
 
-        std::thread::Builder::new()
+        let handle = std::thread::Builder::new()
             .name("konserve-backup".into())
             .stack_size(8 * 1024 * 1024)
             .spawn(move || {
@@ -317,19 +1489,12 @@ impl GUIApp {
                 std::thread::sleep(std::time::Duration::from_millis(800));
 
                 set_status(&status, "Packing into .tar");
-                match backup_gui(&folders, &out_dir, &filename, &progress, verbose, false) {
-                    Ok(path) => {
-                        set_status(&status, format!("✅ Backup created:\n{}", path.display()));
-                    }
-                    Err(e) => {
-                        elog!("ERROR: backup failed: {e}");
-                        set_status(&status, format!("❌ Backup failed: {e}"));
-                    }
-                }
+                sink.finish(&progress, backup_gui(&folders, &out_dir, &filename, &progress, &opts));
 
                 let _ = done_tx.send(actually_closed);
             })
             .expect("failed to spawn backup thread");
+        self.backup_thread = Some(handle);
     }
 
     /// spawns the backup thread, called once the app-conflict prompt is resolved
@@ -340,47 +1505,58 @@ impl GUIApp {
         filename: String,
         skip_locked: bool,
     ) {
-        let status = self.status.clone();
-        let progress = Progress::default();
-        self.backup_progress = Some(progress.clone());
-        let verbose = self.verbose_logging;
+        let sink = self.backup_sink();
+        let progress = self.track_backup(&folders, &out_dir);
+        let opts = self.backup_options(skip_locked, &out_dir, &filename);
 
-        set_status(&status, "Packing into .tar");
+        set_status(&self.status, "Packing into .tar");
 
-        std::thread::Builder::new()
+        let handle = std::thread::Builder::new()
             .name("konserve-backup".into())
             .stack_size(8 * 1024 * 1024)
             .spawn(move || {
-                match backup_gui(
-                    &folders,
-                    &out_dir,
-                    &filename,
-                    &progress,
-                    verbose,
-                    skip_locked,
-                ) {
-                    Ok(path) => {
-                        set_status(&status, format!("✅ Backup created:\n{}", path.display()));
-                    }
-                    Err(e) => {
-                        elog!("ERROR: backup failed: {e}");
-                        set_status(&status, format!("❌ Backup failed: {e}"));
-                    }
-                }
+                sink.finish(&progress, backup_gui(&folders, &out_dir, &filename, &progress, &opts));
             })
             .expect("failed to spawn backup thread");
+        self.backup_thread = Some(handle);
     }
 }
 
 impl eframe::App for GUIApp {
     fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        // closing (the X, alt+f4, the desktop logging out) mid-job kills the job halfway, ask first
+        let running = self.writing_jobs();
+        if ui.ctx().input(|i| i.viewport().close_requested()) && !running.is_empty() {
+            ui.ctx().send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.close_prompt = true;
+        }
+        if self.close_when_done && running.is_empty() {
+            self.close_when_done = false;
+            ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+
         egui::Frame::new()
             .inner_margin(egui::Margin::symmetric(8, 4))
             .show(ui, |ui| {
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::D)) {
+                self.diagnostics = !self.diagnostics;
+                if !self.diagnostics && self.tab == MainTab::Diagnostics {
+                    self.tab = MainTab::Home;
+                }
+            }
             ui.add_space(4.0);
             ui.horizontal(|ui| {
                 ui.add_space(4.0);
-                for (label, tab) in [("Home", MainTab::Home), ("Settings", MainTab::Settings)] {
+                let mut tabs = vec![
+                    ("Home", MainTab::Home),
+                    ("Search", MainTab::Search),
+                    ("History", MainTab::History),
+                    ("Settings", MainTab::Settings),
+                ];
+                if self.diagnostics {
+                    tabs.push(("Diagnostics", MainTab::Diagnostics));
+                }
+                for (label, tab) in tabs {
                     let active = self.tab == tab;
                     let text = if active {
                         egui::RichText::new(label).strong()
@@ -388,6 +1564,9 @@ impl eframe::App for GUIApp {
                         egui::RichText::new(label)
                     };
                     if ui.selectable_label(active, text).clicked() {
+                        if tab == MainTab::History {
+                            self.job_history = helpers::job_history();
+                        }
                         self.tab = tab;
                         *self.status.lock().unwrap() = String::new();
                     }
@@ -395,18 +1574,80 @@ impl eframe::App for GUIApp {
             });
             ui.add_space(2.0);
 
+            if self.close_prompt {
+                ui.separator();
+                if running.is_empty() {
+                    self.close_prompt = false;
+                } else {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("⚠ The {} is still running, closing now would leave it half-written.", running.join(" and ")),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Close when done").on_hover_text("Konserve closes by itself once it's finished").clicked() {
+                            self.close_when_done = true;
+                            self.close_prompt = false;
+                            set_status(&self.status, "Closing once the running jobs finish…");
+                        }
+                        if ui.button("Minimize until done").on_hover_text("Out of the way, closes by itself once it's finished").clicked() {
+                            self.close_when_done = true;
+                            self.close_prompt = false;
+                            ui.ctx().send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                        }
+                        if ui.button("Cancel and close")
+                            .on_hover_text("A backup deletes its partial archive, a restore keeps the files it already wrote")
+                            .clicked()
+                        {
+                            for p in [&self.backup_progress, &self.restore_progress].into_iter().flatten() {
+                                p.cancel();
+                            }
+                            self.close_when_done = true;
+                            self.close_prompt = false;
+                            set_status(&self.status, "Cancelling, closing once the jobs have stopped…");
+                        }
+                        if ui.button("Keep open").clicked() {
+                            self.close_when_done = false;
+                            self.close_prompt = false;
+                        }
+                    });
+                }
+                ui.separator();
+            }
+
+            // passphrase prompt for encrypted archives
+            if let Some(zip) = self.passphrase_prompt.clone() {
+                ui.separator();
+                ui.colored_label(
+                    egui::Color32::LIGHT_BLUE,
+                    format!("🔒 '{}' is encrypted. Passphrase:", zip.file_name().unwrap_or_default().to_string_lossy()),
+                );
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::TextEdit::singleline(&mut self.restore_passphrase).password(true));
+                    let enter = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Open").clicked() || enter) && !self.restore_passphrase.is_empty() {
+                        crypt::remember_passphrase(&zip, std::mem::take(&mut self.restore_passphrase));
+                        self.passphrase_prompt = None;
+                        self.open_archive(zip.clone());
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.passphrase_prompt = None;
+                        self.restore_passphrase.clear();
+                        *self.status.lock().unwrap() = "❌ Cancelled.".into();
+                    }
+                });
+                ui.separator();
+            }
+
             // overwrite confirm for fixed backup names
             if let Some(ref dest) = self.overwrite_confirm.clone() {
                 ui.separator();
-                ui.colored_label(egui::Color32::YELLOW, format!("⚠ '{}' already exists. Overwrite?", dest.file_name().unwrap_or_default().to_string_lossy()));
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ '{}' already exists. Overwrite?", dest.file_name().unwrap_or_default().to_string_lossy()));
                 ui.horizontal(|ui| {
                     if ui.button("Yes, overwrite").clicked() {
                         let dest = dest.clone();
                         let folders = self.selected_folders.clone();
                         let status = self.status.clone();
-                        let progress = Progress::default();
-                        self.backup_progress = Some(progress.clone());
-                        let verbose = self.verbose_logging;
+                        let sink = self.backup_sink();
                         let Some(out_dir) = dest.parent().map(|p| p.to_path_buf()) else {
                 elog!("ERROR: overwrite confirm: dest has no parent: {}", dest.display());
                 set_status(&self.status, "❌ Internal error: invalid path.");
@@ -419,21 +1660,18 @@ impl eframe::App for GUIApp {
                 self.overwrite_confirm = None;
                 return;
             };
+                        let progress = self.track_backup(&folders, &out_dir);
+                        let opts = self.backup_options(false, &out_dir, &filename);
                         self.overwrite_confirm = None;
                         set_status(&status, "Packing into .tar");
-                        std::thread::Builder::new()
+                        let handle = std::thread::Builder::new()
                             .name("konserve-backup".into())
                             .stack_size(8 * 1024 * 1024)
                             .spawn(move || {
-                                match backup_gui(&folders, &out_dir, &filename, &progress, verbose, false) {
-                                    Ok(path) => { set_status(&status, format!("✅ Backup created:\n{}", path.display())); }
-                                    Err(e) => {
-                                        elog!("ERROR: backup failed: {e}");
-                                        set_status(&status, format!("❌ Backup failed: {e}"));
-                                    }
-                                }
+                                sink.finish(&progress, backup_gui(&folders, &out_dir, &filename, &progress, &opts));
                             })
                             .expect("failed to spawn backup thread");
+                        self.backup_thread = Some(handle);
                     }
                     if ui.button("Cancel").clicked() {
                         self.overwrite_confirm = None;
@@ -443,10 +1681,27 @@ impl eframe::App for GUIApp {
                 ui.separator();
             }
 
+            // backup held while on battery
+            if self.held_backup.is_some() {
+                ui.separator();
+                ui.label("On battery, the backup starts by itself once you plug in.");
+                ui.horizontal(|ui| {
+                    if ui.button("Back up now").clicked() {
+                        let held = self.held_backup.take().unwrap();
+                        self.resume_held_backup(held);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.held_backup = None;
+                        *self.status.lock().unwrap() = "❌ Cancelled.".into();
+                    }
+                });
+                ui.separator();
+            }
+
             // app-conflict prompt
             if let Some(ref pending) = self.pending_backup {
                 ui.separator();
-                ui.colored_label(egui::Color32::YELLOW, "⚠ The following apps may be locking files:");
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ The following apps may be locking files:");
                 for &(i, _) in &pending.detected {
                     ui.label(format!("  • {}", KNOWN_APPS[i].name));
                 }
@@ -474,6 +1729,48 @@ impl eframe::App for GUIApp {
                 ui.separator();
             }
 
+            // template paths from another OS that fit more than one folder here
+            if !self.path_review.is_empty() {
+                ui.separator();
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ These template paths could be more than one folder here, pick one:");
+                for review in &mut self.path_review {
+                    ui.label(review.original.display().to_string());
+                    ui.indent(review.original.display().to_string(), |ui| {
+                        for (i, option) in review.options.iter().enumerate() {
+                            ui.radio_value(&mut review.chosen, Some(i), option.display().to_string());
+                        }
+                        ui.radio_value(&mut review.chosen, None, "leave it out");
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        let mut picked = Vec::new();
+                        for review in self.path_review.drain(..) {
+                            let Some(path) = review.chosen.and_then(|i| review.options.into_iter().nth(i)) else {
+                                continue;
+                            };
+                            if !review.note.is_empty() {
+                                self.root_notes.insert(path.clone(), review.note);
+                            }
+                            if self.template_inherited.contains(&review.original) {
+                                self.template_inherited.push(path.clone());
+                            }
+                            picked.push(path);
+                        }
+                        if let Some((_, roots)) = &mut self.loaded_template {
+                            roots.extend(picked.iter().cloned());
+                        }
+                        self.selected_folders.extend(picked);
+                        self.selected_folders.sort();
+                        self.selected_folders.dedup();
+                    }
+                    if ui.button("Leave all out").clicked() {
+                        self.path_review.clear();
+                    }
+                });
+                ui.separator();
+            }
+
             if self.relaunch_prompt {
                 ui.separator();
                 ui.colored_label(egui::Color32::LIGHT_BLUE, "Backup finished. Relaunch apps?");
@@ -516,7 +1813,7 @@ impl eframe::App for GUIApp {
             }
             if let Some(ref path) = self.conflict_file.clone() {
                 ui.separator();
-                ui.colored_label(egui::Color32::YELLOW, "⚠ File already exists at restore destination:");
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ File already exists at restore destination:");
                 ui.label(path.display().to_string());
                 ui.add_space(4.0);
                 ui.horizontal(|ui| {
@@ -554,43 +1851,209 @@ impl eframe::App for GUIApp {
                         ui.set_width(ui.available_width());
                         let mut to_remove = None;
 
-                        for (i, path) in self.template_paths.iter_mut().enumerate() {
-                            let mut path_str = path.display().to_string();
+                        for (i, entry) in self.template_paths.iter_mut().enumerate() {
+                            let mut path_str = entry.path.display().to_string();
 
                             ui.horizontal(|ui| {
-                                ui.add_sized(
-                                    [240.0, 20.0],
-                                    egui::TextEdit::singleline(&mut path_str),
-                                );
+                                ui.checkbox(&mut entry.enabled, "")
+                                    .on_hover_text("Unchecked paths stay in the template but are left out of backups");
+                                // switched-off rows are greyed out, still editable once switched back on
+                                ui.add_enabled_ui(entry.enabled, |ui| {
+                                    let path = &mut entry.path;
+                                    ui.add_sized(
+                                        [240.0, 20.0],
+                                        egui::TextEdit::singleline(&mut path_str),
+                                    );
 
-                                if path_str != path.display().to_string() {
-                                    *path = PathBuf::from(path_str.clone());
-                                }
+                                    if path_str != path.display().to_string() {
+                                        *path = PathBuf::from(path_str.clone());
+                                    }
 
-                                if path.exists() {
-                                    ui.label("✅").on_hover_text("This path exists");
-                                } else {
-                                    ui.label("❌").on_hover_text("This path does not exist");
-                                }
+                                    let expanded = helpers::expand_vars(path);
+                                    if helpers::is_glob(&expanded) {
+                                        match helpers::expand_glob(&expanded).len() {
+                                            0 => ui.label("❌").on_hover_text("Matches nothing on this machine yet"),
+                                            n => ui.label("✅").on_hover_text(format!("Matches {} here", helpers::format_count(n, "path"))),
+                                        };
+                                    } else if expanded != *path && expanded.exists() {
+                                        ui.label("✅").on_hover_text(format!("On this machine: {}", expanded.display()));
+                                    } else if path.exists() {
+                                        ui.label("✅").on_hover_text("This path exists");
+                                    } else {
+                                        ui.label("❌").on_hover_text("This path does not exist");
+                                    }
 
-                                if ui.button("Browse").clicked()
-                                    && let Some(p) = FileDialog::new().set_directory(exe_dir()).pick_folder()
-                                {
-                                    *path = p;
-                                }
+                                    if ui.button("Browse").clicked()
+                                        && let Some(p) = FileDialog::new().set_directory(exe_dir()).pick_folder()
+                                    {
+                                        *path = p;
+                                    }
+                                });
 
                                 if ui.button("Remove").clicked() {
                                     to_remove = Some(i);
                                 }
                             });
+                            ui.add_enabled_ui(entry.enabled, |ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut entry.note)
+                                        .hint_text("Note, shown when restoring this path")
+                                        .desired_width(240.0),
+                                );
+                            });
                         }
                         if let Some(i) = to_remove {
                             self.template_paths.remove(i);
                         }
                     });
                 ui.separator();
-                if ui.button("Add Path").clicked() {
-                    self.template_paths.push(PathBuf::new());
+                if let Some(found) = self.template_suggest_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                    self.template_suggest_rx = None;
+                    self.template_suggestions = Some(found);
+                }
+                let dirs = self.destinations();
+                ui.horizontal(|ui| {
+                    if ui.button("Add Path").clicked() {
+                        self.template_paths.push(PathBuf::new().into());
+                    }
+                    if ui
+                        .add_enabled(self.template_suggest_rx.is_none() && !dirs.is_empty(), egui::Button::new("Suggest from past backups"))
+                        .on_hover_text(format!(
+                            "Lists paths that went into {}+ backups in your destinations but aren't in this template",
+                            helpers::SUGGEST_MIN_BACKUPS
+                        ))
+                        .clicked()
+                    {
+                        let (tx, rx) = mpsc::channel();
+                        self.template_suggest_rx = Some(rx);
+                        let known: Vec<PathBuf> = self.template_paths.iter().map(|p| p.path.clone()).collect();
+                        let verbose = self.verbose_logging;
+                        thread::spawn(move || {
+                            let _ = tx.send(helpers::suggest_template_paths(&dirs, &known, verbose));
+                        });
+                    }
+                    if self.template_suggest_rx.is_some() {
+                        ui.add(egui::Spinner::new().size(14.0));
+                        ui.ctx().request_repaint_after(std::time::Duration::from_millis(30));
+                    }
+                });
+                if let Some(suggestions) = &mut self.template_suggestions {
+                    suggestions.retain(|(p, _)| !self.template_paths.iter().any(|t| &t.path == p));
+                    if suggestions.is_empty() {
+                        ui.weak("Nothing your backups keep picking is missing from this template.");
+                    }
+                    let mut add = None;
+                    for (i, (path, count)) in suggestions.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("+").on_hover_text("Add to the template").clicked() {
+                                add = Some(i);
+                            }
+                            ui.label(path.display().to_string());
+                            ui.weak(format!("in {count} backups"));
+                        });
+                    }
+                    if let Some(i) = add {
+                        let (path, _) = suggestions.remove(i);
+                        self.template_paths.push(path.into());
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Destination:");
+                    let mut dest_str = self
+                        .template_edit_destination
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default();
+                    if ui
+                        .add_sized([200.0, 20.0], egui::TextEdit::singleline(&mut dest_str).hint_text("ask every time"))
+                        .changed()
+                    {
+                        self.template_edit_destination =
+                            (!dest_str.trim().is_empty()).then(|| PathBuf::from(dest_str.trim()));
+                    }
+                    if ui.button("Browse").clicked()
+                        && let Some(p) = FileDialog::new().set_directory(exe_dir()).pick_folder()
+                    {
+                        self.template_edit_destination = Some(p);
+                    }
+                });
+                ui.label("Exclude patterns (one per line):")
+                    .on_hover_text("Added to the ones in Settings for backups of this template.\n*.tmp, Thumbs.db, node_modules/**, Cache/");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.template_edit_excludes)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Includes:")
+                        .on_hover_text("Paths and exclude patterns of these templates get added when this one is loaded.");
+                    let mut to_remove = None;
+                    for (i, include) in self.template_edit_includes.iter().enumerate() {
+                        ui.label(include.display().to_string());
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            to_remove = Some(i);
+                        }
+                    }
+                    if let Some(i) = to_remove {
+                        self.template_edit_includes.remove(i);
+                    }
+                    if ui.small_button("Add…").clicked()
+                        && let Some(p) = FileDialog::new().set_directory(exe_dir()).add_filter("JSON", &["json"]).pick_file()
+                        && !self.template_edit_includes.contains(&p)
+                    {
+                        self.template_edit_includes.push(p);
+                    }
+                });
+                if let Some(edit_path) = self.template_edit_path.clone()
+                    && !self.template_history.is_empty()
+                {
+                    let mut roll_back = None;
+                    egui::CollapsingHeader::new(format!("History ({})", self.template_history.len()))
+                        .id_salt("template_history")
+                        .show(ui, |ui| {
+                            for version in &self.template_history {
+                                ui.horizontal(|ui| {
+                                    let when = version.saved.map(helpers::format_ago).unwrap_or_default();
+                                    ui.label(format!("#{} · {when}", version.number))
+                                        .on_hover_text("The template as it was before that save");
+                                    let open = self.template_history_diff.as_ref().is_some_and(|(n, _)| *n == version.number);
+                                    if ui.selectable_label(open, "Diff").clicked() {
+                                        self.template_history_diff = if open {
+                                            None
+                                        } else {
+                                            let old = fs::read_to_string(&version.path).unwrap_or_default();
+                                            let now = fs::read_to_string(&edit_path).unwrap_or_default();
+                                            Some((version.number, helpers::line_diff(&old, &now)))
+                                        };
+                                    }
+                                    if ui.button("Roll back").clicked() {
+                                        roll_back = Some(version.number);
+                                    }
+                                });
+                            }
+                            if let Some((number, diff)) = &self.template_history_diff {
+                                ui.weak(format!("#{number} → saved file (- removed since, + added since):"));
+                                egui::ScrollArea::vertical()
+                                    .id_salt("template_history_diff")
+                                    .max_height(160.0)
+                                    .show(ui, |ui| {
+                                        for (mark, line) in diff.iter().filter(|(mark, _)| *mark != ' ') {
+                                            let color = match (*mark, ui.visuals().dark_mode) {
+                                                ('+', true) => egui::Color32::LIGHT_GREEN,
+                                                ('+', false) => egui::Color32::DARK_GREEN,
+                                                _ => ui.visuals().error_fg_color,
+                                            };
+                                            ui.colored_label(color, egui::RichText::new(format!("{mark} {line}")).monospace());
+                                        }
+                                        if diff.iter().all(|(mark, _)| *mark == ' ') {
+                                            ui.weak("No differences.");
+                                        }
+                                    });
+                            }
+                        });
+                    if let Some(number) = roll_back {
+                        self.roll_back_template(&edit_path, number);
+                    }
                 }
                     let save_path = if self.save_template_exe_dir {
                     std::env::current_exe().ok()
@@ -609,22 +2072,17 @@ impl eframe::App for GUIApp {
                     if let Some(path) = path {
                         let tpl = BackupTemplate {
                             paths: self.template_paths.clone(),
+                            destination: self.template_edit_destination.clone(),
+                            exclude_patterns: helpers::parse_patterns(&self.template_edit_excludes),
+                            includes: self.template_edit_includes.clone(),
                         };
-                        match serde_json::to_string_pretty(&tpl) {
-                            Ok(json) => match fs::write(&path, json) {
-                                Ok(()) => {
-                                    *self.status.lock().unwrap() = "✅ Template saved".into();
-                                    self.template_editor = false;
-                                }
-                                Err(e) => {
-                                    elog!("ERROR: failed to write template {}: {e}", path.display());
-                                    *self.status.lock().unwrap() = "❌ Couldn't write file.".into();
-                                }
-                            },
-                            Err(e) => {
-                                elog!("ERROR: failed to serialize template: {e}");
-                                *self.status.lock().unwrap() = "❌ Failed to serialize.".into();
+                        match tpl.save(&path) {
+                            Ok(()) => {
+                                self.recent_templates = helpers::remember_template(&path);
+                                *self.status.lock().unwrap() = "✅ Template saved".into();
+                                self.template_editor = false;
                             }
+                            Err(msg) => *self.status.lock().unwrap() = msg,
                         }
                     }
                 }
@@ -637,8 +2095,8 @@ impl eframe::App for GUIApp {
                 return;
             }
 
-            if self.restore_editor {
-                ui.label("Restore Selection");
+            if self.backup_tree_editor {
+                ui.label("Backup Selection");
 
                 ui.add_space(4.0);
 
@@ -646,63 +2104,48 @@ impl eframe::App for GUIApp {
                     .max_height(300.0)
                     .show(ui, |ui| {
                         let mut current_path = vec![];
-                        render_tree(ui, &mut current_path, &mut self.restore_tree, self.verbose_logging)
+                        render_tree(ui, &mut current_path, &mut self.backup_tree, None, self.verbose_logging)
                     });
 
                 ui.separator();
 
-                if ui.button("Restore selected").clicked()
-                    && let Some(zip_path) = &self.restore_zip_path.clone()
-                {
-                    let selected = collect_paths(&self.restore_tree, self.verbose_logging);
-                    let zip_path = zip_path.clone();
-                    let status = self.status.clone();
-
-                    let progress = Progress::default();
-                    self.restore_progress = Some(progress.clone());
-                    self.restore_opening = false;
-                    let verbose = self.verbose_logging;
-                    let mode = if self.conflict_resolution_enabled {
-                        self.conflict_resolution_mode
-                    } else {
-                        ConflictResolutionMode::Overwrite
-                    };
-
-                    let conflict_ch = if mode == ConflictResolutionMode::Prompt {
-                        let (ctx, crx) = mpsc::channel::<PathBuf>();
-                        let (atx, arx) = mpsc::channel::<ConflictAnswer>();
-                        self.conflict_rx = Some(crx);
-                        self.conflict_answer_tx = Some(atx);
-                        Some((ctx, arx))
+                if ui.button("Apply").clicked() {
+                    self.backup_excluded = collect_unchecked(&self.backup_tree);
+                    self.backup_tree = FolderTreeNode::default();
+                    self.backup_tree_editor = false;
+                    let msg = if self.backup_excluded.is_empty() {
+                        "✅ Everything selected will be backed up".to_string()
                     } else {
-                        self.conflict_rx = None;
-                        self.conflict_answer_tx = None;
-                        None
+                        format!("✅ {} items excluded from backup", self.backup_excluded.len())
                     };
-
-                    thread::spawn(move || {
-                        if let Err(e) =
-                            restore_backup(&zip_path, Some(selected), status.clone(), &progress, verbose, mode, conflict_ch)
-                        {
-                            elog!("ERROR: restore failed: {e}");
-                            set_status(&status, format!("❌ Restore failed: {e}"));
-                        }
-                    });
-
-                    self.restore_editor = false;
+                    set_status(&self.status, msg);
                 }
 
                 if ui.button("Cancel").clicked() {
-                    self.restore_editor = false;
-                    self.restore_opening = false;
-                    self.restore_zip_path = None;
-                    self.restore_tree = FolderTreeNode::default();
-                    *self.status.lock().unwrap() = String::new();
+                    self.backup_tree = FolderTreeNode::default();
+                    self.backup_tree_editor = false;
                 }
 
                 return;
             }
 
+            if self.restore_editor {
+                self.show_restore_window(ui.ctx());
+            }
+
+            // a held backup goes once the power's back, looked at every few seconds whatever tab is open
+            if let Some(held) = &mut self.held_backup {
+                if held.checked.elapsed().as_secs() >= 5 {
+                    held.checked = Instant::now();
+                    if !helpers::on_battery() {
+                        let held = self.held_backup.take().unwrap();
+                        dlog!("[INFO] plugged in, starting the held backup");
+                        self.resume_held_backup(held);
+                    }
+                }
+                ui.ctx().request_repaint_after(std::time::Duration::from_secs(5));
+            }
+
             match self.tab {
                 MainTab::Home => {
                     // poll the detect-apps thread
@@ -736,12 +2179,19 @@ impl eframe::App for GUIApp {
                         }
                     }
 
+                    if let Some(tree) = self.backup_tree_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                        self.backup_tree = tree;
+                        self.backup_tree_editor = true;
+                        self.backup_tree_rx = None;
+                        set_status(&self.status, "");
+                    }
+
                     // handle the restore preview thread's result
                     if let Some(finished_msg) =
                         self.restore_rx.as_ref().and_then(|rx| rx.try_recv().ok())
                     {
                         match finished_msg {
-                            Ok((mut tree, zip)) => {
+                            Ok((mut tree, zip, when, blocker)) => {
                                 // checks every node in the tree
                                 fn check_all(n: &mut FolderTreeNode) {
                                     n.checked = true;
@@ -749,15 +2199,26 @@ impl eframe::App for GUIApp {
                                         check_all(c);
                                     }
                                 }
-                                check_all(&mut tree);
+                                match self.pending_restore_selection.take() {
+                                    Some(paths) => {
+                                        apply_human_paths(&mut tree, &paths);
+                                    }
+                                    None => check_all(&mut tree),
+                                }
 
                                 self.restore_tree = tree;
+                                self.restore_filter.clear();
+                                self.restore_filter_hits = None;
                                 self.restore_zip_path = Some(zip);
+                                self.restore_script_blocker = blocker;
+                                self.restore_backed_up = when;
                                 self.restore_editor = true;
                                 self.restore_opening = false;
                                 *self.status.lock().unwrap() = String::new();
                             }
                             Err(e) => {
+                                self.pending_restore_selection = None;
+                                self.restore_opening = false;
                                 elog!("ERROR: failed to open archive: {e}");
                                 *self.status.lock().unwrap() = format!("❌ Failed to open archive: {e}");
                             }
@@ -804,59 +2265,10 @@ impl eframe::App for GUIApp {
                         ui.set_width(ui.available_width());
                         ui.horizontal(|ui| {
                         if ui.button("Add Folders").clicked() {
-                            #[cfg(target_os = "macos")]
-                            {
-                                // macos wants dialogs on the main thread
-                                if let Some(folders) = FileDialog::new().set_directory(exe_dir()).pick_folders() {
-                                    self.selected_folders.extend(folders);
-                                    self.selected_folders.sort();
-                                    self.selected_folders.dedup();
-                                }
-                            }
-
-                            #[cfg(not(target_os = "macos"))]
-                            {
-                                // linux/windows: run the dialog on a background thread
-                                if self.file_dialog_rx.is_none() {
-                                    self.file_dialog_opening = true;
-
-                                    let (tx, rx) = mpsc::channel::<FileDialogMsg>();
-                                    self.file_dialog_rx = Some(rx);
-
-                                    std::thread::spawn(move || {
-                                        let folders =
-                                            FileDialog::new().set_directory(exe_dir()).pick_folders().unwrap_or_default();
-                                        let _ = tx.send(folders);
-                                    });
-                                }
-                            }
+                            self.pick_paths(true);
                         }
-
                         if ui.button("Add Files").clicked() {
-                            #[cfg(target_os = "macos")]
-                            {
-                                if let Some(files) = FileDialog::new().set_directory(exe_dir()).pick_files() {
-                                    self.selected_folders.extend(files);
-                                    self.selected_folders.sort();
-                                    self.selected_folders.dedup();
-                                }
-                            }
-
-                            #[cfg(not(target_os = "macos"))]
-                            {
-                                if self.file_dialog_rx.is_none() {
-                                    self.file_dialog_opening = true;
-
-                                    let (tx, rx) = mpsc::channel::<FileDialogMsg>();
-                                    self.file_dialog_rx = Some(rx);
-
-                                    std::thread::spawn(move || {
-                                        let files =
-                                            FileDialog::new().set_directory(exe_dir()).pick_files().unwrap_or_default();
-                                        let _ = tx.send(files);
-                                    });
-                                }
-                            }
+                            self.pick_paths(false);
                         }
                         });
                     }); // end picker frame
@@ -887,14 +2299,13 @@ impl eframe::App for GUIApp {
                             .filter_map(|f| f.path.clone())
                             .collect()
                     });
+                    // same as paths on the command line, a single dropped .tar opens for restore
                     if !dropped_paths.is_empty() {
-                        self.selected_folders.extend(dropped_paths);
-                        self.selected_folders.sort();
-                        self.selected_folders.dedup();
+                        self.take_paths(dropped_paths, "dropped");
                     }
                     // selected paths card
                     let stroke = if zone_hovering {
-                        egui::Stroke::new(2.0, egui::Color32::from_rgb(80, 160, 240))
+                        egui::Stroke::new(2.0, self.accent.0)
                     } else {
                         ui.visuals().widgets.noninteractive.bg_stroke
                     };
@@ -914,22 +2325,85 @@ impl eframe::App for GUIApp {
                                 });
                             } else {
                                 ui.horizontal(|ui| {
-                                    ui.weak(format!("Selected ({})", self.selected_folders.len()));
+                                    if self.backup_excluded.is_empty() {
+                                        ui.weak(format!("Selected ({})", self.selected_folders.len()));
+                                    } else {
+                                        ui.weak(format!(
+                                            "Selected ({}), {} excluded",
+                                            self.selected_folders.len(),
+                                            self.backup_excluded.len()
+                                        ));
+                                    }
                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                         if ui.small_button("Clear All").clicked() {
                                             self.selected_folders.clear();
+                                            self.backup_excluded.clear();
+                                            self.template_destination = None;
+                                            self.template_excludes.clear();
+                                            self.template_includes.clear();
+                                            self.template_own_excludes.clear();
+                                            self.template_inherited.clear();
+                                            self.path_review.clear();
+                                            self.loaded_template = None;
+                                            self.template_disabled.clear();
+                                            self.template_globs.clear();
+                                            self.root_notes.clear();
+                                            self.note_editing = None;
+                                        }
+                                        if self.backup_tree_rx.is_none()
+                                            && ui.small_button("Pick contents")
+                                                .on_hover_text("Uncheck files or subfolders you don't want in the backup")
+                                                .clicked()
+                                        {
+                                            let (tx, rx) = mpsc::channel::<FolderTreeNode>();
+                                            self.backup_tree_rx = Some(rx);
+                                            let roots = self.selected_folders.clone();
+                                            let excluded = self.backup_excluded.clone();
+                                            let verbose = self.verbose_logging;
+                                            set_status(&self.status, "Scanning selection…");
+                                            thread::spawn(move || {
+                                                let _ = tx.send(build_backup_tree(&roots, &excluded, verbose));
+                                            });
                                         }
                                     });
                                 });
                                 ui.separator();
+                                // only looked at again when the selection changes, it canonicalizes every path
+                                if self.selection_overlaps.0 != self.selected_folders {
+                                    self.selection_overlaps = (
+                                        self.selected_folders.clone(),
+                                        helpers::nested_selections(&self.selected_folders),
+                                    );
+                                }
+                                let overlaps = self.selection_overlaps.1.clone();
+                                let warn = ui.visuals().warn_fg_color;
                                 let mut to_remove = None;
                                 egui::ScrollArea::vertical()
                                     .max_height(200.0)
                                     .show(ui, |ui| {
                                         ui.set_width(ui.available_width());
                                         for (i, path) in self.selected_folders.iter().enumerate() {
+                                            let note = self.root_notes.get(path).filter(|n| !n.is_empty());
+                                            let outer = overlaps.iter().find(|(n, _)| *n == i).map(|&(_, o)| &self.selected_folders[o]);
                                             ui.horizontal(|ui| {
-                                                ui.weak("•");
+                                                match outer {
+                                                    Some(outer) => {
+                                                        ui.colored_label(warn, "⚠")
+                                                            .on_hover_text(format!("Inside {}, which is selected too", outer.display()));
+                                                    }
+                                                    None => {
+                                                        ui.weak("•");
+                                                    }
+                                                }
+                                                let editing = self.note_editing.as_ref() == Some(path);
+                                                let note_btn = ui.small_button(if note.is_some() { "📝" } else { "✏" });
+                                                let note_btn = match note {
+                                                    Some(note) => note_btn.on_hover_text(format!("Note: {note}")),
+                                                    None => note_btn.on_hover_text("Attach a note, shown when restoring this path"),
+                                                };
+                                                if note_btn.clicked() {
+                                                    self.note_editing = (!editing).then(|| path.clone());
+                                                }
                                                 if ui.selectable_label(false, path.display().to_string())
                                                     .on_hover_text("Click to remove")
                                                     .clicked()
@@ -937,10 +2411,66 @@ impl eframe::App for GUIApp {
                                                     to_remove = Some(i);
                                                 }
                                             });
+                                            if self.note_editing.as_ref() == Some(path) {
+                                                let note = self.root_notes.entry(path.clone()).or_default();
+                                                ui.add(
+                                                    egui::TextEdit::singleline(note)
+                                                        .hint_text("e.g. uses profile X, close the app before restoring")
+                                                        .desired_width(f32::INFINITY),
+                                                );
+                                            }
                                         }
                                     });
+                                // nested picks get packed with their folder anyway, this just makes it visible
+                                if !overlaps.is_empty() {
+                                    ui.separator();
+                                    ui.colored_label(warn, match overlaps.len() {
+                                        1 => "⚠ A selected path is inside another selected folder:".to_string(),
+                                        n => format!("⚠ {n} selected paths are inside other selected folders:"),
+                                    });
+                                    for &(inner, outer) in &overlaps {
+                                        let name = |p: &Path| p.file_name().map_or_else(
+                                            || p.display().to_string(),
+                                            |n| n.to_string_lossy().into_owned(),
+                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!(
+                                                "{} in {}",
+                                                name(&self.selected_folders[inner]),
+                                                name(&self.selected_folders[outer])
+                                            ))
+                                            .on_hover_text(format!(
+                                                "{}\nis inside\n{}",
+                                                self.selected_folders[inner].display(),
+                                                self.selected_folders[outer].display()
+                                            ));
+                                            if ui.small_button("Keep folder")
+                                                .on_hover_text("Drop the inner pick, the folder covers it")
+                                                .clicked()
+                                            {
+                                                to_remove = Some(inner);
+                                            }
+                                            if ui.small_button("Keep inner only")
+                                                .on_hover_text("Drop the folder, only the inner pick gets backed up")
+                                                .clicked()
+                                            {
+                                                to_remove = Some(outer);
+                                            }
+                                        });
+                                    }
+                                    if overlaps.len() > 1
+                                        && ui.small_button("Keep all folders").clicked()
+                                    {
+                                        let inner: Vec<PathBuf> = overlaps.iter().map(|&(i, _)| self.selected_folders[i].clone()).collect();
+                                        self.selected_folders.retain(|p| !inner.contains(p));
+                                        for p in &inner {
+                                            self.root_notes.remove(p);
+                                        }
+                                    }
+                                }
                                 if let Some(i) = to_remove {
-                                    self.selected_folders.remove(i);
+                                    let path = self.selected_folders.remove(i);
+                                    self.root_notes.remove(&path);
                                 }
                             }
                         });
@@ -966,45 +2496,7 @@ impl eframe::App for GUIApp {
                                     };
 
                                     if let Some(path) = path {
-                                        match fs::read_to_string(&path) {
-                                            Ok(data) => match serde_json::from_str::<BackupTemplate>(&data) {
-                                                Ok(template) => {
-                                                    let mut valid = Vec::new();
-                                                    let mut skipped = Vec::new();
-
-                                                    let verbose = self.verbose_logging;
-                                                    for p in template.paths {
-                                                        match fix_skip(&p, verbose) {
-                                                            Some(adjusted) => valid.push(adjusted),
-                                                            None => skipped.push(p),
-                                                        }
-                                                    }
-
-                                                    self.selected_folders = valid;
-                                                    let msg = if skipped.is_empty() {
-                                                        "✅ Template loaded".into()
-                                                    } else {
-                                                        // tell them how many got skipped
-                                                        format!(
-                                                            "✅ Loaded with {} paths skipped",
-                                                            skipped.len()
-                                                        )
-                                                    };
-
-                                                    *self.status.lock().unwrap() = msg;
-                                                }
-                                                Err(e) => {
-                                                    elog!("ERROR: failed to parse template {}: {e}", path.display());
-                                                    *self.status.lock().unwrap() =
-                                                        "❌ Bad template format.".into();
-                                                }
-                                            },
-                                            Err(e) => {
-                                                elog!("ERROR: failed to read template {}: {e}", path.display());
-                                                *self.status.lock().unwrap() =
-                                                    "❌ Couldn't read template file.".into();
-                                            }
-                                        }
+                                        self.load_template(path);
                                     }
                                 });
 
@@ -1019,37 +2511,82 @@ impl eframe::App for GUIApp {
                                     };
 
                                     if let Some(path) = path {
+                                        // the loaded template's switched-off paths aren't selected but still belong to it,
+                                        // and wildcard paths get saved as written instead of as what they matched.
+                                        // what its includes brought in stays with them, like the editor saves it
+                                        let own = |path: &PathBuf| !self.template_inherited.contains(path);
                                         let template = BackupTemplate {
-                                            paths: self.selected_folders.clone(),
+                                            paths: self
+                                                .selected_folders
+                                                .iter()
+                                                .filter(|path| !self.template_globs.iter().any(|g| g.matches.contains(path)))
+                                                .filter(|path| own(path))
+                                                .cloned()
+                                                .map(|path| TemplatePath {
+                                                    note: self.root_notes.get(&path).cloned().unwrap_or_default(),
+                                                    ..path.into()
+                                                })
+                                                .chain(self.template_globs.iter().filter(|g| own(&g.path)).map(|g| TemplatePath {
+                                                    path: g.path.clone(),
+                                                    enabled: true,
+                                                    note: g.note.clone(),
+                                                }))
+                                                .chain(self.template_disabled.iter().filter(|p| own(&p.path)).cloned())
+                                                .collect(),
+                                            destination: self.template_destination.clone(),
+                                            exclude_patterns: self.template_own_excludes.clone(),
+                                            includes: self.template_includes.clone(),
                                         };
 
-                                        match serde_json::to_string_pretty(&template) {
-                                            Ok(json) => match fs::write(&path, json) {
-                                                Ok(()) => {
-                                                    *self.status.lock().unwrap() =
-                                                        "✅ Template saved.".into();
-                                                }
-                                                Err(e) => {
-                                                    elog!("ERROR: failed to write template {}: {e}", path.display());
-                                                    *self.status.lock().unwrap() =
-                                                        "❌ Failed to write template.".into();
-                                                }
-                                            },
-                                            Err(e) => {
-                                                elog!("ERROR: failed to serialize template: {e}");
+                                        match template.save(&path) {
+                                            Ok(()) => {
+                                                self.recent_templates = helpers::remember_template(&path);
+                                                self.loaded_template = Some((path.clone(), self.selected_folders.clone()));
                                                 *self.status.lock().unwrap() =
-                                                    "❌ Failed to serialize template.".into();
+                                                    "✅ Template saved.".into();
                                             }
+                                            Err(msg) => *self.status.lock().unwrap() = msg,
                                         }
                                     }
                                 });
+
+                                // the daily template without going through the file dialog
+                                if !self.recent_templates.is_empty() {
+                                    let mut picked = None;
+                                    egui::ComboBox::from_id_salt("recent_templates")
+                                        .selected_text("Recent")
+                                        .width(btn_size.x)
+                                        .show_ui(ui, |ui| {
+                                            for path in &self.recent_templates {
+                                                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                                                // lots of them are template.json, the folder tells them apart
+                                                let label = match path.parent().and_then(|d| d.file_name()) {
+                                                    Some(dir) => format!("{name}  ({})", dir.to_string_lossy()),
+                                                    None => name.into_owned(),
+                                                };
+                                                if ui.selectable_label(false, label).on_hover_text(path.display().to_string()).clicked() {
+                                                    picked = Some(path.clone());
+                                                }
+                                            }
+                                        });
+                                    if let Some(path) = picked {
+                                        self.load_template(path);
+                                    }
+                                }
                         });
                         ui.vertical(|ui| {
                             let btn_size = egui::vec2(115.0, 24.0);
-                            ui.add_sized(btn_size, egui::Button::new("Create Backup")
-                                .fill(egui::Color32::from_rgb(40, 100, 180)))
+                            let create = ui.add_sized(btn_size, egui::Button::new("Create Backup")
+                                .fill(self.accent.1));
+                            let create = match &self.template_destination {
+                                Some(dest) => create.on_hover_text(format!("Saves to {} (from the template)", dest.display())),
+                                None => create,
+                            };
+                            create
                                 .clicked()
                                 .then(|| {
+                                    // a template's wildcards pick up what matches by now
+                                    self.refresh_template_globs();
                                     let folders = self.selected_folders.clone();
                                     let status = self.status.clone();
 
@@ -1057,9 +2594,32 @@ impl eframe::App for GUIApp {
                                         set_status(&status, "❌ Nothing selected.");
                                         return;
                                     }
+                                    if let Some(msg) = self.job_blocked("backup", &folders) {
+                                        set_status(&status, msg);
+                                        return;
+                                    }
+                                    if self.encrypt_backups {
+                                        if self.backup_passphrase.is_empty() {
+                                            set_status(&status, "❌ Enter a passphrase for the encrypted backup.");
+                                            return;
+                                        }
+                                        if self.backup_passphrase != self.backup_passphrase_confirm {
+                                            set_status(&status, "❌ Passphrases don't match.");
+                                            return;
+                                        }
+                                    }
 
-                                    // figure out where to save it
-                                    let out_dir = if self.save_to_exe_dir {
+                                    // figure out where to save it, a template's own destination wins
+                                    let template_dest = self.template_destination.clone().filter(|d| {
+                                        let ok = d.is_dir();
+                                        if !ok {
+                                            dlog!("[WARN] template destination {} not found, asking instead", d.display());
+                                        }
+                                        ok
+                                    });
+                                    let out_dir = if template_dest.is_some() {
+                                        template_dest
+                                    } else if self.save_to_exe_dir {
                                         std::env::current_exe().ok()
                                             .and_then(|p| p.parent().map(|d| d.to_path_buf()))
                                     } else {
@@ -1074,6 +2634,12 @@ impl eframe::App for GUIApp {
                                         return;
                                     };
 
+                                    if let Some(msg) = self.destination_refused(&folders, &out_dir) {
+                                        dlog!("[WARN] backup refused: {msg}");
+                                        set_status(&status, msg);
+                                        return;
+                                    }
+
                                     // figure out the filename
                                     let filename = match &self.backup_name_mode {
                                         BackupNameMode::Timestamp(fmt) => {
@@ -1091,40 +2657,31 @@ impl eframe::App for GUIApp {
                                         return;
                                     }
 
-                                    set_status(&status, "Checking for open apps…");
-                                    self.spawn_detect_and_backup(folders, out_dir, filename);
+                                    self.begin_backup(folders, out_dir, filename);
     });
                             ui.add_sized(btn_size, egui::Button::new("Restore Backup"))
                                 .on_hover_text("⚠ Only restore archives you created yourself. Restoring untrusted archives can overwrite files on your system.")
                                 .clicked()
                                 .then(|| {
-                                    let status = self.status.clone();
                                     if let Some(zip_file) = FileDialog::new().set_directory(exe_dir())
                                         .add_filter("Tar archives", &["tar", "tar.gz"])
                                         .pick_file()
                                     {
-                                        self.restore_opening = true;
-                                        set_status(&status, "⚠ Only restore archives you created yourself — opening archive…");
-
-                                        let (tx, rx) = mpsc::channel::<RestoreMsg>();
-                                        self.restore_rx = Some(rx);
-                                        let verbose = self.verbose_logging;
-
-                                        thread::spawn(move || {
-                                            let result: RestoreMsg = parse_fingerprint(&zip_file, verbose)
-                                                .map(|(entries, map)| {
-                                                    (
-                                                        build_human_tree(entries, map, verbose),
-                                                        zip_file.clone(),
-                                                    )
-                                                });
-                                            let _ = tx.send(result);
-                                        });
+                                        self.open_archive(zip_file);
                                     }
                                 });
                         });
                     });
 
+                    if self.encrypt_backups {
+                        ui.horizontal(|ui| {
+                            ui.label("🔒 Passphrase:");
+                            ui.add(egui::TextEdit::singleline(&mut self.backup_passphrase).password(true).desired_width(120.0));
+                            ui.label("Confirm:");
+                            ui.add(egui::TextEdit::singleline(&mut self.backup_passphrase_confirm).password(true).desired_width(120.0));
+                        });
+                    }
+
                     if self.restore_opening {
                         ui.horizontal(|ui| {
                             ui.add(egui::Spinner::new().size(16.0)); // 16 px is default
@@ -1133,7 +2690,7 @@ impl eframe::App for GUIApp {
                         ui.ctx().request_repaint_after(std::time::Duration::from_millis(30));
                     }
 
-                    for opt in [&mut self.backup_progress, &mut self.restore_progress]
+                    for opt in [&mut self.backup_progress, &mut self.restore_progress, &mut self.verify_progress]
                         .into_iter()
                         .enumerate()
                     {
@@ -1144,20 +2701,53 @@ impl eframe::App for GUIApp {
                                 0..=100 => {
                                     ui.add(
                                         egui::ProgressBar::new((p.get() as f32) / 100.0)
-                                            .fill(egui::Color32::from_rgb(80, 160, 240))
+                                            .fill(self.accent.0)
                                             .desired_height(6.0)
                                             .animate(true)
                                             .desired_width(ui.available_width()),
                                     );
                                     ui.add_space(1.0);
-                                    ui.label(format!("{pct}%"));
+                                    match p.speed() {
+                                        Some((rate, left)) => ui.label(format!(
+                                            "{pct}%  ·  {}/s  ·  {} left",
+                                            format_size(rate),
+                                            helpers::format_duration(left)
+                                        )),
+                                        None => ui.label(format!("{pct}%")),
+                                    };
                                     ui.add_space(1.0);
-                                    let progress_status = if i == 0 {
-                                        "Backing up..."
-                                    } else {
-                                        "Restoring..."
+                                    let progress_status = match i {
+                                        0 => "Backing up...",
+                                        1 => "Restoring...",
+                                        _ => "Verifying...",
                                     };
-                                    ui.label(progress_status);
+                                    ui.horizontal(|ui| {
+                                        ui.label(progress_status);
+                                        let cancel_hint = match i {
+                                            0 => Some("Stop the backup and delete the partial archive"),
+                                            1 => Some("Stop after the current file, files already restored stay"),
+                                            _ => None,
+                                        };
+                                        if let Some(hint) = cancel_hint {
+                                            if p.is_cancelled() {
+                                                ui.add_enabled(false, egui::Button::new("Cancelling…"));
+                                            } else if ui.button("Cancel").on_hover_text(hint).clicked() {
+                                                p.cancel();
+                                            }
+                                        }
+                                    });
+                                    // a pending conflict prompt holds the job on purpose, that's not a stall
+                                    let (idle, what) = p.stalled();
+                                    if self.stall_warn_secs > 0
+                                        && idle.as_secs() >= self.stall_warn_secs as u64
+                                        && self.conflict_file.is_none()
+                                    {
+                                        ui.colored_label(
+                                            ui.visuals().warn_fg_color,
+                                            format!("⚠ No progress for {}, stuck on:\n{what}", helpers::format_duration(idle)),
+                                        )
+                                        .on_hover_text("Usually a hung network share or a file another program is holding open.");
+                                    }
                                     ui.ctx().request_repaint_after(std::time::Duration::from_millis(33));
                                 }
                                 _ => {
@@ -1175,6 +2765,92 @@ impl eframe::App for GUIApp {
                             ui.set_width(ui.available_width());
                             let status_text = self.status.lock().unwrap_or_else(|e| e.into_inner()).clone();
                             ui.label(status_text.as_str());
+                            let last_archive = self.last_archive.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                            if let Some(archive) = last_archive {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Copy path")
+                                        .on_hover_text("Full path of the last created archive")
+                                        .clicked()
+                                    {
+                                        ui.ctx().copy_text(archive.display().to_string());
+                                    }
+                                    if let Some((read, result)) =
+                                        self.manifest_copy_rx.as_ref().and_then(|rx| rx.try_recv().ok())
+                                    {
+                                        self.manifest_copy_rx = None;
+                                        match result {
+                                            Ok(json) => ui.ctx().copy_text(json),
+                                            Err(e) => {
+                                                elog!("ERROR: failed to read manifest of {}: {e}", read.display());
+                                                set_status(&self.status, format!("❌ Couldn't read manifest: {e}"));
+                                            }
+                                        }
+                                    }
+                                    let reading = self.manifest_copy_rx.is_some();
+                                    let label = if reading { "Reading manifest…" } else { "Copy manifest" };
+                                    if ui.add_enabled(!reading, egui::Button::new(label).small())
+                                        .on_hover_text("JSON summary of the last archive's roots")
+                                        .clicked()
+                                    {
+                                        let (tx, rx) = mpsc::channel();
+                                        self.manifest_copy_rx = Some(rx);
+                                        let verbose = self.verbose_logging;
+                                        thread::spawn(move || {
+                                            let result = manifest_summary_json(&archive, verbose);
+                                            let _ = tx.send((archive, result));
+                                        });
+                                    }
+                                    if reading {
+                                        ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+                                    }
+                                });
+                            }
+                        });
+
+                    ui.add_space(2.0);
+                    egui::CollapsingHeader::new("Log")
+                        .id_salt("home_log")
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                // debug lines only get recorded while verbose logging is on
+                                let levels: &[helpers::LogLevel] = if self.verbose_logging {
+                                    &[helpers::LogLevel::Debug, helpers::LogLevel::Info, helpers::LogLevel::Warn, helpers::LogLevel::Error]
+                                } else {
+                                    &[helpers::LogLevel::Info, helpers::LogLevel::Warn, helpers::LogLevel::Error]
+                                };
+                                egui::ComboBox::from_id_salt("log_level")
+                                    .selected_text(self.log_level.label())
+                                    .show_ui(ui, |ui| {
+                                        for &level in levels {
+                                            ui.selectable_value(&mut self.log_level, level, level.label());
+                                        }
+                                    });
+                                if ui.small_button("Clear").clicked() {
+                                    helpers::clear_log_panel();
+                                }
+                                if !self.verbose_logging {
+                                    ui.weak("Turn on Verbose Logging in Settings for debug lines.");
+                                }
+                            });
+                            let lines = helpers::log_panel_lines(self.log_level);
+                            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                            egui::ScrollArea::vertical()
+                                .id_salt("home_log_scroll")
+                                .max_height(160.0)
+                                .stick_to_bottom(true)
+                                .auto_shrink([false, true])
+                                .show_rows(ui, row_height, lines.len(), |ui, rows| {
+                                    for line in &lines[rows] {
+                                        let text = egui::RichText::new(format!("{}  {}", line.time, line.msg)).monospace();
+                                        let text = match line.level {
+                                            helpers::LogLevel::Error => text.color(ui.visuals().error_fg_color),
+                                            helpers::LogLevel::Warn => text.color(ui.visuals().warn_fg_color),
+                                            helpers::LogLevel::Debug => text.weak(),
+                                            helpers::LogLevel::Info => text,
+                                        };
+                                        ui.add(egui::Label::new(text).truncate());
+                                    }
+                                });
                         });
                 }
 
@@ -1201,14 +2877,7 @@ impl eframe::App for GUIApp {
                             if let Some(path) = path {
                                 match fs::read_to_string(&path) {
                                     Ok(data) => match serde_json::from_str::<BackupTemplate>(&data) {
-                                        Ok(template) => {
-                                            self.template_paths = template
-                                                .paths
-                                                .into_iter()
-                                                .map(|p| fix_skip(&p, self.verbose_logging).unwrap_or(p))
-                                                .collect();
-                                            self.template_editor = true;
-                                        }
+                                        Ok(template) => self.open_template_editor(&path, template),
                                         Err(e) => {
                                             elog!("ERROR: failed to parse template {}: {e}", path.display());
                                             *self.status.lock().unwrap() =
@@ -1247,6 +2916,7 @@ impl eframe::App for GUIApp {
                             if resp.changed() {
                                 if self.verbose_logging { helpers::init_verbose_log(); }
                                 else { helpers::close_verbose_log(); }
+                                self.log_level = if self.verbose_logging { helpers::LogLevel::Debug } else { helpers::LogLevel::Info };
                             }
                             if self.verbose_logging && ui.small_button("Open Log").clicked() {
                                 let path = verbose_log_path();
@@ -1255,9 +2925,153 @@ impl eframe::App for GUIApp {
                                 #[cfg(not(target_os = "windows"))]
                                 let _ = std::process::Command::new("open").arg(&path).spawn();
                             }
+                            if ui.small_button("Job Logs")
+                                .on_hover_text("One log per backup/restore run, every processed path and warning")
+                                .clicked()
+                            {
+                                let dir = helpers::job_log_dir();
+                                let _ = fs::create_dir_all(&dir);
+                                #[cfg(target_os = "windows")]
+                                let _ = std::process::Command::new("explorer").arg(&dir).spawn();
+                                #[cfg(not(target_os = "windows"))]
+                                let _ = std::process::Command::new("open").arg(&dir).spawn();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.checkbox(&mut self.error_stats, "Count error types")
+                                .on_hover_text("Keeps local counters of what kind of errors happen (never paths or file names),\nto paste into a bug report. Nothing is sent anywhere.")
+                                .changed()
+                            {
+                                helpers::set_error_stats(self.error_stats);
+                            }
+                            if self.error_stats && ui.small_button("View").clicked() {
+                                self.diagnostics = true;
+                                self.tab = MainTab::Diagnostics;
+                            }
                         });
                         ui.checkbox(&mut self.automatic_updates, "Check for Updates on Startup (WIP)");
                         ui.checkbox(&mut self.file_size_summary, "File Size Summary (WIP)");
+                        ui.checkbox(&mut self.obfuscate_manifest, "Hide root paths and owner names in archives")
+                            .on_hover_text("Obfuscates the list of backed-up roots in fingerprint.txt and the checksum list,\nand leaves owner/group names out of the tar headers.\nFile and folder names below each root stay readable, so do file contents.\nThis isn't encryption: builds without their own fingerprint share one key, set a passphrase to really hide an archive.\nOnly builds with the same fingerprint can restore these archives.");
+                        if ui.small_button("Open .tar files with Konserve")
+                            .on_hover_text("Windows: writes konserve-open-with.reg next to the exe, double-click it to register.\nLinux: installs a konserve.desktop entry for tar archives.")
+                            .clicked()
+                        {
+                            match helpers::write_file_association() {
+                                Ok(path) => set_status(&self.status, format!("✅ File association written:\n{}", path.display())),
+                                Err(e) => {
+                                    elog!("ERROR: failed to write file association: {e}");
+                                    set_status(&self.status, format!("❌ Couldn't write file association: {e}"));
+                                }
+                            }
+                        }
+                        if ui.small_button("Add \"Send to Konserve\"")
+                            .on_hover_text("Windows: adds Konserve to the Explorer Send To menu.\nLinux: adds a Nautilus script under Scripts.\nSelected files/folders open in a new Konserve window, pre-selected.")
+                            .clicked()
+                        {
+                            match helpers::write_send_to() {
+                                Ok(path) => set_status(&self.status, format!("✅ Send to entry written:\n{}", path.display())),
+                                Err(e) => {
+                                    elog!("ERROR: failed to write send to entry: {e}");
+                                    set_status(&self.status, format!("❌ Couldn't write Send to entry: {e}"));
+                                }
+                            }
+                        }
+                        ui.checkbox(&mut self.include_konserve_config, "Include Konserve settings in backups")
+                            .on_hover_text("Packs config.json and the exe-dir template.json into every backup.\nThey show up as \"Konserve settings\" in the restore tree.");
+                        ui.checkbox(&mut self.encrypt_backups, "Encrypt backups with a passphrase")
+                            .on_hover_text("AES-256 encrypted archives, the passphrase is asked for on the Home tab and never saved.\nWithout it the backup can't be restored, there is no recovery.");
+                        ui.checkbox(&mut self.incremental_backups, "Incremental backups")
+                            .on_hover_text("Only files that changed since the newest backup in the destination get packed.\nRestoring needs every earlier backup of the chain, down to the last full one.");
+                        ui.horizontal(|ui| {
+                            ui.label("Differential baseline:")
+                                .on_hover_text("Backups into the baseline's folder pack only what changed since it, instead of since the newest backup.\nRestoring needs the baseline next to them.");
+                            match &self.differential_baseline {
+                                Some(b) => ui.label(b.file_name().unwrap_or_default().to_string_lossy()).on_hover_text(b.display().to_string()),
+                                None => ui.weak("none"),
+                            };
+                            if ui.small_button("Pick…").clicked()
+                                && let Some(archive) = rfd::FileDialog::new()
+                                    .set_directory(exe_dir())
+                                    .add_filter("Tar archives", &["tar"])
+                                    .pick_file()
+                            {
+                                self.differential_baseline = Some(archive);
+                            }
+                            if self.differential_baseline.is_some() && ui.small_button("Clear").clicked() {
+                                self.differential_baseline = None;
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Checksum file:")
+                                .on_hover_text("Writes <archive>.sha256 next to every backup, listing each packed file's SHA-256.\nExtract the archive with tar and run sha256sum -c (or shasum -c for BSD style) in that folder\nto check it without Konserve. Not for encrypted archives, tar can't read those.");
+                            egui::ComboBox::from_id_salt("checksum_export")
+                                .selected_text(self.checksum_export.label())
+                                .show_ui(ui, |ui| {
+                                    for format in [ChecksumExport::Off, ChecksumExport::Sha256sum, ChecksumExport::Bsd] {
+                                        ui.selectable_value(&mut self.checksum_export, format, format.label());
+                                    }
+                                });
+                        });
+                        ui.checkbox(&mut self.snapshot_json, "Write a snapshot summary for other backup tools")
+                            .on_hover_text("Writes <archive>.snapshot.json next to every backup: restic-style snapshot info\n(time, host, paths, parent) plus every file with size, mtime and SHA-256,\nto cross-check coverage against restic or borg listings.\nWith \"Hide root paths and owner names in archives\" on it leaves out paths, host and user,\nfiles are only listed by their archive entry.");
+                        #[cfg(target_os = "windows")]
+                        {
+                            ui.checkbox(&mut self.shadow_copy, "Back up from a shadow copy (needs admin)")
+                                .on_hover_text("Snapshots the drives the selected paths are on before packing, so files other programs\nhave open (browser profiles, Outlook .pst, game saves) get backed up as of one moment.\nThe snapshot is deleted afterwards. Without admin rights the drive is read live.");
+                        }
+                        ui.checkbox(&mut self.hold_on_battery, "Wait for power before backing up on battery")
+                            .on_hover_text("A backup started while the laptop runs on battery waits until it's plugged in.\n\"Back up now\" starts it anyway.");
+                        ui.horizontal(|ui| {
+                            ui.label("Jobs at once:");
+                            ui.add(egui::DragValue::new(&mut self.max_jobs).range(1..=3))
+                                .on_hover_text("More than 1 lets a backup, a restore and a verify run side by side.\nThere's never more than one of each, and a restore never writes where another job is working.");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Warn when one file takes longer than");
+                            ui.add(egui::DragValue::new(&mut self.stall_warn_secs).range(0..=3600));
+                            ui.label("s (0 = off)");
+                        });
+                        ui.label("Exclude patterns (one per line):")
+                            .on_hover_text("Left out of every backup, inside the selected folders.\n*.tmp        any file ending in .tmp\nnode_modules/**  the folder and everything in it\nCache/       folders named Cache\n/build      only directly in a selected folder");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.exclude_patterns)
+                                .desired_rows(3)
+                                .desired_width(f32::INFINITY),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label("Theme:");
+                            egui::ComboBox::from_id_salt("theme")
+                                .selected_text(self.theme.label())
+                                .show_ui(ui, |ui| {
+                                    for theme in [Theme::System, Theme::Dark, Theme::Light] {
+                                        ui.selectable_value(&mut self.theme, theme, theme.label());
+                                    }
+                                });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Accent color:");
+                            let is_custom = matches!(self.accent_color, AccentColor::Custom(_));
+                            egui::ComboBox::from_id_salt("accent_color")
+                                .selected_text(match self.accent_color {
+                                    AccentColor::Default => "Default",
+                                    AccentColor::System => "Follow system",
+                                    AccentColor::Custom(_) => "Custom",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.accent_color, AccentColor::Default, "Default");
+                                    ui.selectable_value(&mut self.accent_color, AccentColor::System, "Follow system")
+                                        .on_hover_text("Windows accent color, the macOS accent or GNOME's accent-color.
+Falls back to the default when there isn't one.");
+                                    if ui.selectable_label(is_custom, "Custom").clicked() && !is_custom {
+                                        let [r, g, b, _] = self.accent.0.to_array();
+                                        self.accent_color = AccentColor::Custom([r, g, b]);
+                                    }
+                                });
+                            if let AccentColor::Custom(rgb) = &mut self.accent_color {
+                                ui.color_edit_button_srgb(rgb);
+                            }
+                        });
                     });
 
                     ui.add_space(4.0);
@@ -1283,8 +3097,73 @@ impl eframe::App for GUIApp {
                                     ui.selectable_value(&mut self.conflict_resolution_mode, ConflictResolutionMode::Rename, "Rename");
                                 });
                         }
+                        ui.checkbox(&mut self.materialize_symlinks, "Restore symlinks as copies of what they point to")
+                            .on_hover_text("For restoring onto a drive or system that can't hold the links themselves.\nWindows falls back to copies on its own when it isn't allowed to make links.");
+                        ui.checkbox(&mut self.reset_mtimes, "Date restored files to the restore, not their last change")
+                            .on_hover_text("Off, restored files and folders keep the modified time they had when backed up,\nwhich sync tools and sorting by date rely on.");
                     });
 
+                    // --- ownership + permissions, only mean something on unix ---
+                    #[cfg(unix)]
+                    {
+                        ui.add_space(4.0);
+                        frame.show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.label(egui::RichText::new("Restored Files (Unix)").weak().small());
+                            ui.add_space(2.0);
+                            let is_fixed = matches!(self.ownership_mode, OwnershipMode::Fixed { .. });
+                            egui::ComboBox::from_id_salt("ownership_mode")
+                                .selected_text(match self.ownership_mode {
+                                    OwnershipMode::Restorer => "Current user",
+                                    OwnershipMode::FromArchive => "From archive",
+                                    OwnershipMode::Fixed { .. } => "Fixed uid/gid",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.ownership_mode, OwnershipMode::Restorer, "Current user");
+                                    ui.selectable_value(&mut self.ownership_mode, OwnershipMode::FromArchive, "From archive")
+                                        .on_hover_text("Matches the archived owner by user/group name, falls back to the numeric id.\nNeeds root to give files to other users.");
+                                    if ui.selectable_label(is_fixed, "Fixed uid/gid").clicked() && !is_fixed {
+                                        self.ownership_mode = OwnershipMode::Fixed { uid: 1000, gid: 1000 };
+                                    }
+                                });
+                            if let OwnershipMode::Fixed { uid, gid } = &mut self.ownership_mode {
+                                ui.horizontal(|ui| {
+                                    ui.label("uid");
+                                    ui.add(egui::DragValue::new(uid));
+                                    ui.label("gid");
+                                    ui.add(egui::DragValue::new(gid));
+                                });
+                            }
+                            if self.ownership_mode != OwnershipMode::Restorer {
+                                ui.checkbox(&mut self.ownership_root_only, "Only when running as root")
+                                    .on_hover_text("Without root the owners can't be changed, restored files then simply belong to you.");
+                            }
+
+                            ui.add_space(2.0);
+                            ui.label("New folders:");
+                            let dir_label = |p: DirPermissions| match p {
+                                DirPermissions::FromArchive => "From archive".to_string(),
+                                DirPermissions::InheritParent => "Same as parent".to_string(),
+                                DirPermissions::Fixed(mode) => format!("Fixed {mode:o}"),
+                            };
+                            egui::ComboBox::from_id_salt("dir_permissions")
+                                .selected_text(dir_label(self.dir_permissions))
+                                .show_ui(ui, |ui| {
+                                    for p in [
+                                        DirPermissions::FromArchive,
+                                        DirPermissions::InheritParent,
+                                        DirPermissions::Fixed(0o700),
+                                        DirPermissions::Fixed(0o750),
+                                        DirPermissions::Fixed(0o755),
+                                    ] {
+                                        ui.selectable_value(&mut self.dir_permissions, p, dir_label(p));
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Applies to every folder the restore has to create, including parents outside the backup.");
+                        });
+                    }
+
                     ui.add_space(4.0);
 
                     // --- backup location & naming ---
@@ -1318,6 +3197,26 @@ impl eframe::App for GUIApp {
                             }
                         });
 
+                        ui.add_space(2.0);
+                        ui.horizontal(|ui| {
+                            ui.label("Scratch folder:")
+                                .on_hover_text("In-progress archives are written here and moved to the destination when done.\nUseful when the destination is slow or a big scratch disk is around.");
+                            match &self.scratch_dir {
+                                Some(dir) => ui.label(dir.display().to_string()),
+                                None => ui.weak("next to the archive"),
+                            };
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Browse").clicked()
+                                && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                            {
+                                self.scratch_dir = Some(folder);
+                            }
+                            if self.scratch_dir.is_some() && ui.small_button("Clear").clicked() {
+                                self.scratch_dir = None;
+                            }
+                        });
+
                         ui.add_space(4.0);
 
                         const TS_PRESETS: &[(&str, &str)] = &[
@@ -1387,6 +3286,178 @@ impl eframe::App for GUIApp {
                         }
                     });
 
+                    ui.add_space(4.0);
+
+                    // --- destinations ---
+                    let dests = self.destinations();
+                    if self
+                        .destination_health
+                        .as_ref()
+                        .is_none_or(|h| h.iter().map(|d| &d.path).ne(dests.iter()))
+                    {
+                        self.destination_health = Some(dests.iter().map(|d| destination_health(d)).collect());
+                    }
+                    if let Some(checks) = self.destination_check_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                        self.destination_check_rx = None;
+                        self.destination_checks = Some(checks);
+                    }
+                    frame.show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new("Destinations").weak().small());
+                            if ui.small_button("Refresh").clicked() {
+                                self.destination_health = None;
+                                self.destination_checks = None;
+                            }
+                            if self.destination_check_rx.is_some() {
+                                ui.add(egui::Spinner::new().size(12.0));
+                            } else if ui.small_button("Check")
+                                .on_hover_text("Looks for checksum/snapshot files left behind by archives that were moved or deleted,\nand incremental backups whose parent isn't there any more. Opens every archive, so it takes a while.")
+                                .clicked()
+                            {
+                                let (tx, rx) = mpsc::channel();
+                                self.destination_check_rx = Some(rx);
+                                let dirs = dests.clone();
+                                let verbose = self.verbose_logging;
+                                thread::spawn(move || {
+                                    let checks = dirs.iter().map(|d| helpers::check_destination(d, verbose)).collect();
+                                    let _ = tx.send(checks);
+                                });
+                            }
+                        });
+                        ui.add_space(2.0);
+
+                        let stale_days = self.stale_after_days;
+                        let mut delete_leftovers = None;
+                        let health = self.destination_health.as_deref().unwrap_or_default();
+                        if health.is_empty() {
+                            ui.weak("No destination set, backups ask for a folder every time.");
+                        }
+                        for h in health {
+                            ui.horizontal(|ui| {
+                                if h.reachable {
+                                    ui.label("✅");
+                                } else {
+                                    ui.label("❌").on_hover_text("Not reachable");
+                                }
+                                ui.label(h.path.display().to_string());
+                            });
+                            if !h.reachable {
+                                continue;
+                            }
+                            let age_days = h
+                                .last_backup
+                                .and_then(|t| t.elapsed().ok())
+                                .map(|d| d.as_secs() / 86_400);
+                            let last = match h.last_backup {
+                                Some(t) => helpers::format_ago(t),
+                                None => "never".into(),
+                            };
+                            let resp = ui.weak(format!(
+                                "{}, {}, last backup {last}",
+                                helpers::format_count(h.archives, "archive"),
+                                format_size(h.total_bytes)
+                            ));
+                            if let Some(t) = h.last_backup {
+                                resp.on_hover_text(helpers::format_timestamp(t));
+                            }
+                            if stale_days > 0 && age_days.is_none_or(|d| d >= stale_days as u64) {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("⚠ No backup here in the last {stale_days} days"),
+                                );
+                            }
+                            ui.horizontal(|ui| {
+                                let mut limit_gb = self.destination_quotas.get(&h.path).copied().unwrap_or(0);
+                                ui.label("Limit");
+                                if ui.add(egui::DragValue::new(&mut limit_gb).range(0..=100_000).suffix(" GB"))
+                                    .on_hover_text("0 = no limit. New backups are refused once the .tar files here reach it.")
+                                    .changed()
+                                {
+                                    if limit_gb == 0 {
+                                        self.destination_quotas.remove(&h.path);
+                                    } else {
+                                        self.destination_quotas.insert(h.path.clone(), limit_gb);
+                                    }
+                                }
+                                if limit_gb > 0 && h.total_bytes >= limit_gb as u64 * 1024 * 1024 * 1024 {
+                                    ui.colored_label(ui.visuals().error_fg_color, "⚠ over the limit");
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                let mut keep = self.retention.get(&h.path).copied().unwrap_or_default();
+                                let hint = "After each backup here, older backup_*.tar archives are deleted unless one of these keeps them.\n0 = that rule is off, both 0 = keep everything. Fixed-name archives and\nanything a kept incremental/differential backup builds on are never deleted.";
+                                ui.label("Keep last").on_hover_text(hint);
+                                let count = ui.add(egui::DragValue::new(&mut keep.keep_last).range(0..=1000));
+                                ui.label("or newer than");
+                                let days = ui.add(egui::DragValue::new(&mut keep.keep_days).range(0..=3650).suffix(" days"));
+                                if count.changed() || days.changed() {
+                                    if keep.is_off() {
+                                        self.retention.remove(&h.path);
+                                    } else {
+                                        self.retention.insert(h.path.clone(), keep);
+                                    }
+                                }
+                            });
+                            let check = self.destination_checks.as_ref().and_then(|c| c.iter().find(|c| c.path == h.path));
+                            if let Some(check) = check {
+                                if check.is_clean() {
+                                    ui.weak("✅ No leftovers or broken chains");
+                                }
+                                for (archive, why) in &check.broken_chains {
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        format!("⚠ {} can't be restored, {why}", archive.file_name().unwrap_or_default().to_string_lossy()),
+                                    )
+                                    .on_hover_text("Put the missing backup back into this folder to restore it.");
+                                }
+                                if !check.orphan_sidecars.is_empty() {
+                                    ui.horizontal(|ui| {
+                                        let names: Vec<_> = check
+                                            .orphan_sidecars
+                                            .iter()
+                                            .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                                            .collect();
+                                        ui.label(format!("{} without an archive", helpers::format_count(names.len(), "checksum/snapshot file")))
+                                            .on_hover_text(names.join("\n"));
+                                        if ui.small_button("Delete").clicked() {
+                                            delete_leftovers = Some(h.path.clone());
+                                        }
+                                    });
+                                }
+                                if check.unreadable > 0 {
+                                    ui.weak(format!("{} couldn't be opened, not checked", helpers::format_count(check.unreadable, "archive")));
+                                }
+                            }
+                        }
+                        if let Some(dir) = delete_leftovers
+                            && let Some(check) = self.destination_checks.as_mut().and_then(|c| c.iter_mut().find(|c| c.path == dir))
+                        {
+                            let mut removed = 0;
+                            for p in std::mem::take(&mut check.orphan_sidecars) {
+                                match fs::remove_file(&p) {
+                                    Ok(()) => removed += 1,
+                                    Err(e) => {
+                                        elog!("ERROR: couldn't delete leftover {}: {e}", p.display());
+                                        check.orphan_sidecars.push(p);
+                                    }
+                                }
+                            }
+                            if check.orphan_sidecars.is_empty() {
+                                set_status(&self.status, format!("✅ Deleted {}", helpers::format_count(removed, "leftover file")));
+                            } else {
+                                set_status(&self.status, format!("⚠ {} couldn't be deleted", helpers::format_count(check.orphan_sidecars.len(), "leftover file")));
+                            }
+                        }
+
+                        ui.add_space(2.0);
+                        ui.horizontal(|ui| {
+                            ui.label("Warn after");
+                            ui.add(egui::DragValue::new(&mut self.stale_after_days).range(0..=365));
+                            ui.label("days without a backup (0 = off)");
+                        });
+                    });
+
                     // apply the default backup location change
                     let should_update = match &self.default_backup_location {
                         Some(p) => loc_str != p.display().to_string(),
@@ -1403,12 +3474,16 @@ impl eframe::App for GUIApp {
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                         if ui.add(egui::Button::new("  Save  ")
-                            .fill(egui::Color32::from_rgb(40, 100, 180)))
+                            .fill(self.accent.1))
                             .clicked()
                         {
                             self.config.verbose_logging = self.verbose_logging;
+                            self.config.error_stats = self.error_stats;
                             self.config.conflict_resolution_enabled = self.conflict_resolution_enabled;
                             self.config.conflict_resolution_mode = self.conflict_resolution_mode;
+                            self.config.ownership_mode = self.ownership_mode;
+                            self.config.ownership_root_only = self.ownership_root_only;
+                            self.config.dir_permissions = self.dir_permissions;
                             self.config.default_backup_location = self.default_backup_location.clone();
                             self.config.automatic_updates = self.automatic_updates;
                             self.config.file_size_summary = self.file_size_summary;
@@ -1416,6 +3491,28 @@ impl eframe::App for GUIApp {
                             self.config.save_template_exe_dir = self.save_template_exe_dir;
                             self.config.load_templates_from_exe_dir = self.load_templates_from_exe_dir;
                             self.config.backup_name_mode = self.backup_name_mode.clone();
+                            self.config.obfuscate_manifest = self.obfuscate_manifest;
+                            self.config.include_konserve_config = self.include_konserve_config;
+                            self.config.stale_after_days = self.stale_after_days;
+                            self.config.destination_quotas = self.destination_quotas.clone();
+                            self.config.retention = self.retention.clone();
+                            self.config.scratch_dir = self.scratch_dir.clone();
+                            self.config.stall_warn_secs = self.stall_warn_secs;
+                            self.config.encrypt_backups = self.encrypt_backups;
+                            self.config.incremental_backups = self.incremental_backups;
+                            self.config.differential_baseline = self.differential_baseline.clone();
+                            self.config.exclude_patterns = helpers::parse_patterns(&self.exclude_patterns);
+                            self.config.max_jobs = self.max_jobs;
+                            self.config.accent_color = self.accent_color;
+                            self.config.checksum_export = self.checksum_export;
+                            self.config.snapshot_json = self.snapshot_json;
+                            self.config.shadow_copy = self.shadow_copy;
+                            self.config.hold_on_battery = self.hold_on_battery;
+                            self.config.materialize_symlinks = self.materialize_symlinks;
+                            self.config.reset_mtimes = self.reset_mtimes;
+                            self.config.theme = self.theme;
+                            ui.ctx().set_theme(self.theme.preference());
+                            self.accent = self.accent_color.colors();
                             let msg = if self.config.save() { "✅ Settings saved" } else { "❌ Failed to save settings" };
                             *self.status.lock().unwrap() = msg.into();
                             ui.ctx().request_repaint();
@@ -1423,6 +3520,234 @@ impl eframe::App for GUIApp {
                     });
 
                 }
+                MainTab::Search => {
+                    if let Some(hits) = self.search_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                        self.search_rx = None;
+                        self.search_results = Some(hits);
+                    }
+
+                    let dirs = self.destinations();
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::TextEdit::singleline(&mut self.search_query)
+                                .hint_text("file name or path, * matches anything")
+                                .desired_width(ui.available_width() - 70.0),
+                        );
+                        let enter = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        let can_search = self.search_rx.is_none() && !self.search_query.trim().is_empty();
+                        if (ui.add_enabled(can_search, egui::Button::new("Search")).clicked() || enter) && can_search {
+                            let (tx, rx) = mpsc::channel();
+                            self.search_rx = Some(rx);
+                            let query = self.search_query.clone();
+                            let dirs = dirs.clone();
+                            let verbose = self.verbose_logging;
+                            thread::spawn(move || {
+                                let _ = tx.send(search_archives(&dirs, &query, verbose));
+                            });
+                        }
+                    });
+                    if ui.add_enabled(self.search_rx.is_none(), egui::Button::new("Find identical copies of a file…"))
+                        .on_hover_text("Hashes a local file and lists the archives holding the exact same content,\nhandy before deleting something you think is backed up")
+                        .clicked()
+                        && let Some(file) = FileDialog::new().pick_file()
+                    {
+                        let (tx, rx) = mpsc::channel();
+                        self.search_rx = Some(rx);
+                        let dirs = dirs.clone();
+                        let status = self.status.clone();
+                        let verbose = self.verbose_logging;
+                        thread::spawn(move || {
+                            let hits = find_identical(&dirs, &file, verbose).unwrap_or_else(|e| {
+                                elog!("ERROR: can't hash {}: {e}", file.display());
+                                set_status(&status, format!("❌ Couldn't read {}: {e}", file.display()));
+                                Vec::new()
+                            });
+                            let _ = tx.send(hits);
+                        });
+                    }
+                    if dirs.is_empty() {
+                        ui.weak("No backup destinations set up yet, set a default location in Settings.");
+                    } else {
+                        let list: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                        ui.label(egui::RichText::new(format!("Searching the archives in: {}", list.join(", "))).weak().small());
+                    }
+                    ui.add_space(4.0);
+
+                    if self.search_rx.is_some() {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::Spinner::new().size(16.0));
+                            ui.label("Searching archives…");
+                        });
+                        ui.ctx().request_repaint_after(std::time::Duration::from_millis(30));
+                    }
+
+                    let mut open: Option<(PathBuf, Vec<String>)> = None;
+                    if let Some(hits) = &self.search_results {
+                        if hits.is_empty() && self.search_rx.is_none() {
+                            ui.label("No archive contains a match.");
+                        }
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            for hit in hits {
+                                let name = hit.archive.file_name().unwrap_or_default().to_string_lossy();
+                                let age = hit.when
+                                    .map(|t| format!(", {}", helpers::format_ago(t)))
+                                    .unwrap_or_default();
+                                egui::CollapsingHeader::new(format!("{name}  ({} matches{age})", hit.paths.len()))
+                                    .id_salt(&hit.archive)
+                                    .show(ui, |ui| {
+                                        ui.label(egui::RichText::new(hit.archive.display().to_string()).weak().small());
+                                        for p in hit.paths.iter().take(SEARCH_SHOWN) {
+                                            ui.label(p);
+                                        }
+                                        if hit.paths.len() > SEARCH_SHOWN {
+                                            ui.weak(format!("…and {} more", hit.paths.len() - SEARCH_SHOWN));
+                                        }
+                                        if ui.button("Restore these…")
+                                            .on_hover_text("Opens the archive with only the matches selected")
+                                            .clicked()
+                                        {
+                                            open = Some((hit.archive.clone(), hit.paths.clone()));
+                                        }
+                                    });
+                            }
+                        });
+                    }
+                    if let Some((archive, paths)) = open {
+                        self.pending_restore_selection = Some(paths);
+                        self.tab = MainTab::Home;
+                        self.open_archive(archive);
+                    }
+                }
+
+                MainTab::History => {
+                    ui.horizontal(|ui| {
+                        ui.heading("History");
+                        if ui.small_button("Refresh").clicked() {
+                            self.job_history = helpers::job_history();
+                        }
+                    });
+                    ui.separator();
+                    if self.job_history.is_empty() {
+                        ui.weak("No backups or restores yet.");
+                    }
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("job_history").num_columns(4).striped(true).show(ui, |ui| {
+                            for job in &self.job_history {
+                                ui.label(&job.started);
+                                ui.label(&job.kind);
+                                ui.vertical(|ui| {
+                                    ui.label(&job.summary);
+                                    if !job.outcome.is_empty() {
+                                        ui.label(egui::RichText::new(&job.outcome).weak().small());
+                                    }
+                                });
+                                if ui.small_button("Open log")
+                                    .on_hover_text(job.log.display().to_string())
+                                    .clicked()
+                                {
+                                    #[cfg(target_os = "windows")]
+                                    let _ = std::process::Command::new("explorer").arg(&job.log).spawn();
+                                    #[cfg(not(target_os = "windows"))]
+                                    let _ = std::process::Command::new("open").arg(&job.log).spawn();
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+
+                MainTab::Diagnostics => {
+                    ui.heading("Diagnostics");
+                    ui.separator();
+                    ui.label("Generates a synthetic archive to reproduce archive bugs without anyone's real files.");
+                    ui.add_space(4.0);
+                    egui::Grid::new("fixture_grid").num_columns(2).show(ui, |ui| {
+                        ui.label("Kind:");
+                        egui::ComboBox::from_id_salt("fixture_kind")
+                            .selected_text(self.fixture_kind.label())
+                            .show_ui(ui, |ui| {
+                                for kind in fixtures::FixtureKind::ALL {
+                                    ui.selectable_value(&mut self.fixture_kind, kind, kind.label());
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Subfolders per folder:");
+                        ui.add(egui::Slider::new(&mut self.fixture_shape.width, 0..=8));
+                        ui.end_row();
+                        ui.label("Depth:");
+                        ui.add(egui::Slider::new(&mut self.fixture_shape.depth, 0..=5));
+                        ui.end_row();
+                        ui.label("Files per folder:");
+                        ui.add(egui::Slider::new(&mut self.fixture_shape.files, 0..=20));
+                        ui.end_row();
+                    });
+                    let out = fixtures::fixture_path(self.fixture_kind);
+                    ui.weak(format!("Written to {}", out.display()));
+                    if self.fixture_kind == fixtures::FixtureKind::OtherOs {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            "⚠ Restoring this one maps its paths onto your home folder like a real archive would.",
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Generate").clicked() {
+                            match fixtures::write_fixture(self.fixture_kind, self.fixture_shape, &out) {
+                                Ok(()) => set_status(&self.status, format!("✅ Wrote {}", out.display())),
+                                Err(e) => {
+                                    elog!("ERROR: fixture: {e}");
+                                    set_status(&self.status, format!("❌ {e}"));
+                                }
+                            }
+                        }
+                        if ui.button("Generate and open")
+                            .on_hover_text("Opens it in the restore view like any other archive")
+                            .clicked()
+                        {
+                            match fixtures::write_fixture(self.fixture_kind, self.fixture_shape, &out) {
+                                Ok(()) => {
+                                    self.tab = MainTab::Home;
+                                    self.open_archive(out.clone());
+                                }
+                                Err(e) => {
+                                    elog!("ERROR: fixture: {e}");
+                                    set_status(&self.status, format!("❌ {e}"));
+                                }
+                            }
+                        }
+                    });
+                    ui.add_space(4.0);
+                    ui.label(self.status.lock().unwrap_or_else(|e| e.into_inner()).as_str());
+
+                    ui.add_space(8.0);
+                    ui.label(egui::RichText::new("Error statistics").strong());
+                    ui.separator();
+                    if !self.error_stats {
+                        ui.weak("Off, switch on \"Count error types\" in Settings to start counting.");
+                    }
+                    let stats = helpers::error_stats();
+                    if stats.is_empty() {
+                        ui.weak("No errors counted.");
+                    } else {
+                        egui::Grid::new("error_stats_grid").num_columns(2).show(ui, |ui| {
+                            for (category, count) in &stats {
+                                ui.label(category);
+                                ui.label(count.to_string());
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Copy for bug report")
+                            .on_hover_text("Version, OS and the counters above, no paths")
+                            .clicked()
+                        {
+                            ui.ctx().copy_text(helpers::error_stats_report());
+                        }
+                        if ui.add_enabled(!stats.is_empty(), egui::Button::new("Reset")).clicked() {
+                            helpers::clear_error_stats();
+                        }
+                    });
+                }
             }
         ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
         }); // end margin frame
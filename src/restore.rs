@@ -1,12 +1,22 @@
 ﻿//! unpacks .tar backups, checks the fingerprint, puts files back where they came from
-use crate::helpers::{ConflictResolutionMode, Progress, adjust_path, get_fingered};
-use crate::{dlog, elog};
+use crate::backup::CANCELLED;
+use crate::crypt::{self, open_archive_reader};
+#[cfg(unix)]
+use crate::helpers::UnixIds;
+use crate::helpers::{
+    CHECKSUMS_ENTRY, ConflictResolutionMode, DirPermissions, JobLog, KONSERVE_CONFIG_ENTRY,
+    MAX_CHAIN, OwnershipMode, ParentLink, Progress, ProgressRead, UNCHANGED_ENTRY, adjust_path,
+    entry_human_path, format_count, format_duration, is_windows_path, konserve_config_target,
+    loose, parse_fingerprint, parse_manifest, parse_unchanged, path_prefixes, read_manifest,
+};
+use crate::{dlog, elog, jlog};
 use std::{
     collections::{HashMap, HashSet},
-    fs::{self, File},
+    fs,
     io::Read,
     path::{Path, PathBuf},
     sync::{Arc, Mutex, mpsc},
+    time::{Duration, Instant, SystemTime},
 };
 use tar::Archive;
 
@@ -17,6 +27,132 @@ pub enum ConflictAnswer {
     Rename,
 }
 
+/// per-run knobs for restore_backup, everything that isn't the archive or the selection
+#[derive(Clone, Default)]
+pub struct RestoreOptions {
+    pub verbose: bool,
+    pub mode: ConflictResolutionMode,
+    /// unix only, who ends up owning the restored files
+    pub ownership: OwnershipMode,
+    /// unix only, ownership falls back to Restorer unless running as root
+    pub ownership_root_only: bool,
+    /// unix only, mode for directories the restore creates
+    pub dir_permissions: DirPermissions,
+    /// write a copy of what archived symlinks point at instead of the links themselves
+    pub materialize_symlinks: bool,
+    /// leave restored files and folders with the time of the restore instead of the archived mtime
+    pub reset_mtimes: bool,
+}
+
+/// sets a directory restore just created to the configured mode
+#[cfg(unix)]
+fn apply_dir_permissions(dir: &Path, policy: DirPermissions) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = match policy {
+        DirPermissions::FromArchive => return,
+        DirPermissions::InheritParent => match dir.parent().and_then(|p| fs::metadata(p).ok()) {
+            Some(meta) => meta.permissions().mode() & 0o7777,
+            None => return,
+        },
+        DirPermissions::Fixed(mode) => mode & 0o7777,
+    };
+    if let Err(e) = fs::set_permissions(dir, fs::Permissions::from_mode(mode)) {
+        dlog!("[WARN] couldn't set permissions of {}: {e}", dir.display());
+    }
+}
+
+/// create_dir_all that runs the permission policy over every directory it actually made, top down
+/// so InheritParent sees the parent's final mode. `made` is every folder this restore already
+/// knows is there, so a folder with thousands of files gets checked once instead of per file
+fn create_dirs(
+    dir: &Path,
+    policy: DirPermissions,
+    made: &mut HashSet<PathBuf>,
+) -> std::io::Result<()> {
+    if made.contains(dir) {
+        return Ok(());
+    }
+    let mut missing: Vec<&Path> = Vec::new();
+    let mut cur = Some(dir);
+    while let Some(d) = cur
+        && !d.exists()
+    {
+        missing.push(d);
+        cur = d.parent();
+    }
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    for d in missing.iter().rev() {
+        apply_dir_permissions(d, policy);
+    }
+    #[cfg(not(unix))]
+    let _ = (missing, policy);
+    // its parents are there now too, up to the first one already known
+    for d in dir.ancestors() {
+        if !made.insert(d.to_path_buf()) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// the ownership setting that applies to this run, Restorer when chowning is off for non-root
+#[cfg(unix)]
+fn effective_ownership(opts: &RestoreOptions) -> OwnershipMode {
+    if opts.ownership != OwnershipMode::Restorer
+        && opts.ownership_root_only
+        && !crate::helpers::running_as_root()
+    {
+        jlog!("not running as root, restored files stay owned by the current user");
+        return OwnershipMode::Restorer;
+    }
+    opts.ownership
+}
+
+/// chowns a freshly unpacked path per the ownership setting. only root can hand files to
+/// someone else, so a failure is a warning, the file itself is already restored
+#[cfg(unix)]
+fn apply_ownership(path: &Path, header: &tar::Header, mode: OwnershipMode, ids: &UnixIds) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (uid, gid) = match mode {
+        OwnershipMode::Restorer => return,
+        OwnershipMode::FromArchive => {
+            let uid = header
+                .username()
+                .ok()
+                .flatten()
+                .and_then(|n| ids.user_id(n))
+                .or_else(|| header.uid().ok().map(|u| u as u32));
+            let gid = header
+                .groupname()
+                .ok()
+                .flatten()
+                .and_then(|n| ids.group_id(n))
+                .or_else(|| header.gid().ok().map(|g| g as u32));
+            (uid, gid)
+        }
+        OwnershipMode::Fixed { uid, gid } => (Some(uid), Some(gid)),
+    };
+    if let Err(e) = std::os::unix::fs::lchown(path, uid, gid) {
+        dlog!("[WARN] couldn't set owner of {}: {e}", path.display());
+        return;
+    }
+    // chown drops setuid/setgid, they only mean the same thing again with the archived owner
+    let special = header.mode().map_or(0, |m| m & 0o7000);
+    if mode == OwnershipMode::FromArchive
+        && special != 0
+        && let Ok(meta) = fs::symlink_metadata(path)
+        && !meta.file_type().is_symlink()
+    {
+        let mode = meta.permissions().mode() & 0o777 | special;
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(mode)) {
+            dlog!("[WARN] couldn't set permissions of {}: {e}", path.display());
+        }
+    }
+}
+
 /// figures out where to actually write, or None if we're skipping it
 fn resolve_conflict(
     dest: &Path,
@@ -66,29 +202,370 @@ fn unique_path(dest: &Path) -> PathBuf {
     }
 }
 
+/// unpacks a file next to its target under a temp name and renames it over the target once it's
+/// fully written, so an interrupted restore can't leave a half-written file behind. dirs and links
+/// go straight through, there's nothing to half-write
+fn unpack_staged<R: Read>(entry: &mut tar::Entry<'_, R>, dest: &Path) -> std::io::Result<()> {
+    if !entry.header().entry_type().is_file() {
+        return entry.unpack(dest).map(|_| ());
+    }
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let staging = dest.with_file_name(format!(".{name}.konserve-part"));
+    let res = entry
+        .unpack(&staging)
+        .and_then(|_| fs::rename(&staging, dest));
+    if res.is_err() {
+        let _ = fs::remove_file(&staging);
+    }
+    res
+}
+
+/// puts an archived symlink back at `link`. absolute targets under the old home get moved to the
+/// current one like every other path. a copy of the target gets written instead when
+/// `materialize` is set or the link can't be made (windows without developer mode). false if
+/// there was nothing to copy, the target isn't there
+fn restore_symlink(
+    link: &Path,
+    target: &Path,
+    materialize: bool,
+    current_home: &Path,
+    verbose: bool,
+) -> bool {
+    let target = if target.is_absolute() || is_windows_path(&target.to_string_lossy()) {
+        adjust_path(target, current_home, verbose)
+    } else {
+        target.to_path_buf()
+    };
+    // whatever resolve_conflict let through gets replaced
+    if fs::symlink_metadata(link).is_ok_and(|m| !m.is_dir()) {
+        let _ = fs::remove_file(link);
+    }
+    if !materialize {
+        match make_symlink(&target, link) {
+            Ok(()) => return true,
+            Err(e) => dlog!(
+                "[WARN] can't create symlink {} → {}, copying instead: {e}",
+                link.display(),
+                target.display()
+            ),
+        }
+    }
+
+    let resolved = match link.parent() {
+        Some(dir) if target.is_relative() => dir.join(&target),
+        _ => target.clone(),
+    };
+    let copied = match fs::metadata(&resolved) {
+        Ok(m) if m.is_dir() => copy_tree(&resolved, link),
+        Ok(_) => fs::copy(&resolved, link).map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match copied {
+        Ok(()) => true,
+        Err(e) => {
+            elog!(
+                "ERROR: symlink {} points at {}, nothing copied: {e}",
+                link.display(),
+                resolved.display()
+            );
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    let resolved = match link.parent() {
+        Some(dir) if target.is_relative() => dir.join(target),
+        _ => target.to_path_buf(),
+    };
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// copies the folder `from` to `to` with everything in it, links inside get followed
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in walkdir::WalkDir::new(from).follow_links(true) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(from).unwrap_or(Path::new(""));
+        let dest = to.join(rel);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+        } else {
+            fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// puts a folder's archived mtime back, has to wait until everything inside it is written
+fn set_dir_mtime(dir: &Path, mtime: SystemTime) -> std::io::Result<()> {
+    let mut open = fs::OpenOptions::new();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // FILE_FLAG_BACKUP_SEMANTICS, windows won't open a folder without it
+        open.write(true).custom_flags(0x0200_0000);
+    }
+    #[cfg(not(windows))]
+    open.read(true);
+    open.open(dir)?.set_modified(mtime)
+}
+
 /// swap backslashes for / so paths compare consistently
 fn canon<S: AsRef<str>>(s: S) -> String {
     s.as_ref().replace('\\', "/")
 }
 
-/// restores from the tar, if selected is given only those paths get restored
+type ConflictChannel = Option<(mpsc::Sender<PathBuf>, mpsc::Receiver<ConflictAnswer>)>;
+
+/// what restoring one archive of a (maybe incremental/differential) chain got done
+struct ArchiveRun {
+    restored_settings: bool,
+    unmatched: Vec<String>,
+    /// the parent archive + original paths of selected files only it has
+    from_parent: Option<(ParentLink, Vec<String>)>,
+    /// symlinks that had to become copies but point at nothing
+    dangling_links: Vec<PathBuf>,
+}
+
+/// what the whole chain got done, on top of the restored count
+struct ChainRun {
+    restored_settings: bool,
+    unmatched: Vec<String>,
+    chain_warning: Option<String>,
+    dangling_links: Vec<PathBuf>,
+}
+
+/// restores from the tar, if selected is given only those archive paths (`uuid` or `uuid/rel`) get restored.
+/// incremental and differential archives pull their unchanged files out of the parent chain.
+/// a cancel stops before the next entry, files already written stay and the status says how many
 pub fn restore_backup(
-    zip_path: &PathBuf,
+    zip_path: &Path,
     selected: Option<Vec<String>>,
     status: Arc<Mutex<String>>,
     progress: &Progress,
-    verbose: bool,
-    mode: ConflictResolutionMode,
-    conflict_ch: Option<(mpsc::Sender<PathBuf>, mpsc::Receiver<ConflictAnswer>)>,
+    opts: &RestoreOptions,
+    conflict_ch: ConflictChannel,
 ) -> Result<(), String> {
     *status.lock().unwrap() = "Restoring backup…".into();
+    let started = Instant::now();
+    let job_log = JobLog::start("restore");
+    jlog!("restore from {}", zip_path.display());
 
-    let mut archive = Archive::new(File::open(zip_path).map_err(|e| {
+    let mut restored_count = 0;
+    let run = match restore_chain(
+        zip_path,
+        selected,
+        progress,
+        opts,
+        &conflict_ch,
+        &mut restored_count,
+    ) {
+        Ok(run) => run,
+        // a cancel mid-file surfaces as a read error, the flag says what it really was
+        Err(_) if progress.is_cancelled() => {
+            jlog!("restore cancelled after {restored_count} entries");
+            *status.lock().unwrap() = format!(
+                "❌ Restore cancelled after {}, the rest wasn't touched.",
+                format_count(restored_count, "item")
+            );
+            progress.done();
+            return Err(CANCELLED.into());
+        }
+        Err(e) => return Err(e),
+    };
+    let ChainRun {
+        restored_settings,
+        unmatched,
+        chain_warning,
+        dangling_links,
+    } = run;
+
+    jlog!("[done]   restored {restored_count} entries");
+    let mut msg = format!(
+        "✅ Restore complete, {} in {}.",
+        format_count(restored_count, "item"),
+        format_duration(started.elapsed())
+    );
+    if restored_settings {
+        msg.push_str("\nKonserve settings were restored, restart Konserve to load them.");
+    }
+    if let Some(warning) = chain_warning {
+        msg.push_str(&format!("\n{warning}"));
+    }
+    if !dangling_links.is_empty() {
+        msg.push_str(&format!(
+            "\n⚠ Nothing written for {}, what they point to is gone:",
+            format_count(dangling_links.len(), "symlink")
+        ));
+        for p in dangling_links.iter().take(5) {
+            msg.push_str(&format!("\n• {}", p.display()));
+        }
+        if dangling_links.len() > 5 {
+            msg.push_str("\n…see the job log for the rest");
+        }
+    }
+    if !unmatched.is_empty() {
+        msg.push_str(&format!(
+            "\n⚠ {} matched nothing in the archive:",
+            format_count(unmatched.len(), "selected path")
+        ));
+        for s in unmatched.iter().take(5) {
+            msg.push_str(&format!("\n• {s}"));
+        }
+        if unmatched.len() > 5 {
+            msg.push_str("\n…see the job log for the rest");
+        }
+        if let Some(log) = &job_log.path {
+            msg.push_str(&format!("\nDetails: {}", log.display()));
+        }
+    }
+    *status.lock().unwrap() = msg;
+    progress.done();
+    Ok(())
+}
+
+/// restores the archive, then whatever its parent chain has for the selection.
+/// `restored_count` keeps counting across archives so a cancel can still report it
+fn restore_chain(
+    zip_path: &Path,
+    selected: Option<Vec<String>>,
+    progress: &Progress,
+    opts: &RestoreOptions,
+    conflict_ch: &ConflictChannel,
+    restored_count: &mut usize,
+) -> Result<ChainRun, String> {
+    let mut run = restore_archive(
+        zip_path,
+        selected,
+        progress,
+        opts,
+        conflict_ch,
+        restored_count,
+    )?;
+    let unmatched = std::mem::take(&mut run.unmatched);
+    let mut restored_settings = run.restored_settings;
+    let mut dangling_links = std::mem::take(&mut run.dangling_links);
+
+    let mut chain_warning = None;
+    let mut child = zip_path.to_path_buf();
+    let mut hops = 0;
+    while let Some((link, wanted)) = run.from_parent.take() {
+        let parent = child.with_file_name(&link.name);
+        let name = &link.name;
+        let label = if link.differential {
+            "Baseline"
+        } else {
+            "Base backup"
+        };
+        hops += 1;
+        if !parent.is_file() || hops > MAX_CHAIN {
+            elog!(
+                "ERROR: base backup {} is missing or the chain loops, {} unchanged files not restored",
+                parent.display(),
+                wanted.len()
+            );
+            chain_warning = Some(format!(
+                "⚠ {label} {name} is missing, {} weren't restored.",
+                format_count(wanted.len(), "unchanged file")
+            ));
+            break;
+        }
+        // a chain normally shares one passphrase
+        crypt::inherit_passphrase(&child, &parent);
+
+        // same name but a different archive, e.g. a fixed backup name that got overwritten since
+        if let Some(id) = &link.id
+            && read_manifest(&parent, opts.verbose)?.id.as_ref() != Some(id)
+        {
+            elog!(
+                "ERROR: base backup {} isn't the archive {} was made against (id {id})",
+                parent.display(),
+                child.display()
+            );
+            chain_warning = Some(format!(
+                "⚠ {label} {name} was replaced by a different backup, {} weren't restored.",
+                format_count(wanted.len(), "unchanged file")
+            ));
+            break;
+        }
+
+        let (entries, parent_map) = parse_fingerprint(&parent, opts.verbose)?;
+        let wanted: HashSet<String> = wanted.into_iter().collect();
+        let mut found = 0;
+        let keys: Vec<String> = entries
+            .into_iter()
+            .filter(|e| entry_human_path(e, &parent_map).is_some_and(|h| wanted.contains(&h)))
+            .inspect(|_| found += 1)
+            // standalone files get selected by bare uuid, same as the restore tree does it
+            .map(|e| match e.split_once('/') {
+                Some(_) => e,
+                None => e.split('.').next().unwrap_or_default().to_string(),
+            })
+            .collect();
+        if found < wanted.len() {
+            elog!(
+                "ERROR: {} unchanged files not found in base backup {}",
+                wanted.len() - found,
+                parent.display()
+            );
+            chain_warning = Some(format!(
+                "⚠ {} weren't found in base backup {name}.",
+                format_count(wanted.len() - found, "unchanged file")
+            ));
+        }
+        jlog!("chaining to {} for {} files", parent.display(), keys.len());
+
+        run = restore_archive(
+            &parent,
+            Some(keys),
+            progress,
+            opts,
+            conflict_ch,
+            restored_count,
+        )?;
+        restored_settings |= run.restored_settings;
+        dangling_links.append(&mut run.dangling_links);
+        child = parent;
+    }
+
+    Ok(ChainRun {
+        restored_settings,
+        unmatched,
+        chain_warning,
+        dangling_links,
+    })
+}
+
+/// unpacks the selected entries of one archive, doesn't follow its parent. adds what it wrote
+/// to `restored_count`
+fn restore_archive(
+    zip_path: &Path,
+    selected: Option<Vec<String>>,
+    progress: &Progress,
+    opts: &RestoreOptions,
+    conflict_ch: &ConflictChannel,
+    restored_count: &mut usize,
+) -> Result<ArchiveRun, String> {
+    let verbose = opts.verbose;
+    let mode = opts.mode;
+
+    let mut archive = Archive::new(open_archive_reader(zip_path).map_err(|e| {
         let msg = format!("ERROR: cannot open archive {}: {e}", zip_path.display());
         elog!("{msg}");
         msg
     })?);
     let mut path_map: HashMap<String, PathBuf> = HashMap::new();
+    let mut parent = None;
     let mut valid_fingerprint = false;
 
     for entry_res in archive.entries().map_err(|e| e.to_string())? {
@@ -100,15 +577,16 @@ pub fn restore_backup(
             let mut txt = String::new();
             entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
 
-            // bail if the fingerprint doesn't match this build
-            if txt.contains(get_fingered()) {
+            // bail if the fingerprint doesn't match this build, or the format is too new for it
+            let manifest = parse_manifest(&txt, false);
+            if let Err(e) = manifest.check_version() {
+                elog!("ERROR: restore aborted, {}: {e}", zip_path.display());
+                return Err(e);
+            }
+            if manifest.valid {
                 valid_fingerprint = true;
-
-                for line in txt.lines().filter(|l| l.contains(": ")) {
-                    if let Some((uuid, p)) = line.split_once(": ") {
-                        path_map.insert(uuid.to_string(), PathBuf::from(p.trim()));
-                    }
-                }
+                path_map = manifest.paths;
+                parent = manifest.parent;
             }
             break;
         }
@@ -128,81 +606,141 @@ pub fn restore_backup(
 
     let mut to_extract: HashSet<String> = HashSet::new();
 
-    if let Some(human_sel_raw) = &selected {
-        let human_sel: HashSet<String> = human_sel_raw.iter().map(canon).collect();
-
-        for (uuid, orig) in &path_map {
-            let parent_c = canon(orig.parent().unwrap_or(orig).display().to_string());
-            let item_name = orig.file_name().unwrap_or_default().to_string_lossy();
-            let base = format!("{parent_c}/{item_name}");
-            let base_slash = format!("{base}/");
+    // uuid -> loose paths inside that root picked by the fallback matcher, "" = the whole root
+    let mut loose_extract: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut unmatched: Vec<String> = Vec::new();
 
-            if human_sel.contains(&base) {
-                to_extract.insert(uuid.clone());
-
-                if let Some(ext) = orig.extension().and_then(|e| e.to_str()) {
-                    to_extract.insert(format!("{uuid}.{ext}"));
-                }
+    // selection comes straight from the restore tree as archive paths: `uuid` for a
+    // standalone file root, `uuid/rel/path` for stuff inside a folder root
+    if let Some(sel) = &selected {
+        for s in sel {
+            let s = canon(s);
+            let root = s.split('/').next().unwrap_or_default().to_string();
+            if root == KONSERVE_CONFIG_ENTRY {
+                to_extract.insert(s);
+                continue;
             }
-
-            for h in &human_sel {
-                if let Some(rest) = h.strip_prefix(&base_slash) {
-                    to_extract.insert(format!("{uuid}/{rest}"));
+            let Some(orig) = path_map.get(&root) else {
+                // not uuid-rooted, so it's a human path from an older selection. match it
+                // against the original paths, ignoring separator style and windows casing
+                let mut matched = false;
+                for (uuid, orig) in &path_map {
+                    let orig = orig.display().to_string();
+                    let fold = is_windows_path(&orig);
+                    let base = loose(&orig, fold);
+                    let wanted = loose(&s, fold);
+                    let rest = if wanted == base {
+                        Some(String::new())
+                    } else {
+                        wanted.strip_prefix(&format!("{base}/")).map(str::to_string)
+                    };
+                    if let Some(rest) = rest {
+                        loose_extract.entry(uuid.clone()).or_default().insert(rest);
+                        matched = true;
+                    }
                 }
+                if !matched {
+                    dlog!("[select]  {s}  (matches nothing in the archive)");
+                    unmatched.push(s);
+                }
+                continue;
+            };
+            // standalone files are stored as uuid.ext
+            if s == root
+                && let Some(ext) = orig.extension().and_then(|e| e.to_str())
+            {
+                to_extract.insert(format!("{root}.{ext}"));
             }
+            to_extract.insert(s);
         }
     }
 
-    // counting as we go so we don't have to walk the archive twice
-    let mut total_files: u32 = 1;
-    let mut done: u32 = 0;
+    // fallback check for entries picked by human path, same loose rules as above
+    let loosely_selected = |path_in_tar: &str| {
+        let (uuid, rest) = match path_in_tar.split_once('/') {
+            Some((uuid, rest)) => (uuid, rest),
+            None => (path_in_tar.split('.').next().unwrap_or_default(), ""),
+        };
+        let (Some(wanted), Some(orig)) = (loose_extract.get(uuid), path_map.get(uuid)) else {
+            return false;
+        };
+        let rest = loose(rest, is_windows_path(&orig.display().to_string()));
+        wanted.contains("") || path_prefixes(&rest).any(|p| wanted.contains(p))
+    };
 
     if verbose {
         dlog!("[select]  to_extract = {to_extract:?}");
     }
 
     let current_home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("C:\\"));
-    let mut archive = Archive::new(File::open(zip_path).map_err(|e| {
+    #[cfg(unix)]
+    let ids = UnixIds::load();
+    #[cfg(unix)]
+    let ownership = effective_ownership(opts);
+    let inner = open_archive_reader(zip_path).map_err(|e| {
         let msg = format!(
             "ERROR: cannot reopen archive for extraction {}: {e}",
             zip_path.display()
         );
         elog!("{msg}");
         msg
-    })?);
+    })?;
+    // the archive's size stands in for the total, no second pass over it just to add up entries.
+    // skipped entries still have to be read past so they count as progress too
+    progress.add_total_bytes(fs::metadata(zip_path).map_or(0, |m| m.len()));
+    let mut archive = Archive::new(ProgressRead { inner, progress });
+    archive.set_preserve_mtime(!opts.reset_mtimes);
+    // setuid/setgid/sticky only with the archived owners, for anyone else they'd mean something
+    // new. apply_ownership puts back what chown clears
+    #[cfg(unix)]
+    archive.set_preserve_permissions(ownership == OwnershipMode::FromArchive);
 
     if verbose {
         dlog!("[extract] scanning archive…");
     }
-    let mut restored_count = 0;
+    let mut restored_settings = false;
+    let mut unchanged = Vec::new();
+    // (where, target), made once everything else is out so copies find their targets
+    let mut symlinks: Vec<(PathBuf, PathBuf)> = Vec::new();
+    // folders this restore created or found, see create_dirs
+    let mut made_dirs: HashSet<PathBuf> = HashSet::new();
+    // folders and their archived mtime, set at the end since writing into them bumps it
+    let mut dir_times: Vec<(PathBuf, SystemTime)> = Vec::new();
+
+    // if a selection was given, only exact matches or stuff inside a selected folder (uuid/ prefix).
+    // looks up the entry's own folders, the selection can be as big as the archive
+    let is_selected = |path_in_tar: &str| {
+        selected.is_none()
+            || to_extract.contains(path_in_tar)
+            || path_prefixes(path_in_tar.trim_end_matches('/')).any(|p| to_extract.contains(p))
+            || loosely_selected(path_in_tar)
+    };
 
     for entry_res in archive.entries().map_err(|e| e.to_string())? {
+        if progress.is_cancelled() {
+            return Err(CANCELLED.into());
+        }
         let mut entry = entry_res.map_err(|e| e.to_string())?;
         let tar_path_ref = entry.path().map_err(|e| e.to_string())?;
         let path_in_tar = tar_path_ref.to_string_lossy().into_owned();
 
-        if path_in_tar == "fingerprint.txt" {
+        if path_in_tar == "fingerprint.txt" || path_in_tar == CHECKSUMS_ENTRY {
+            continue;
+        }
+        if path_in_tar == UNCHANGED_ENTRY {
+            let mut txt = String::new();
+            entry.read_to_string(&mut txt).map_err(|e| e.to_string())?;
+            unchanged = parse_unchanged(&txt);
             continue;
         }
 
-        // if a selection was given, skip anything that's not an exact match or
-        // inside a selected folder (uuid/ prefix)
-        if selected.is_some()
-            && !to_extract.contains(&path_in_tar)
-            && !to_extract.iter().any(|s| {
-                path_in_tar.len() > s.len()
-                    && path_in_tar.as_bytes()[s.len()] == b'/'
-                    && path_in_tar.starts_with(s.as_str())
-            })
-        {
+        if !is_selected(&path_in_tar) {
             if verbose {
                 dlog!("[skip]    {path_in_tar}  (not selected)");
             }
             continue;
         }
 
-        total_files += 1;
-
         let tar_path = Path::new(&path_in_tar);
         let root_component = match tar_path.components().next() {
             Some(c) => c.as_os_str().to_string_lossy().into_owned(),
@@ -214,27 +752,70 @@ pub fn restore_backup(
             }
         };
 
+        // konserve's own settings, they go back to wherever this build keeps them
+        if root_component == KONSERVE_CONFIG_ENTRY {
+            let name = path_in_tar[KONSERVE_CONFIG_ENTRY.len()..].trim_matches('/');
+            let Some(unpack_to) = konserve_config_target(name) else {
+                if verbose {
+                    dlog!("[skip]    {path_in_tar}  (unknown settings file)");
+                }
+                continue;
+            };
+            jlog!("[write] settings {path_in_tar}  →  {}", unpack_to.display());
+
+            if let Some(final_path) = resolve_conflict(&unpack_to, mode, conflict_ch) {
+                progress.working_on(final_path.display().to_string());
+                if let Some(dir) = final_path.parent() {
+                    create_dirs(dir, opts.dir_permissions, &mut made_dirs).map_err(|e| {
+                        let msg = format!("ERROR: failed to create dir {}: {e}", dir.display());
+                        elog!("{msg}");
+                        msg
+                    })?;
+                }
+                unpack_staged(&mut entry, &final_path).map_err(|e| {
+                    let msg = format!(
+                        "ERROR: failed to unpack {} → {}: {e}",
+                        path_in_tar,
+                        final_path.display()
+                    );
+                    elog!("{msg}");
+                    msg
+                })?;
+                *restored_count += 1;
+                restored_settings = true;
+            } else {
+                jlog!("[skip] conflict: {}", unpack_to.display());
+            }
+        }
         // uuid prefix = folder root
-        if let Some(orig_base) = path_map.get(&root_component) {
+        else if let Some(orig_base) = path_map.get(&root_component) {
             let adjusted_base = adjust_path(orig_base, &current_home, verbose);
             let rel = tar_path
                 .strip_prefix(Path::new(&root_component))
                 .unwrap_or_else(|_| Path::new(""));
 
             let unpack_to = adjusted_base.join(rel);
-            if verbose {
-                dlog!("[write] dir {path_in_tar}  →  {}", unpack_to.display());
-            }
+            jlog!("[write] dir {path_in_tar}  →  {}", unpack_to.display());
 
-            if let Some(final_path) = resolve_conflict(&unpack_to, mode, &conflict_ch) {
+            if let Some(final_path) = resolve_conflict(&unpack_to, mode, conflict_ch) {
+                progress.working_on(final_path.display().to_string());
                 if let Some(dir) = final_path.parent() {
-                    fs::create_dir_all(dir).map_err(|e| {
+                    create_dirs(dir, opts.dir_permissions, &mut made_dirs).map_err(|e| {
                         let msg = format!("ERROR: failed to create dir {}: {e}", dir.display());
                         elog!("{msg}");
                         msg
                     })?;
                 }
-                entry.unpack(&final_path).map_err(|e| {
+                if entry.header().entry_type().is_symlink() {
+                    if let Ok(Some(target)) = entry.link_name() {
+                        symlinks.push((final_path, target.into_owned()));
+                    }
+                    continue;
+                }
+                // archived folders count as created by the restore too, unless they were already there
+                #[cfg(unix)]
+                let new_dir = entry.header().entry_type().is_dir() && !final_path.exists();
+                unpack_staged(&mut entry, &final_path).map_err(|e| {
                     let msg = format!(
                         "ERROR: failed to unpack {} → {}: {e}",
                         path_in_tar,
@@ -243,32 +824,42 @@ pub fn restore_backup(
                     elog!("{msg}");
                     msg
                 })?;
-                restored_count += 1;
-            } else {
-                if verbose {
-                    dlog!("[skip] conflict: {}", unpack_to.display());
+                #[cfg(unix)]
+                if new_dir {
+                    apply_dir_permissions(&final_path, opts.dir_permissions);
                 }
+                if entry.header().entry_type().is_dir()
+                    && !opts.reset_mtimes
+                    && let Ok(secs) = entry.header().mtime()
+                {
+                    dir_times.push((
+                        final_path.clone(),
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                    ));
+                }
+                #[cfg(unix)]
+                apply_ownership(&final_path, entry.header(), ownership, &ids);
+                *restored_count += 1;
+            } else {
+                jlog!("[skip] conflict: {}", unpack_to.display());
             }
-            done += 1;
-            progress.set((done * 100) / total_files);
         }
         // uuid.ext = standalone file
         else if let Some((uuid_part, _ext)) = root_component.split_once('.') {
             if let Some(orig_file) = path_map.get(uuid_part) {
                 let unpack_to = adjust_path(orig_file, &current_home, verbose);
-                if verbose {
-                    dlog!("[write] file {path_in_tar}  →  {}", unpack_to.display());
-                }
+                jlog!("[write] file {path_in_tar}  →  {}", unpack_to.display());
 
-                if let Some(final_path) = resolve_conflict(&unpack_to, mode, &conflict_ch) {
+                if let Some(final_path) = resolve_conflict(&unpack_to, mode, conflict_ch) {
+                    progress.working_on(final_path.display().to_string());
                     if let Some(dir) = final_path.parent() {
-                        fs::create_dir_all(dir).map_err(|e| {
+                        create_dirs(dir, opts.dir_permissions, &mut made_dirs).map_err(|e| {
                             let msg = format!("ERROR: failed to create dir {}: {e}", dir.display());
                             elog!("{msg}");
                             msg
                         })?;
                     }
-                    entry.unpack(&final_path).map_err(|e| {
+                    unpack_staged(&mut entry, &final_path).map_err(|e| {
                         let msg = format!(
                             "ERROR: failed to unpack {} → {}: {e}",
                             path_in_tar,
@@ -277,14 +868,12 @@ pub fn restore_backup(
                         elog!("{msg}");
                         msg
                     })?;
-                    restored_count += 1;
+                    #[cfg(unix)]
+                    apply_ownership(&final_path, entry.header(), ownership, &ids);
+                    *restored_count += 1;
                 } else {
-                    if verbose {
-                        dlog!("[skip] conflict: {}", unpack_to.display());
-                    }
+                    jlog!("[skip] conflict: {}", unpack_to.display());
                 }
-                done += 1;
-                progress.set((done * 100) / total_files);
             } else {
                 if verbose {
                     dlog!("[skip]    {path_in_tar}  (uuid not in map)");
@@ -297,10 +886,245 @@ pub fn restore_backup(
         }
     }
 
-    if verbose {
-        dlog!("[done]   restored {restored_count} entries");
+    let mut dangling_links = Vec::new();
+    for (link, target) in symlinks {
+        jlog!("[link]  {}  →  {}", link.display(), target.display());
+        if restore_symlink(
+            &link,
+            &target,
+            opts.materialize_symlinks,
+            &current_home,
+            verbose,
+        ) {
+            *restored_count += 1;
+        } else {
+            dangling_links.push(link);
+        }
     }
-    *status.lock().unwrap() = "✅ Restore complete.".into();
-    progress.done();
-    Ok(())
+
+    for (dir, mtime) in dir_times {
+        if let Err(e) = set_dir_mtime(&dir, mtime) {
+            dlog!(
+                "[WARN] couldn't set modified time of {}: {e}",
+                dir.display()
+            );
+        }
+    }
+
+    // selected files that didn't change since the parent are only in the parent
+    let from_parent = parent.and_then(|link| {
+        let wanted: Vec<String> = unchanged
+            .iter()
+            .filter(|key| is_selected(key))
+            .filter_map(|key| entry_human_path(key, &path_map))
+            .collect();
+        (!wanted.is_empty()).then_some((link, wanted))
+    });
+
+    Ok(ArchiveRun {
+        restored_settings,
+        unmatched,
+        from_parent,
+        dangling_links,
+    })
+}
+
+/// which shell an exported restore script is written for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScriptKind {
+    PowerShell,
+    Sh,
+}
+
+impl ScriptKind {
+    /// the one that runs natively on this os
+    pub fn native() -> Self {
+        if cfg!(target_os = "windows") {
+            ScriptKind::PowerShell
+        } else {
+            ScriptKind::Sh
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScriptKind::PowerShell => "ps1",
+            ScriptKind::Sh => "sh",
+        }
+    }
+
+    fn quote(self, s: &str) -> String {
+        match self {
+            ScriptKind::PowerShell => format!("'{}'", s.replace('\'', "''")),
+            ScriptKind::Sh => format!("'{}'", s.replace('\'', r"'\''")),
+        }
+    }
+}
+
+/// why restore_script can't replay this archive with plain tar, None if it can
+pub fn script_blocker(zip_path: &Path) -> Option<&'static str> {
+    if crypt::is_encrypted(zip_path) {
+        return Some("Encrypted archives only open in Konserve, tar can't read them");
+    }
+    // files that didn't change are only in the parent chain, tar would quietly leave them all out
+    if read_manifest(zip_path, false).is_ok_and(|m| m.parent.is_some()) {
+        return Some(
+            "Incremental and differential archives need their earlier backups, only Konserve puts those together",
+        );
+    }
+    None
+}
+
+/// turns a restore-tree selection (same `uuid` / `uuid/rel` paths restore_backup takes) into a
+/// script that replays it with plain `tar`, no konserve needed on the other end.
+/// everything gets overwritten, the script doesn't do conflict resolution
+pub fn restore_script(
+    zip_path: &Path,
+    selected: &[String],
+    kind: ScriptKind,
+) -> Result<String, String> {
+    if let Some(why) = script_blocker(zip_path) {
+        return Err(why.into());
+    }
+    let (entries, path_map) = parse_fingerprint(zip_path, false)?;
+
+    // tar entries to pull out, plus where each root ends up
+    let mut members: Vec<String> = Vec::new();
+    let mut dir_roots: Vec<(String, PathBuf)> = Vec::new();
+    let mut file_roots: Vec<(String, PathBuf)> = Vec::new();
+
+    for s in selected {
+        let s = canon(s);
+        let root = s.split('/').next().unwrap_or_default().to_string();
+        let Some(orig) = path_map.get(&root) else {
+            continue;
+        };
+        if s == root {
+            // standalone file, stored as uuid.ext (or bare uuid without an extension)
+            let Some(entry) = entries
+                .iter()
+                .find(|e| e.split('.').next() == Some(root.as_str()) && !e.contains('/'))
+            else {
+                continue;
+            };
+            members.push(entry.clone());
+            file_roots.push((entry.clone(), orig.clone()));
+        } else {
+            members.push(s);
+            if !dir_roots.iter().any(|(u, _)| *u == root) {
+                dir_roots.push((root, orig.clone()));
+            }
+        }
+    }
+
+    if members.is_empty() {
+        return Err("Nothing selected that exists in this archive.".into());
+    }
+
+    // a folder member already pulls in everything under it, and gnu tar errors on
+    // names it has already matched
+    members.sort();
+    members.dedup();
+    let picked: HashSet<&str> = members.iter().map(String::as_str).collect();
+    let members: Vec<String> = members
+        .iter()
+        .filter(|m| !path_prefixes(m).any(|p| p.len() < m.len() && picked.contains(p)))
+        .cloned()
+        .collect();
+
+    let q = |s: &str| kind.quote(s);
+    let archive = zip_path.display().to_string();
+    let generated = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    let mut out = String::new();
+
+    match kind {
+        ScriptKind::PowerShell => {
+            out.push_str(&format!(
+                "# konserve restore script, generated {generated}\n\
+                 # replays a selective restore with tar.exe, existing files get overwritten\n\
+                 # usage: .\\restore.ps1 [path\\to\\archive.tar]\n\
+                 $ErrorActionPreference = 'Stop'\n\
+                 $Archive = if ($args.Count -gt 0) {{ $args[0] }} else {{ {} }}\n\
+                 $Tmp = Join-Path ([IO.Path]::GetTempPath()) ([guid]::NewGuid())\n\
+                 New-Item -ItemType Directory -Force -Path $Tmp | Out-Null\n\
+                 $List = Join-Path $Tmp 'konserve-entries.txt'\n\
+                 $Entries = @(\n",
+                q(&archive)
+            ));
+            for m in &members {
+                out.push_str(&format!("    {}\n", q(m)));
+            }
+            out.push_str(
+                ")\n\
+                 [IO.File]::WriteAllLines($List, [string[]]$Entries)\n\
+                 tar -xf $Archive -C $Tmp -T $List\n",
+            );
+            // C:\Users\<whoever> -> whoever runs the script, same idea as adjust_path
+            let dest = |p: &PathBuf| {
+                let shown = p.display().to_string();
+                let parts: Vec<&str> = shown.splitn(4, '\\').collect();
+                if parts.len() == 4 && shown.to_lowercase().starts_with("c:\\users\\") {
+                    format!("(Join-Path $env:USERPROFILE {})", q(parts[3]))
+                } else {
+                    q(&shown)
+                }
+            };
+            for (uuid, orig) in &dir_roots {
+                out.push_str(&format!(
+                    "New-Item -ItemType Directory -Force -Path {0} | Out-Null\n\
+                     Copy-Item -Path (Join-Path $Tmp {1}) -Destination {0} -Recurse -Force\n",
+                    dest(orig),
+                    q(&format!("{uuid}\\*"))
+                ));
+            }
+            for (entry, orig) in &file_roots {
+                out.push_str(&format!(
+                    "New-Item -ItemType Directory -Force -Path (Split-Path -Parent {0}) | Out-Null\n\
+                     Copy-Item -Path (Join-Path $Tmp {1}) -Destination {0} -Force\n",
+                    dest(orig),
+                    q(entry)
+                ));
+            }
+            out.push_str("Remove-Item -Recurse -Force $Tmp\n");
+        }
+        ScriptKind::Sh => {
+            out.push_str(&format!(
+                "#!/bin/sh\n\
+                 # konserve restore script, generated {generated}\n\
+                 # replays a selective restore with tar, existing files get overwritten\n\
+                 # usage: sh restore.sh [path/to/archive.tar]\n\
+                 set -e\n\
+                 ARCHIVE={}\n\
+                 if [ $# -gt 0 ]; then ARCHIVE=\"$1\"; fi\n\
+                 TMP=$(mktemp -d)\n\
+                 cat > \"$TMP/konserve-entries.txt\" <<'KONSERVE_EOF'\n",
+                q(&archive)
+            ));
+            for m in &members {
+                out.push_str(m);
+                out.push('\n');
+            }
+            out.push_str(
+                "KONSERVE_EOF\n\
+                 tar -xf \"$ARCHIVE\" -C \"$TMP\" -T \"$TMP/konserve-entries.txt\"\n",
+            );
+            for (uuid, orig) in &dir_roots {
+                let dest = q(&orig.display().to_string());
+                out.push_str(&format!(
+                    "mkdir -p {dest}\ncp -a \"$TMP/\"{}/. {dest}/\n",
+                    q(uuid)
+                ));
+            }
+            for (entry, orig) in &file_roots {
+                let dest = q(&orig.display().to_string());
+                out.push_str(&format!(
+                    "mkdir -p \"$(dirname {dest})\"\ncp -a \"$TMP/\"{} {dest}\n",
+                    q(entry)
+                ));
+            }
+            out.push_str("rm -rf \"$TMP\"\n");
+        }
+    }
+
+    Ok(out)
 }
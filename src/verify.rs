@@ -0,0 +1,213 @@
+//! re-reads a finished archive and checks every file against the checksums recorded at backup time
+use crate::crypt::open_archive_reader;
+use crate::helpers::{
+    CHECKSUMS_ENTRY, JobLog, Progress, ProgressRead, UNCHANGED_ENTRY, entry_human_path,
+    format_count, format_duration, parse_checksums, parse_manifest, parse_unchanged, to_hex,
+};
+use crate::{elog, jlog};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tar::Archive;
+
+/// what a verify run found, paths are the original ones where the manifest knows them
+pub struct VerifyReport {
+    pub archive: PathBuf,
+    /// files read back with a matching checksum
+    pub ok: usize,
+    /// read back fine but the content isn't what got packed
+    pub corrupted: Vec<String>,
+    /// have a checksum but no entry in the archive
+    pub missing: Vec<String>,
+    /// archive is from before checksums were recorded, only checked that every entry reads
+    pub no_checksums: bool,
+    /// incremental/differential: files only the parent chain has, not checked here
+    pub in_parent: usize,
+    /// files packed with zero bytes. tar doesn't compress, so stored size is the original size
+    /// and these were empty (or read as empty) when backed up: the "file is there but the
+    /// content isn't" case
+    pub empty_files: Vec<String>,
+    pub log: Option<PathBuf>,
+    pub took: Duration,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupted.is_empty() && self.missing.is_empty()
+    }
+
+    /// status-bar text for the gui
+    pub fn summary(&self) -> String {
+        let name = self
+            .archive
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let took = format_duration(self.took);
+        let mut msg = if !self.is_ok() {
+            format!("❌ {name} failed verification ({took}):")
+        } else if self.no_checksums {
+            format!(
+                "⚠ {name} reads fine, {} checked ({took}).\nIt's older than checksums though, so file contents couldn't be compared.",
+                format_count(self.ok, "file")
+            )
+        } else {
+            format!(
+                "✅ {name} verified, {} match their checksums ({took}).",
+                format_count(self.ok, "file")
+            )
+        };
+        let problems = self
+            .corrupted
+            .iter()
+            .map(|p| (p, "corrupted"))
+            .chain(self.missing.iter().map(|p| (p, "missing")));
+        for (path, what) in problems.clone().take(5) {
+            msg.push_str(&format!("\n• {path} ({what})"));
+        }
+        if problems.count() > 5
+            && let Some(log) = &self.log
+        {
+            msg.push_str(&format!("\n…the rest are in {}", log.display()));
+        }
+        if !self.empty_files.is_empty() {
+            msg.push_str(&format!(
+                "\n⚠ {} packed with zero bytes, check the originals really were empty:",
+                format_count(self.empty_files.len(), "file")
+            ));
+            for path in self.empty_files.iter().take(5) {
+                msg.push_str(&format!("\n• {path}"));
+            }
+            if self.empty_files.len() > 5
+                && let Some(log) = &self.log
+            {
+                msg.push_str(&format!("\n…the rest are in {}", log.display()));
+            }
+        }
+        if self.in_parent > 0 {
+            msg.push_str(&format!(
+                "\n{} live in earlier backups of the chain, verify those too.",
+                format_count(self.in_parent, "unchanged file")
+            ));
+        }
+        msg
+    }
+}
+
+/// reads the whole archive back, hashing every file. an archive that can't be read to the end
+/// (truncated, wrong passphrase, bad encryption chunk) is an Err
+pub fn verify_archive(
+    zip_path: &Path,
+    progress: &Progress,
+    verbose: bool,
+) -> Result<VerifyReport, String> {
+    let started = Instant::now();
+    let job_log = JobLog::start("verify");
+    jlog!("verify {}", zip_path.display());
+
+    let inner = open_archive_reader(zip_path).map_err(|e| {
+        elog!("ERROR: cannot open {} to verify: {e}", zip_path.display());
+        e
+    })?;
+    progress.add_total_bytes(fs::metadata(zip_path).map_or(0, |m| m.len()));
+    let mut archive = Archive::new(ProgressRead { inner, progress });
+
+    let mut path_map = HashMap::new();
+    let mut actual: HashMap<String, String> = HashMap::new();
+    let mut recorded = None;
+    let mut in_parent = 0;
+    let mut empty = Vec::new();
+    let damaged = |read: usize, e: io::Error| {
+        let msg = format!(
+            "the archive is damaged after {}: {e}",
+            format_count(read, "file")
+        );
+        elog!("ERROR: verify {}: {msg}", zip_path.display());
+        msg
+    };
+
+    for entry in archive.entries().map_err(|e| damaged(0, e))? {
+        let mut entry = entry.map_err(|e| damaged(actual.len(), e))?;
+        let name = entry
+            .path()
+            .map_err(|e| damaged(actual.len(), e))?
+            .to_string_lossy()
+            .into_owned();
+        if name == "fingerprint.txt" || name == CHECKSUMS_ENTRY || name == UNCHANGED_ENTRY {
+            let mut txt = String::new();
+            entry
+                .read_to_string(&mut txt)
+                .map_err(|e| damaged(actual.len(), e))?;
+            match name.as_str() {
+                "fingerprint.txt" => path_map = parse_manifest(&txt, verbose).paths,
+                CHECKSUMS_ENTRY => recorded = Some(parse_checksums(&txt)),
+                _ => in_parent = parse_unchanged(&txt).len(),
+            }
+            continue;
+        }
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        progress.working_on(name.clone());
+        if entry.size() == 0 {
+            empty.push(name.clone());
+        }
+        let mut hasher = Sha256::new();
+        io::copy(&mut entry, &mut hasher).map_err(|e| damaged(actual.len(), e))?;
+        actual.insert(name, to_hex(&hasher.finalize()));
+    }
+
+    let human = |key: &str| entry_human_path(key, &path_map).unwrap_or_else(|| key.to_string());
+    let mut report = VerifyReport {
+        archive: zip_path.to_path_buf(),
+        ok: 0,
+        corrupted: Vec::new(),
+        missing: Vec::new(),
+        no_checksums: recorded.is_none(),
+        in_parent,
+        empty_files: empty.iter().map(|key| human(key)).collect(),
+        log: job_log.path.clone(),
+        took: Duration::ZERO,
+    };
+    match recorded {
+        Some(recorded) => {
+            for (key, digest) in &recorded {
+                match actual.get(key) {
+                    Some(d) if d == digest => report.ok += 1,
+                    Some(_) => {
+                        jlog!("[corrupted] {}", human(key));
+                        report.corrupted.push(human(key));
+                    }
+                    None => {
+                        jlog!("[missing]   {}", human(key));
+                        report.missing.push(human(key));
+                    }
+                }
+            }
+        }
+        // nothing to compare against, reading every entry to the end is all there is
+        None => report.ok = actual.len(),
+    }
+    report.corrupted.sort();
+    report.missing.sort();
+    report.empty_files.sort();
+    for path in &report.empty_files {
+        jlog!("[empty]     {path}");
+    }
+    report.took = started.elapsed();
+
+    jlog!(
+        "verify finished, {} ok, {} corrupted, {} missing, {} empty",
+        report.ok,
+        report.corrupted.len(),
+        report.missing.len(),
+        report.empty_files.len()
+    );
+    progress.done();
+    Ok(report)
+}
@@ -0,0 +1,168 @@
+//! volume shadow copies on windows: a frozen view of a volume, so files other programs keep open
+//! (browser profiles, outlook .pst, game saves while the game runs) can still be read, all as of
+//! the same moment. goes through powershell + the Win32_ShadowCopy wmi class, needs admin
+use crate::{dlog, elog};
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// one snapshot, `device` is where its files can be read (`\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy3`)
+struct ShadowCopy {
+    id: String,
+    /// drive letter it's a snapshot of, uppercase
+    drive: u8,
+    device: String,
+}
+
+/// the snapshots taken for one backup run, they get deleted again when this is dropped
+#[derive(Default)]
+pub struct ShadowSet {
+    copies: Vec<ShadowCopy>,
+}
+
+impl ShadowSet {
+    /// snapshots every drive one of `roots` lives on. drives that can't be snapshotted come back
+    /// as ("C:\", why), their roots just get read live
+    pub fn create(roots: &[PathBuf]) -> (Self, Vec<(String, String)>) {
+        let mut drives: Vec<u8> = roots.iter().filter_map(|r| drive_of(r)).collect();
+        drives.sort();
+        drives.dedup();
+
+        let mut set = Self::default();
+        let mut failed = Vec::new();
+        for drive in drives {
+            let volume = format!("{}:\\", drive as char);
+            match create_shadow(&volume) {
+                Ok((id, device)) => {
+                    dlog!("[INFO] shadow copy of {volume} at {device}");
+                    set.copies.push(ShadowCopy { id, drive, device });
+                }
+                Err(e) => {
+                    elog!("ERROR: no shadow copy of {volume}, reading it live: {e}");
+                    failed.push((volume, e));
+                }
+            }
+        }
+        (set, failed)
+    }
+
+    /// where `path` can be read inside its drive's snapshot, None if that drive doesn't have one
+    pub fn map(&self, path: &Path) -> Option<PathBuf> {
+        let drive = drive_of(path)?;
+        let copy = self.copies.iter().find(|c| c.drive == drive)?;
+        // everything after the `C:\`
+        let rest: PathBuf = path
+            .components()
+            .skip_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+            .collect();
+        Some(PathBuf::from(format!(
+            "{}\\{}",
+            copy.device,
+            rest.display()
+        )))
+    }
+}
+
+impl Drop for ShadowSet {
+    fn drop(&mut self) {
+        for copy in &self.copies {
+            if let Err(e) = delete_shadow(&copy.id) {
+                elog!(
+                    "ERROR: shadow copy {} of {}:\\ wasn't deleted: {e}",
+                    copy.id,
+                    copy.drive as char
+                );
+            }
+        }
+    }
+}
+
+/// `C:\Users\…` -> b'C', None for network paths and anything else without a drive letter
+fn drive_of(path: &Path) -> Option<u8> {
+    match path.components().next()? {
+        Component::Prefix(p) => match p.kind() {
+            Prefix::Disk(d) | Prefix::VerbatimDisk(d) => Some(d.to_ascii_uppercase()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Win32_ShadowCopy.Create's return codes, the ones worth telling apart
+fn create_error(code: &str) -> String {
+    match code {
+        "1" => "access denied, run Konserve as administrator".into(),
+        "4" | "5" => "the volume doesn't support shadow copies".into(),
+        "6" => "not enough free space for a shadow copy".into(),
+        "8" => "too many shadow copies on the volume already".into(),
+        "9" => "another shadow copy is being made right now".into(),
+        _ => format!("Win32_ShadowCopy.Create returned {code}"),
+    }
+}
+
+/// (id, device object) of a fresh ClientAccessible snapshot of `volume`
+#[cfg(target_os = "windows")]
+fn create_shadow(volume: &str) -> Result<(String, String), String> {
+    let script = format!(
+        "$r = Invoke-CimMethod -ClassName Win32_ShadowCopy -MethodName Create \
+         -Arguments @{{ Volume = '{volume}'; Context = 'ClientAccessible' }}; \
+         if ($r.ReturnValue -ne 0) {{ \"ERR|$($r.ReturnValue)\"; exit }}; \
+         $s = Get-CimInstance -ClassName Win32_ShadowCopy -Filter \"ID='$($r.ShadowID)'\"; \
+         \"OK|$($s.ID)|$($s.DeviceObject)\""
+    );
+    let out = powershell(&script)?;
+    for line in out.lines() {
+        let mut parts = line.trim().split('|');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("OK"), Some(id), Some(device)) if !device.is_empty() => {
+                return Ok((id.to_string(), device.to_string()));
+            }
+            (Some("ERR"), Some(code), _) => return Err(create_error(code)),
+            _ => {}
+        }
+    }
+    Err("no answer from Win32_ShadowCopy".into())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn create_shadow(_volume: &str) -> Result<(String, String), String> {
+    Err(create_error("4"))
+}
+
+#[cfg(target_os = "windows")]
+fn delete_shadow(id: &str) -> Result<(), String> {
+    // came out of wmi, but it still ends up inside a script
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_hexdigit() || "{}-".contains(c))
+    {
+        return Err(format!("odd shadow copy id {id}"));
+    }
+    powershell(&format!(
+        "Get-CimInstance -ClassName Win32_ShadowCopy -Filter \"ID='{id}'\" | Remove-CimInstance"
+    ))
+    .map(|_| ())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn delete_shadow(_id: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// runs `script` without a console window popping up, stdout on success, the first line of
+/// stderr otherwise
+#[cfg(target_os = "windows")]
+fn powershell(script: &str) -> Result<String, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("can't run powershell: {e}"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // wmi errors (access denied when not elevated) land here instead of in ReturnValue
+    if let Some(line) = stderr.lines().map(str::trim).find(|l| !l.is_empty()) {
+        return Err(line.to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
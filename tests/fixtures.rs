@@ -0,0 +1,198 @@
+//! every fixture kind run through restore and verify, so what each one is meant to trip stays
+//! tripped and what should still work keeps working
+use konserve::{
+    fixtures::{FixtureKind, FixtureRoot, FixtureShape, fixture_roots, write_fixture},
+    helpers::{ConflictResolutionMode, Progress, build_human_tree, list_archive, translate_path},
+    restore::{RestoreOptions, restore_backup},
+    verify::verify_archive,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Once},
+};
+
+const SHAPE: FixtureShape = FixtureShape {
+    width: 2,
+    depth: 1,
+    files: 2,
+};
+
+/// the fixture home is shared, tests that write into it take turns
+static HOME: Mutex<()> = Mutex::new(());
+
+/// job logs (and the rest of konserve's config folder) go to a temp dir instead of the real one
+fn use_temp_config() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let dir = std::env::temp_dir().join("konserve-tests/config");
+        // SAFETY: every test comes through here before konserve reads the environment, and
+        // call_once holds them all until the variable is set
+        unsafe { std::env::set_var("KONSERVE_CONFIG_DIR", dir) };
+    });
+}
+
+/// writes a fixture into its own spot so tests running side by side don't share archives
+fn fixture(kind: FixtureKind, name: &str) -> PathBuf {
+    use_temp_config();
+    let out = std::env::temp_dir()
+        .join("konserve-tests")
+        .join(format!("{name}.tar"));
+    write_fixture(kind, SHAPE, &out).expect("fixture should write");
+    out
+}
+
+fn restore(archive: &Path) -> Result<String, String> {
+    let status = Arc::new(Mutex::new(String::new()));
+    let opts = RestoreOptions {
+        mode: ConflictResolutionMode::Overwrite,
+        ..Default::default()
+    };
+    restore_backup(archive, None, status.clone(), &Progress::new(), &opts, None)?;
+    Ok(status.lock().unwrap().clone())
+}
+
+/// every file of every root is on disk with what got packed
+fn assert_restored(roots: &[FixtureRoot]) {
+    for root in roots {
+        for (rel, data) in &root.files {
+            let path = match root.single_file {
+                true => root.path.clone(),
+                false => root.path.join(rel),
+            };
+            let got = fs::read(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            assert_eq!(&got, data, "{} differs", path.display());
+        }
+    }
+}
+
+fn clear(roots: &[FixtureRoot]) {
+    for root in roots {
+        let _ = match root.single_file {
+            true => fs::remove_file(&root.path),
+            false => fs::remove_dir_all(&root.path),
+        };
+    }
+}
+
+fn file_count(roots: &[FixtureRoot]) -> usize {
+    roots.iter().map(|r| r.files.len()).sum()
+}
+
+#[test]
+fn plain_restores_and_verifies() {
+    let _home = HOME.lock().unwrap_or_else(|e| e.into_inner());
+    let archive = fixture(FixtureKind::Plain, "plain");
+    let roots = fixture_roots(FixtureKind::Plain, SHAPE);
+
+    let report = verify_archive(&archive, &Progress::new(), false).unwrap();
+    assert!(report.is_ok());
+    assert!(report.empty_files.is_empty());
+    assert_eq!(report.ok, file_count(&roots));
+
+    clear(&roots);
+    let status = restore(&archive).unwrap();
+    assert!(status.starts_with("✅"), "{status}");
+    assert_restored(&roots);
+}
+
+#[test]
+fn obfuscated_restores_and_verifies() {
+    let _home = HOME.lock().unwrap_or_else(|e| e.into_inner());
+    let archive = fixture(FixtureKind::Obfuscated, "obfuscated");
+    let roots = fixture_roots(FixtureKind::Obfuscated, SHAPE);
+
+    let report = verify_archive(&archive, &Progress::new(), false).unwrap();
+    assert!(report.is_ok() && !report.no_checksums);
+    assert_eq!(report.ok, file_count(&roots));
+
+    clear(&roots);
+    restore(&archive).unwrap();
+    assert_restored(&roots);
+}
+
+#[test]
+fn colliding_roots_stay_apart() {
+    let _home = HOME.lock().unwrap_or_else(|e| e.into_inner());
+    let archive = fixture(FixtureKind::Collisions, "collisions");
+    let roots = fixture_roots(FixtureKind::Collisions, SHAPE);
+
+    // both zen folders and both picks of Documents show up as roots of their own
+    let tree = build_human_tree(list_archive(&archive, false).unwrap(), false);
+    assert_eq!(tree.children.len(), roots.len());
+    let uuids: HashSet<_> = tree
+        .children
+        .values()
+        .filter_map(|n| n.uuid.clone())
+        .collect();
+    assert_eq!(uuids.len(), roots.len());
+    let mut zens: Vec<_> = tree
+        .children
+        .values()
+        .filter_map(|n| n.label.as_deref().filter(|l| l.starts_with("zen")))
+        .collect();
+    zens.sort();
+    assert_eq!(zens, ["zen  (Local)", "zen  (Roaming)"]);
+
+    assert!(
+        verify_archive(&archive, &Progress::new(), false)
+            .unwrap()
+            .is_ok()
+    );
+
+    clear(&roots);
+    restore(&archive).unwrap();
+    assert_restored(&roots);
+}
+
+#[test]
+fn wrong_fingerprint_is_refused() {
+    let archive = fixture(FixtureKind::WrongFingerprint, "wrong_fingerprint");
+    let err = restore(&archive).unwrap_err();
+    assert!(err.contains("fingerprint"), "{err}");
+}
+
+#[test]
+fn other_os_paths_land_in_this_home() {
+    let archive = fixture(FixtureKind::OtherOs, "other_os");
+    let listing = list_archive(&archive, false).unwrap();
+    assert_eq!(listing.path_map.len(), 2);
+
+    // each root has exactly one spot under this machine's home
+    let home = std::env::temp_dir().join("konserve-tests").join("home");
+    for original in listing.path_map.values() {
+        let mapped = translate_path(original, &home);
+        assert_eq!(mapped.len(), 1, "{}", original.display());
+        assert!(
+            mapped[0].starts_with(&home),
+            "{} went to {}",
+            original.display(),
+            mapped[0].display()
+        );
+    }
+    assert!(
+        verify_archive(&archive, &Progress::new(), false)
+            .unwrap()
+            .is_ok()
+    );
+}
+
+#[test]
+fn bad_checksum_is_reported() {
+    let archive = fixture(FixtureKind::BadChecksum, "bad_checksum");
+    let roots = fixture_roots(FixtureKind::BadChecksum, SHAPE);
+    let report = verify_archive(&archive, &Progress::new(), false).unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.corrupted.len(), 1);
+    assert!(report.missing.is_empty());
+    assert_eq!(report.ok, file_count(&roots) - 1);
+}
+
+#[test]
+fn truncated_fails_verify_and_restore() {
+    let _home = HOME.lock().unwrap_or_else(|e| e.into_inner());
+    let archive = fixture(FixtureKind::Truncated, "truncated");
+    assert!(verify_archive(&archive, &Progress::new(), false).is_err());
+    assert!(restore(&archive).is_err());
+}